@@ -36,7 +36,7 @@ async fn main() -> sqlx::Result<()> {
     // -----------------------
     // ORM bootstrap
     // -----------------------
-    let mut orm = OrmStruct::new(
+    let orm = OrmStruct::new(
         "postgres://postgres@localhost:5432/postgres".into(),
         vec![User::slint_schema(), Postsx::slint_schema()],
     );
@@ -44,6 +44,9 @@ async fn main() -> sqlx::Result<()> {
     orm.connect().await?;
     orm.migrate().await?;
 
+    orm.ping().await?;
+    println!("Pool status: {:?}", orm.pool_status());
+
     // =======================
     // OLD ORM API (direct)
     // =======================
@@ -74,17 +77,17 @@ async fn main() -> sqlx::Result<()> {
     // =======================
 
     let user_table = Table::<User>::new(&orm, "userx_table", "email");
-    let post_table = Table::<Postsx>::new(&orm, "postsx_table", "id");
+    let _post_table = Table::<Postsx>::from_schema(&orm);
 
     // -----------------------
     // Insert
     // -----------------------
     user_table
-        .insert(&User {
-            id: "".into(),
-            name: "Grace".into(),
-            email: "grace@mail.com".into(),
-        })
+        .insert_new(&NewUser::new(
+            "".into(),
+            "Grace".into(),
+            "grace@mail.com".into(),
+        ))
         .await?;
 
     // -----------------------
@@ -118,8 +121,8 @@ async fn main() -> sqlx::Result<()> {
 
     let queried = user_table
         .query()
-        .where_clause("name", "LIKE", "%Ada%")
-        .order_by("name", "ASC")
+        .ilike_with("name", "ada", Wildcard::Suffix)
+        .order_by("name", Direction::Asc)
         .limit(10)
         .get()
         .await?;
@@ -154,5 +157,8 @@ async fn main() -> sqlx::Result<()> {
         .await?;
     println!("First_Value via new Query API: {:?}", first_userx);
 
+    orm.close().await;
+    println!("Connected after close: {}", orm.is_connected());
+
     Ok(())
 }
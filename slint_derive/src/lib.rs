@@ -2,56 +2,232 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Meta, Lit, Expr, token, parse::ParseBuffer};
+use syn::{
+    DeriveInput, Expr, Lit, Meta,
+    parse::{ParseBuffer, Parser},
+    punctuated::Punctuated,
+    token,
+};
+
+/// Table options written bare, e.g. `#[slint(temporary)]`.
+const TABLE_FLAGS: &[&str] = &[
+    "temporary",
+    "unlogged",
+    "blame",
+    "versioned",
+    "active_record",
+];
+/// Table options written `name = value`.
+const TABLE_VALUES: &[&str] = &["table_name", "comment"];
+/// Field options written bare, e.g. `#[slint(unique)]`.
+const FIELD_FLAGS: &[&str] = &[
+    "uuid",
+    "primary",
+    "primary_key",
+    "unique",
+    "unique_ci",
+    "cidr",
+    "macaddr",
+    "deferrable",
+    "deferred",
+    "not_null",
+    "encrypted",
+    "masked",
+    "serial",
+    "trigram",
+];
+/// Field options written `name = value`.
+const FIELD_VALUES: &[&str] = &[
+    "sql_type",
+    "default",
+    "generated",
+    "foreign_key",
+    "comment",
+    "collate",
+    "hashed",
+    "unique_ci",
+    "length",
+    "precision",
+    "scale",
+];
 
 #[proc_macro_attribute]
 pub fn slint(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut input = parse_macro_input!(item as DeriveInput);
+    expand(attr, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(attr: TokenStream, item: TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let mut input: DeriveInput = syn::parse(item)?;
     let struct_name = &input.ident;
 
-    // -------- table_name parsing --------
+    // -------- table options parsing --------
     let mut table_name = struct_name.to_string().to_lowercase();
-    if !attr.is_empty() {
-        let meta = parse_macro_input!(attr as Meta);
-        if let Meta::NameValue(nv) = meta {
-            if nv.path.is_ident("table_name") {
-                if let Expr::Lit(expr_lit) = nv.value {
-                    if let Lit::Str(litstr) = expr_lit.lit {
-                        table_name = litstr.value();
-                    }
-                }
+    let mut explicit_name = false;
+    let mut table_comment: Option<String> = None;
+    let mut temporary = false;
+    let mut unlogged = false;
+    let mut blame = false;
+    let mut versioned = false;
+    let mut active_record = false;
+    let metas = Punctuated::<Meta, token::Comma>::parse_terminated.parse(attr)?;
+    for meta in metas {
+        let name = option_name(&meta)?;
+        match (name.as_str(), &meta) {
+            ("table_name", Meta::NameValue(nv)) => {
+                table_name = str_value(&name, &nv.value)?;
+                explicit_name = true;
             }
+            ("comment", Meta::NameValue(nv)) => table_comment = Some(str_value(&name, &nv.value)?),
+            ("temporary", Meta::Path(_)) => temporary = true,
+            ("unlogged", Meta::Path(_)) => unlogged = true,
+            ("blame", Meta::Path(_)) => blame = true,
+            ("versioned", Meta::Path(_)) => versioned = true,
+            ("active_record", Meta::Path(_)) => active_record = true,
+            _ => return Err(option_error(&meta, &name, TABLE_FLAGS, TABLE_VALUES)),
         }
     }
+    if temporary && unlogged {
+        return Err(syn::Error::new_spanned(
+            struct_name,
+            "a table cannot be both `temporary` and `unlogged`",
+        ));
+    }
 
     // Remove the #[slint] attribute from the struct
     input.attrs.retain(|attr| !attr.path().is_ident("slint"));
 
     // -------- fields --------
     let fields = match &input.data {
-        syn::Data::Struct(s) => s.fields.iter().collect::<Vec<_>>(),
-        _ => vec![],
+        syn::Data::Struct(s) if matches!(s.fields, syn::Fields::Named(_)) => {
+            s.fields.iter().cloned().collect::<Vec<_>>()
+        }
+        syn::Data::Struct(_) => {
+            return Err(syn::Error::new_spanned(
+                struct_name,
+                "`#[slint]` needs named fields to name the columns; tuple and unit structs aren't supported",
+            ));
+        }
+        syn::Data::Union(u) => {
+            return Err(syn::Error::new_spanned(
+                u.union_token,
+                "`#[slint]` doesn't support unions",
+            ));
+        }
+        syn::Data::Enum(e) => {
+            if blame || versioned || active_record {
+                return Err(syn::Error::new_spanned(
+                    struct_name,
+                    "`blame`, `versioned` and `active_record` don't apply to enum lookup tables",
+                ));
+            }
+            let table_comment = option_tokens(table_comment);
+            let values = lookup_values(&input.attrs, e)?;
+            return Ok(quote! {
+                #input
+
+                impl #struct_name {
+                    pub fn slint_schema() -> TableSchema {
+                        TableSchema {
+                            name: #table_name,
+                            model: stringify!(#struct_name),
+                            explicit_name: #explicit_name,
+                            columns: &[ColumnSchema {
+                                name: "name",
+                                sql_type: "TEXT",
+                                primary: true,
+                                unique: false,
+                                unique_ci: false,
+                                deferrable: false,
+                                initially_deferred: false,
+                                not_null: true,
+                                uuid: false,
+                                length: None,
+                                comment: None,
+                                collation: None,
+                                encrypted: false,
+                                hashed: None,
+                                masked: false,
+                                default: None,
+                                generated: None,
+                                serial: false,
+                                foreign_key: None,
+                                trigram: false,
+                            }],
+                            comment: #table_comment,
+                            temporary: #temporary,
+                            unlogged: #unlogged,
+                            blame: false,
+                            versioned: false,
+                            lookup_values: &[#(#values),*],
+                        }
+                    }
+                }
+
+                impl SlintModel for #struct_name {
+                    fn slint_schema() -> TableSchema {
+                        #struct_name::slint_schema()
+                    }
+                }
+            });
+        }
     };
 
+    // Field-level #[slint(...)] options are only read here, so strip them
+    // from the emitted struct.
+    if let syn::Data::Struct(s) = &mut input.data {
+        for f in s.fields.iter_mut() {
+            f.attrs.retain(|attr| {
+                !attr.path().is_ident("slint") && !attr.path().is_ident("slint_internal_field")
+            });
+        }
+    }
+
+    let field_names: Vec<String> = fields
+        .iter()
+        .filter_map(|f| f.ident.as_ref().map(|i| i.to_string()))
+        .collect();
     let mut cols = Vec::new();
+    let mut verifiers = Vec::new();
+    // Insert builder fields: constructor arguments and optional setters
+    let mut required_fields = Vec::new();
+    let mut optional_fields = Vec::new();
 
     for f in fields {
-    let col_name = f.ident.as_ref().unwrap().to_string();
-    let sql_type = "TEXT".to_string();
-    let mut primary = false;
-    let mut unique = false;
-    let mut uuid = false;
-    let mut not_null = true;
-
-    // Optional extra fields
-    let mut default: Option<String> = None;
-    let mut foreign_key: Option<String> = None;
-    let mut relationship: Option<String> = None;
-
-    for attr in &f.attrs {
-
-        // Existing attribute parsing
-        if attr.path().is_ident("slint") {
+        let col_name = f.ident.as_ref().unwrap().to_string();
+        let mut sql_type = "TEXT".to_string();
+        let mut primary = false;
+        let mut unique = false;
+        let mut unique_ci = false;
+        let mut deferrable = false;
+        let mut initially_deferred = false;
+        let mut uuid = false;
+        let mut not_null = true;
+        let mut encrypted = false;
+        let mut masked = false;
+        let mut serial = false;
+        let mut trigram = false;
+        let mut hashed: Option<String> = None;
+
+        // Optional extra fields
+        let mut default: Option<String> = None;
+        let mut generated: Option<String> = None;
+        let mut foreign_key: Option<String> = None;
+        let mut comment: Option<String> = None;
+        let mut collation: Option<String> = None;
+
+        // Sizing options, folded into the SQL type below
+        let mut length: Option<u32> = None;
+        let mut precision: Option<u32> = None;
+        let mut scale: Option<u32> = None;
+
+        // `#[slint(...)]` and the `#[slint_field(...)]` rewrite accept the same options
+        let field_attrs = f.attrs.iter().filter(|attr| {
+            attr.path().is_ident("slint") || attr.path().is_ident("slint_internal_field")
+        });
+
+        for attr in field_attrs {
             let metas = attr.parse_args_with(|input: &ParseBuffer| {
                 let mut metas = Vec::new();
                 while !input.is_empty() {
@@ -61,114 +237,294 @@ pub fn slint(attr: TokenStream, item: TokenStream) -> TokenStream {
                     }
                 }
                 Ok(metas)
-            }).unwrap();
+            })?;
             for meta in metas {
-                match meta {
-                    Meta::NameValue(nv) => {
-                        let ident = nv.path.get_ident().unwrap().to_string();
-                        match ident.as_str() {
-                            "default" => {
-                                if let Expr::Lit(expr_lit) = nv.value {
-                                    if let Lit::Str(litstr) = expr_lit.lit {
-                                        default = Some(litstr.value());
-                                    }
-                                }
-                            }
-                            "foreign_key" => {
-                                if let Expr::Lit(expr_lit) = nv.value {
-                                    if let Lit::Str(litstr) = expr_lit.lit {
-                                        foreign_key = Some(litstr.value());
-                                    }
+                let name = option_name(&meta)?;
+                match (name.as_str(), &meta) {
+                    (_, Meta::NameValue(nv)) if FIELD_VALUES.contains(&name.as_str()) => {
+                        let value = &nv.value;
+                        match name.as_str() {
+                            "sql_type" => sql_type = str_value(&name, value)?,
+                            "default" => default = Some(str_value(&name, value)?),
+                            "generated" => generated = Some(str_value(&name, value)?),
+                            "foreign_key" => foreign_key = Some(str_value(&name, value)?),
+                            "comment" => comment = Some(str_value(&name, value)?),
+                            "collate" => collation = Some(str_value(&name, value)?),
+                            "hashed" => {
+                                if str_value(&name, value)? != "argon2" {
+                                    return Err(syn::Error::new_spanned(
+                                        value,
+                                        "unsupported hash algorithm; expected `hashed = \"argon2\"`",
+                                    ));
                                 }
+                                hashed = Some("argon2".to_string());
                             }
-                            "relationship" => {
-                                if let Expr::Lit(expr_lit) = nv.value {
-                                    if let Lit::Str(litstr) = expr_lit.lit {
-                                        relationship = Some(litstr.value());
-                                    }
+                            "unique_ci" => {
+                                if str_value(&name, value)? != "citext" {
+                                    return Err(syn::Error::new_spanned(
+                                        value,
+                                        "unsupported `unique_ci` mode; expected `unique_ci = \"citext\"`",
+                                    ));
                                 }
+                                sql_type = "CITEXT".to_string();
+                                unique = true;
                             }
-                            _ => {}
+                            "length" => length = Some(int_value(&name, value)?),
+                            "precision" => precision = Some(int_value(&name, value)?),
+                            _ => scale = Some(int_value(&name, value)?),
                         }
                     }
-                    Meta::Path(path) => {
-                        let ident = path.get_ident().unwrap().to_string();
-                        match ident.as_str() {
-                            "uuid" => { uuid = true; primary = true; }
-                            "primary" => primary = true,
+                    (_, Meta::Path(_)) if FIELD_FLAGS.contains(&name.as_str()) => {
+                        match name.as_str() {
+                            "uuid" => {
+                                uuid = true;
+                                primary = true;
+                            }
+                            "primary" | "primary_key" => primary = true,
                             "unique" => unique = true,
+                            "unique_ci" => unique_ci = true,
+                            "cidr" => sql_type = "CIDR".to_string(),
+                            "macaddr" => sql_type = "MACADDR".to_string(),
+                            "deferrable" => deferrable = true,
+                            "deferred" => {
+                                deferrable = true;
+                                initially_deferred = true;
+                            }
                             "not_null" => not_null = true,
-                            _ => {}
+                            "encrypted" => encrypted = true,
+                            "masked" => masked = true,
+                            "trigram" => trigram = true,
+                            _ => {
+                                serial = true;
+                                primary = true;
+                            }
                         }
                     }
-                    _ => {}
+                    ("relationship", _) => {
+                        return Err(syn::Error::new_spanned(
+                            &meta,
+                            "`relationship` is not supported; link tables with `foreign_key = \"table.column\"`",
+                        ));
+                    }
+                    _ => return Err(option_error(&meta, &name, FIELD_FLAGS, FIELD_VALUES)),
                 }
             }
         }
 
-        // New: field-level macro
-        if attr.path().is_ident("slint_internal_field") {
-            let metas = attr.parse_args_with(|input: &ParseBuffer| {
-                let mut metas = Vec::new();
-                while !input.is_empty() {
-                    metas.push(input.parse::<Meta>()?);
-                    if !input.is_empty() {
-                        input.parse::<token::Comma>()?;
-                    }
-                }
-                Ok(metas)
-            }).unwrap();
-            for meta in metas {
-                match meta {
-                    Meta::NameValue(nv) => {
-                        let ident = nv.path.get_ident().unwrap().to_string();
-                        match ident.as_str() {
-                            "foreign_key" => {
-                                if let Expr::Lit(expr_lit) = nv.value {
-                                    if let Lit::Str(litstr) = expr_lit.lit {
-                                        foreign_key = Some(litstr.value());
-                                    }
-                                }
-                            }
-                            "relationship" => {
-                                if let Expr::Lit(expr_lit) = nv.value {
-                                    if let Lit::Str(litstr) = expr_lit.lit {
-                                        relationship = Some(litstr.value());
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                    Meta::Path(path) => {
-                        let ident = path.get_ident().unwrap().to_string();
-                        match ident.as_str() {
-                            "primary_key" => primary = true,
-                            "uuid" => { uuid = true; primary = true; }
-                            "unique" => unique = true,
-                            "not_null" => not_null = true,
-                            _ => {}
-                        }
-                    }
-                    _ => {}
-                }
+        let ident = f.ident.as_ref().unwrap();
+        let conflict = |message: &str| Err(syn::Error::new_spanned(ident, message));
+        if deferrable && !primary && !unique && foreign_key.is_none() {
+            return conflict(
+                "`deferrable` and `deferred` need a `unique`, `primary` or `foreign_key` column",
+            );
+        }
+        if uuid && serial {
+            return conflict("`uuid` and `serial` keys are exclusive; pick one");
+        }
+        if default.is_some() && generated.is_some() {
+            return conflict("a `generated` column cannot also have a `default`");
+        }
+        if encrypted && hashed.is_some() {
+            return conflict("`encrypted` and `hashed` are exclusive: hashes cannot be decrypted");
+        }
+        if trigram && (encrypted || hashed.is_some()) {
+            return conflict(
+                "`trigram` indexes the stored text, which `encrypted` and `hashed` columns don't have",
+            );
+        }
+        if length.is_some() && precision.is_some() {
+            return conflict("`length` is for text and `precision` for numeric columns; use one");
+        }
+        if scale.is_some() && precision.is_none() {
+            return conflict("`scale` needs a `precision`, e.g. `precision = 10, scale = 2`");
+        }
+        if uuid && !is_type(&f.ty, &["String", "Uuid"]) {
+            return Err(syn::Error::new_spanned(
+                &f.ty,
+                "`uuid` keys are generated as strings; the field must be a `String` or `Uuid`",
+            ));
+        }
+        if serial
+            && !is_type(
+                &f.ty,
+                &["i16", "i32", "i64", "u32", "u64", "isize", "usize"],
+            )
+        {
+            return Err(syn::Error::new_spanned(
+                &f.ty,
+                "`serial` keys are assigned integers; the field must be an integer type",
+            ));
+        }
+        if hashed.is_some() && !is_type(&f.ty, &["String"]) {
+            return Err(syn::Error::new_spanned(
+                &f.ty,
+                "`hashed` columns hold a hash string; the field must be a `String`",
+            ));
+        }
+        if let Some(n) = length {
+            sql_type = format!("VARCHAR({})", n);
+        }
+        if let Some(p) = precision {
+            sql_type = match scale {
+                Some(s) => format!("NUMERIC({},{})", p, s),
+                None => format!("NUMERIC({})", p),
+            };
+        }
+        // Addresses and ranges get their Postgres type unless another was given.
+        if sql_type == "TEXT"
+            && let Some(inferred) = inferred_sql_type(&f.ty)
+        {
+            sql_type = inferred.to_string();
+        }
+        // Identity keys default to BIGINT unless an integer type was given.
+        if serial && sql_type == "TEXT" {
+            sql_type = "BIGINT".to_string();
+        }
+        // Ciphertext and password hashes are stored as text whatever the field's type.
+        if encrypted || hashed.is_some() {
+            sql_type = "TEXT".to_string();
+        }
+        if hashed.is_some() {
+            let verify_fn = syn::Ident::new(
+                &format!("verify_{}", col_name),
+                f.ident.as_ref().unwrap().span(),
+            );
+            verifiers.push(quote! {
+            /// Checks `candidate` against the stored hash of this field.
+            pub async fn #verify_fn(record: &Record<#struct_name>, candidate: &str) -> sqlx::Result<bool> {
+                record.verify_hashed(#col_name, candidate).await
             }
+        });
+        }
+        // Server-generated or defaulted columns may be left out of an insert.
+        let field_ident = f.ident.clone().unwrap();
+        let field_ty = f.ty.clone();
+        if uuid
+            || serial
+            || default.is_some()
+            || generated.is_some()
+            || sql_type.to_uppercase().starts_with("TIMESTAMP")
+        {
+            optional_fields.push((field_ident, field_ty));
+        } else {
+            required_fields.push((field_ident, field_ty));
         }
+
+        let length = option_tokens(length);
+        let comment = option_tokens(comment);
+        let collation = option_tokens(collation);
+        let hashed = option_tokens(hashed);
+        let default = option_tokens(default);
+        let generated = option_tokens(generated);
+        let foreign_key = option_tokens(foreign_key);
+
+        // Build ColumnSchema
+        cols.push(quote! {
+            ColumnSchema {
+                name: #col_name,
+                sql_type: #sql_type,
+                primary: #primary,
+                unique: #unique,
+                unique_ci: #unique_ci,
+                deferrable: #deferrable,
+                initially_deferred: #initially_deferred,
+                not_null: #not_null,
+                uuid: #uuid,
+                length: #length,
+                comment: #comment,
+                collation: #collation,
+                encrypted: #encrypted,
+                hashed: #hashed,
+                masked: #masked,
+                default: #default,
+                generated: #generated,
+                serial: #serial,
+                foreign_key: #foreign_key,
+                trigram: #trigram,
+            }
+        });
     }
 
-    // Build ColumnSchema
-    cols.push(quote! {
-        ColumnSchema {
-            name: #col_name,
-            sql_type: #sql_type,
-            primary: #primary,
-            unique: #unique,
-            not_null: #not_null,
-            uuid: #uuid,
+    // Audit columns of `blame` tables, unless the model declares them itself.
+    if blame {
+        for audit_col in ["created_by", "updated_by"] {
+            if !field_names.iter().any(|n| n == audit_col) {
+                cols.push(quote! {
+                    ColumnSchema {
+                        name: #audit_col,
+                        sql_type: "TEXT",
+                        primary: false,
+                        unique: false,
+                        unique_ci: false,
+                        deferrable: false,
+                        initially_deferred: false,
+                        not_null: false,
+                        uuid: false,
+                        length: None,
+                        comment: None,
+                        collation: None,
+                        encrypted: false,
+                        hashed: None,
+                        masked: false,
+                        default: None,
+                        generated: None,
+                        serial: false,
+                        foreign_key: None,
+                        trigram: false,
+                    }
+                });
+            }
         }
-    });
-}
+    }
+
+    let table_comment = option_tokens(table_comment);
+
+    // -------- insert builder --------
+    let vis = &input.vis;
+    let new_name = syn::Ident::new(&format!("New{}", struct_name), struct_name.span());
+    let new_doc = format!(
+        "Insert builder for [`{}`]: required columns are arguments of `new`, \
+         generated and defaulted ones are optional setters.",
+        struct_name
+    );
+    let required_idents: Vec<_> = required_fields.iter().map(|(i, _)| i).collect();
+    let required_tys: Vec<_> = required_fields.iter().map(|(_, t)| t).collect();
+    let optional_idents: Vec<_> = optional_fields.iter().map(|(i, _)| i).collect();
+    let optional_tys: Vec<_> = optional_fields.iter().map(|(_, t)| t).collect();
+
+    // -------- active record functions --------
+    let active_record_fns = if active_record {
+        quote! {
+            /// Typed handle to this model's table.
+            pub fn table(orm: &OrmStruct) -> Table<Self> {
+                Table::from_schema(orm)
+            }
 
+            /// The row whose key column equals `key`.
+            pub async fn find(orm: &OrmStruct, key: impl Into<SqlValue>) -> sqlx::Result<Option<Self>> {
+                Ok(Self::table(orm).find(key).await?.map(|record| record.value))
+            }
+
+            /// Every row of the table.
+            pub async fn all(orm: &OrmStruct) -> sqlx::Result<Vec<Self>> {
+                let records = Self::table(orm).get_all().await?;
+                Ok(records.into_iter().map(|record| record.value).collect())
+            }
+
+            /// Insert `self` as a new row.
+            pub async fn create(&self, orm: &OrmStruct) -> sqlx::Result<()> {
+                Self::table(orm).insert(self).await
+            }
+
+            /// Delete the row whose key column equals `key`, returning
+            /// whether there was one.
+            pub async fn delete_by_id(orm: &OrmStruct, key: impl Into<SqlValue>) -> sqlx::Result<bool> {
+                Self::table(orm).delete_by_key(key).await
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     // -------- generate output --------
     let expanded = quote! {
@@ -178,20 +534,294 @@ pub fn slint(attr: TokenStream, item: TokenStream) -> TokenStream {
             pub fn slint_schema() -> TableSchema {
                 TableSchema {
                     name: #table_name,
+                    model: stringify!(#struct_name),
+                    explicit_name: #explicit_name,
                     columns: &[#(#cols),*],
+                    comment: #table_comment,
+                    temporary: #temporary,
+                    unlogged: #unlogged,
+                    blame: #blame,
+                    versioned: #versioned,
+                    lookup_values: &[],
                 }
             }
+
+            #(#verifiers)*
+
+            #active_record_fns
+        }
+
+        impl SlintModel for #struct_name {
+            fn slint_schema() -> TableSchema {
+                #struct_name::slint_schema()
+            }
+        }
+
+        #[doc = #new_doc]
+        #[derive(Serialize)]
+        #vis struct #new_name {
+            #(pub #required_idents: #required_tys,)*
+            #(
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub #optional_idents: Option<#optional_tys>,
+            )*
+        }
+
+        impl #new_name {
+            #[allow(clippy::too_many_arguments)]
+            pub fn new(#(#required_idents: #required_tys),*) -> Self {
+                Self {
+                    #(#required_idents,)*
+                    #(#optional_idents: None,)*
+                }
+            }
+
+            #(
+                pub fn #optional_idents(mut self, value: #optional_tys) -> Self {
+                    self.#optional_idents = Some(value);
+                    self
+                }
+            )*
+        }
+
+        impl Insertable for #new_name {
+            type Model = #struct_name;
+        }
+    };
+
+    Ok(expanded)
+}
+
+/// The values of a lookup-table enum's rows: its variant names as serde
+/// writes them, following `#[serde(rename = "...")]` on variants and
+/// `#[serde(rename_all = "...")]` on the enum.
+fn lookup_values(attrs: &[syn::Attribute], data: &syn::DataEnum) -> syn::Result<Vec<String>> {
+    let rename_all = serde_option(attrs, "rename_all");
+    data.variants
+        .iter()
+        .map(|variant| {
+            if !matches!(variant.fields, syn::Fields::Unit) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "enum lookup tables can only have unit variants",
+                ));
+            }
+            let name = variant.ident.to_string();
+            Ok(serde_option(&variant.attrs, "rename")
+                .or_else(|| {
+                    rename_all
+                        .as_deref()
+                        .map(|rule| rename_variant(&name, rule))
+                })
+                .unwrap_or(name))
+        })
+        .collect()
+}
+
+/// String value of `#[serde(name = "...")]` among `attrs`.
+fn serde_option(attrs: &[syn::Attribute], name: &str) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Meta, token::Comma>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .find_map(|meta| match meta {
+            Meta::NameValue(nv) if nv.path.is_ident(name) => lit_str(&nv.value),
+            _ => None,
+        })
+}
+
+/// A `PascalCase` variant name under a serde `rename_all` rule.
+fn rename_variant(name: &str, rule: &str) -> String {
+    let snake = || {
+        let mut out = String::new();
+        for (i, c) in name.chars().enumerate() {
+            if i > 0 && c.is_uppercase() {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
         }
+        out
     };
+    match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "camelCase" => {
+            let mut chars = name.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_lowercase().chain(chars).collect()
+            })
+        }
+        "snake_case" => snake(),
+        "SCREAMING_SNAKE_CASE" => snake().to_uppercase(),
+        "kebab-case" => snake().replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => snake().to_uppercase().replace('_', "-"),
+        _ => name.to_string(),
+    }
+}
 
-    TokenStream::from(expanded)
+/// Name of a `#[slint]` option, which must be a plain identifier.
+fn option_name(meta: &Meta) -> syn::Result<String> {
+    meta.path()
+        .get_ident()
+        .map(|ident| ident.to_string())
+        .ok_or_else(|| syn::Error::new_spanned(meta.path(), "expected a `#[slint]` option name"))
 }
 
+/// Error for option `name` written as `meta`, given the options written
+/// bare (`flags`) and those written `name = value` (`values`).
+fn option_error(meta: &Meta, name: &str, flags: &[&str], values: &[&str]) -> syn::Error {
+    let message = if values.contains(&name) && !matches!(meta, Meta::NameValue(_)) {
+        format!("`{}` needs a value: `{} = ...`", name, name)
+    } else if flags.contains(&name) && !matches!(meta, Meta::Path(_)) {
+        format!("`{}` takes no value", name)
+    } else {
+        let mut known: Vec<&str> = flags.iter().chain(values).copied().collect();
+        known.sort_unstable();
+        known.dedup();
+        format!(
+            "unknown `#[slint]` option `{}`; expected one of: {}",
+            name,
+            known.join(", ")
+        )
+    };
+    syn::Error::new_spanned(meta, message)
+}
 
+/// The string literal given to option `name`.
+fn str_value(name: &str, expr: &Expr) -> syn::Result<String> {
+    lit_str(expr).ok_or_else(|| {
+        syn::Error::new_spanned(expr, format!("`{}` expects a string literal", name))
+    })
+}
 
+/// The integer literal given to option `name`.
+fn int_value(name: &str, expr: &Expr) -> syn::Result<u32> {
+    lit_int(expr).ok_or_else(|| {
+        syn::Error::new_spanned(
+            expr,
+            format!("`{}` expects a positive integer literal", name),
+        )
+    })
+}
 
+/// Whether `ty`, or the `T` of an `Option<T>`, is named one of `names`.
+fn is_type(ty: &syn::Type, names: &[&str]) -> bool {
+    let Some(last) = last_segment(ty) else {
+        return false;
+    };
+    if last.ident == "Option" {
+        return type_argument(last).is_some_and(|inner| is_type(inner, names));
+    }
+    names.iter().any(|name| last.ident == name)
+}
 
+fn lit_str(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Str(litstr) => Some(litstr.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
+fn lit_int(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Int(litint) => litint.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// SQL type of fields whose Rust type has a Postgres counterpart other
+/// than `TEXT`, optionally in an `Option`: `IpAddr` (and `Ipv4Addr` /
+/// `Ipv6Addr`) is `INET`, `SqlRange<T>` the range type of `T`, a
+/// `HashMap` / `BTreeMap` of `String` to `String` (or `Option<String>`)
+/// `HSTORE` and, with the `geo` feature, `Point` / `Polygon` a PostGIS
+/// `GEOMETRY` in WGS 84.
+fn inferred_sql_type(ty: &syn::Type) -> Option<&'static str> {
+    let last = last_segment(ty)?;
+    match last.ident.to_string().as_str() {
+        "IpAddr" | "Ipv4Addr" | "Ipv6Addr" => Some("INET"),
+        #[cfg(feature = "geo")]
+        "Point" => Some("GEOMETRY(Point, 4326)"),
+        #[cfg(feature = "geo")]
+        "Polygon" => Some("GEOMETRY(Polygon, 4326)"),
+        "Option" => inferred_sql_type(type_argument(last)?),
+        "HashMap" | "BTreeMap" if is_string_map(last) => Some("HSTORE"),
+        "SqlRange" => match last_segment(type_argument(last)?)?
+            .ident
+            .to_string()
+            .as_str()
+        {
+            "i32" => Some("INT4RANGE"),
+            "i64" => Some("INT8RANGE"),
+            "NaiveDate" => Some("DATERANGE"),
+            "NaiveDateTime" => Some("TSRANGE"),
+            "DateTime" => Some("TSTZRANGE"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `segment` is a map with `String` keys and `String` or
+/// `Option<String>` values.
+fn is_string_map(segment: &syn::PathSegment) -> bool {
+    let is_string = |ty: &syn::Type| last_segment(ty).is_some_and(|s| s.ident == "String");
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return false;
+    };
+    let types: Vec<&syn::Type> = args
+        .args
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .collect();
+    match types.as_slice() {
+        [key, value, ..] => {
+            is_string(key)
+                && (is_string(value)
+                    || last_segment(value).is_some_and(|s| {
+                        s.ident == "Option" && type_argument(s).is_some_and(is_string)
+                    }))
+        }
+        _ => false,
+    }
+}
+
+fn last_segment(ty: &syn::Type) -> Option<&syn::PathSegment> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+/// First generic type argument of `segment`, e.g. `T` of `Option<T>`.
+fn type_argument(segment: &syn::PathSegment) -> Option<&syn::Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn option_tokens<T: quote::ToTokens>(value: Option<T>) -> proc_macro2::TokenStream {
+    match value {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
+    }
+}
 
 #[proc_macro_attribute]
 pub fn slint_field(attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -199,12 +829,11 @@ pub fn slint_field(attr: TokenStream, item: TokenStream) -> TokenStream {
     let field = item.to_string();
 
     // Rewrite into a normal attribute
-    let out = format!(
-        "#[slint_internal_field({})]\n{}",
-        args,
-        field
-    );
+    let out = format!("#[slint_internal_field({})]\n{}", args, field);
 
-    out.parse().unwrap()
+    out.parse().unwrap_or_else(|e: proc_macro::LexError| {
+        syn::Error::new(proc_macro2::Span::call_site(), e)
+            .to_compile_error()
+            .into()
+    })
 }
-
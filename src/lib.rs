@@ -2,4 +2,5 @@ mod libs;
 pub use libs::*;  
 
 pub use serde::{Serialize, Deserialize};
+#[cfg(feature = "derive")]
 pub use slint_derive::slint;
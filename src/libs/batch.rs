@@ -0,0 +1,258 @@
+use crate::OrmStruct;
+use crate::events::Change;
+use crate::orm::bind_json;
+use crate::row::columns_to_json;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{Column, Row};
+use std::ops::Range;
+
+/// Writes queued with `OrmStruct::batch` and sent to the database as one
+/// statement, in a single round trip.
+///
+/// The writes are combined into data-modifying CTEs, so they succeed or fail
+/// together and all see the table contents from before the batch: a queued
+/// update will not find a row inserted by the same batch.
+///
+/// Like single writes, a batch waits for the concurrency slots of its
+/// tables, goes through the circuit breaker, is counted in the query
+/// statistics once per write and reports the rows it wrote to event
+/// subscribers.
+pub struct Batch<'a> {
+    orm: &'a OrmStruct,
+    writes: Vec<Write>,
+    values: Vec<Value>,
+}
+
+/// One queued statement and what it does to which table.
+struct Write {
+    sql: String,
+    table: String,
+    change: Change,
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn new(orm: &'a OrmStruct) -> Self {
+        Self {
+            orm,
+            writes: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Queue an insert of `item`, like `OrmStruct::insert`. The table must
+    /// be registered; otherwise this fails with `InvalidArgument`.
+    pub fn insert<T>(&mut self, table_name: &str, item: &T) -> sqlx::Result<&mut Self>
+    where
+        T: Serialize,
+    {
+        let schema = self.orm.find_schema(table_name).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("table `{}` is not registered", table_name))
+        })?;
+        let (sql, values) =
+            self.orm
+                .insert_statement(schema, std::slice::from_ref(item), "", self.values.len())?;
+        Ok(self.push(table_name, Change::Insert, sql, values))
+    }
+
+    /// Queue an update of the rows where `column = value`, like
+    /// `OrmStruct::update`. The table must be registered; otherwise this
+    /// fails with `InvalidArgument`.
+    pub fn update<T>(
+        &mut self,
+        table_name: &str,
+        column: &str,
+        value: &str,
+        item: &T,
+    ) -> sqlx::Result<&mut Self>
+    where
+        T: Serialize,
+    {
+        let schema = self.orm.find_schema(table_name).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("table `{}` is not registered", table_name))
+        })?;
+        let (sql, values) =
+            self.orm
+                .update_statement(schema, column, value, item, self.values.len())?;
+        Ok(self.push(table_name, Change::Update, sql, values))
+    }
+
+    /// Queue a delete of the rows where `column = value`.
+    pub fn delete(&mut self, table_name: &str, column: &str, value: &str) -> &mut Self {
+        let sql = format!(
+            "DELETE FROM {} WHERE {} = {}",
+            self.orm.table_name(table_name),
+            column,
+            self.orm
+                .placeholder(table_name, column, self.values.len() + 1)
+        );
+        let values = vec![Value::String(value.to_string())];
+        self.push(table_name, Change::Delete, sql, values)
+    }
+
+    /// Number of queued writes.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    fn push(&mut self, table: &str, change: Change, sql: String, values: Vec<Value>) -> &mut Self {
+        self.writes.push(Write {
+            sql,
+            table: table.to_string(),
+            change,
+        });
+        self.values.extend(values);
+        self
+    }
+
+    /// Sends every queued write and returns the rows affected by each, in
+    /// queue order.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut batch = orm.batch();
+    /// batch.insert("users", &new_user)?;
+    /// batch.update("users", "id", "42", &renamed)?;
+    /// batch.delete("sessions", "user_id", "42");
+    /// let affected = batch.execute().await?;
+    /// ```
+    pub async fn execute(self) -> sqlx::Result<Vec<u64>> {
+        if self.writes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let reporting = self
+            .writes
+            .iter()
+            .any(|w| self.orm.event_returning(&w.table).is_some());
+        let sql = if reporting {
+            self.reporting_sql()
+        } else {
+            self.counting_sql()
+        };
+
+        let mut query = self.orm.statement(&sql);
+        for v in self.values {
+            query = bind_json(query, v);
+        }
+        let tables: Vec<&str> = self.writes.iter().map(|w| w.table.as_str()).collect();
+        let orm = self.orm;
+        let writes = &self.writes;
+        let record = |result: &sqlx::Result<Vec<PgRow>>, elapsed| {
+            let affected = match result {
+                Ok(rows) if reporting => rows_per_write(rows, writes.len()),
+                Ok(rows) => counts(&rows[0], writes.len()).unwrap_or_default(),
+                Err(_) => Vec::new(),
+            };
+            for (i, write) in writes.iter().enumerate() {
+                let rows = affected.get(i).copied().unwrap_or(0);
+                orm.record_stat(&write.table, write.change.operation(), elapsed, rows);
+            }
+        };
+        let rows = orm
+            .timed_tables(&tables, query.fetch_all(orm.pool()), record)
+            .await?;
+
+        if !reporting {
+            return counts(&rows[0], writes.len());
+        }
+        let mut written = vec![Vec::new(); writes.len()];
+        for row in &rows {
+            let (i, columns) = segment(row);
+            let mut value = columns_to_json(row, columns);
+            orm.reveal(&writes[i].table, &mut value, false)?;
+            written[i].push(value);
+        }
+        for (write, rows) in writes.iter().zip(&written) {
+            orm.emit_rows(write.change, &write.table, rows);
+        }
+        Ok(written.iter().map(|rows| rows.len() as u64).collect())
+    }
+
+    /// One row holding the number of rows each write affected.
+    fn counting_sql(&self) -> String {
+        let ctes: Vec<String> = self
+            .writes
+            .iter()
+            .enumerate()
+            .map(|(i, w)| format!("w{} AS ({} RETURNING 1)", i, w.sql))
+            .collect();
+        let counts: Vec<String> = (0..self.writes.len())
+            .map(|i| format!("(SELECT count(*) FROM w{})", i))
+            .collect();
+        format!("WITH {} SELECT {}", ctes.join(", "), counts.join(", "))
+    }
+
+    /// One row per written row, for events. The writes' `RETURNING` rows
+    /// are full-joined on `false`, so each result row holds the columns of
+    /// a single write, after that write's `slint_batch_<i>` marker column,
+    /// and NULLs for the others.
+    fn reporting_sql(&self) -> String {
+        let ctes: Vec<String> = self
+            .writes
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let returning = format!(" RETURNING {}", self.orm.select_list(&w.table));
+                format!("w{} AS ({}{})", i, w.sql, returning)
+            })
+            .collect();
+        let mut from = String::new();
+        for i in 0..self.writes.len() {
+            if i > 0 {
+                from.push_str(" FULL JOIN ");
+            }
+            from.push_str(&format!(
+                "(SELECT {i} AS slint_batch_{i}, w{i}.* FROM w{i}) b{i}"
+            ));
+            if i > 0 {
+                from.push_str(" ON false");
+            }
+        }
+        format!("WITH {} SELECT * FROM {}", ctes.join(", "), from)
+    }
+}
+
+/// The per-write counts of a [`Batch::counting_sql`] row.
+fn counts(row: &PgRow, writes: usize) -> sqlx::Result<Vec<u64>> {
+    (0..writes)
+        .map(|i| row.try_get::<i64, _>(i).map(|n| n as u64))
+        .collect()
+}
+
+/// The number of [`Batch::reporting_sql`] rows of each write.
+fn rows_per_write(rows: &[PgRow], writes: usize) -> Vec<u64> {
+    let mut counts = vec![0; writes];
+    for row in rows {
+        counts[segment(row).0] += 1;
+    }
+    counts
+}
+
+/// The write a [`Batch::reporting_sql`] row belongs to, and the range of
+/// its columns: those after the one marker that isn't NULL, up to the next
+/// marker.
+fn segment(row: &PgRow) -> (usize, Range<usize>) {
+    let columns = row.columns();
+    let is_marker = |index: usize| columns[index].name().starts_with("slint_batch_");
+    let start = (0..columns.len())
+        .find(|&index| {
+            is_marker(index)
+                && row
+                    .try_get::<Option<i32>, _>(index)
+                    .ok()
+                    .flatten()
+                    .is_some()
+        })
+        .expect("a batch row has one marker set");
+    let end = (start + 1..columns.len())
+        .find(|&index| is_marker(index))
+        .unwrap_or(columns.len());
+    let write = row.try_get::<i32, _>(start).unwrap_or_default() as usize;
+    (write, start + 1..end)
+}
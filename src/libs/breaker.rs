@@ -0,0 +1,185 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// State changes buffered per subscriber of `CircuitBreaker::subscribe`.
+const CHANGE_CAPACITY: usize = 16;
+
+/// State of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Queries run normally.
+    Closed,
+    /// The database looks down: queries fail fast with [`CircuitOpen`]
+    /// until the cool-down has passed.
+    Open,
+    /// The cool-down has passed and one query is let through to probe the
+    /// database; the others still fail fast.
+    HalfOpen,
+}
+
+/// A transition of a [`CircuitBreaker`], received from
+/// [`CircuitBreaker::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitChange {
+    pub from: CircuitState,
+    pub to: CircuitState,
+    /// Consecutive failures counted when the change happened.
+    pub failures: u32,
+}
+
+/// Error source of queries refused while the circuit is open, see
+/// [`is_circuit_open`].
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitOpen {
+    /// Time left until a query is let through again.
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "circuit breaker is open, retrying the database in {:.1?}",
+            self.retry_in
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// Whether `err` was returned without running the query because the
+/// ORM's circuit breaker is open.
+pub fn is_circuit_open(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(e) => e.get_ref().is_some_and(|e| e.is::<CircuitOpen>()),
+        _ => false,
+    }
+}
+
+/// Whether `err` suggests the database is unreachable or unable to serve
+/// queries, as opposed to a query the database rejected.
+fn is_outage(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::Protocol(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // Connection exceptions, insufficient resources, and shutdowns.
+        sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+            code.starts_with("08") || code.starts_with("53") || code.starts_with("57P")
+        }),
+        _ => false,
+    }
+}
+
+struct Breaker {
+    state: CircuitState,
+    failures: u32,
+    /// When the circuit last opened, or the probe of a half-open circuit
+    /// started.
+    since: Instant,
+}
+
+/// Fails queries fast while the database is down, set up with
+/// `OrmStruct::with_circuit_breaker`.
+///
+/// After `failure_threshold` consecutive connection or query failures
+/// (timeouts, I/O and TLS errors, Postgres connection and shutdown
+/// errors, not rejected queries) the circuit opens, and the ORM's table
+/// operations return an error matched by [`is_circuit_open`] without
+/// touching the pool. Once `cool_down` has passed one query is let
+/// through: its success closes the circuit, its failure opens it for
+/// another `cool_down`.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+    breaker: Mutex<Breaker>,
+    changes: broadcast::Sender<CircuitChange>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(failure_threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+            breaker: Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                failures: 0,
+                since: Instant::now(),
+            }),
+            changes: broadcast::channel(CHANGE_CAPACITY).0,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    /// Receiver of every state change, for alerting and dashboards.
+    pub fn subscribe(&self) -> broadcast::Receiver<CircuitChange> {
+        self.changes.subscribe()
+    }
+
+    /// Errs with [`CircuitOpen`] if a query may not run now. A half-open
+    /// probe that never reported back (its future was dropped) is replaced
+    /// after another cool-down.
+    pub(crate) fn admit(&self) -> sqlx::Result<()> {
+        let mut breaker = self.lock();
+        if breaker.state == CircuitState::Closed {
+            return Ok(());
+        }
+        let elapsed = breaker.since.elapsed();
+        if elapsed < self.cool_down {
+            let retry_in = self.cool_down - elapsed;
+            return Err(sqlx::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                CircuitOpen { retry_in },
+            )));
+        }
+        breaker.since = Instant::now();
+        self.transition(&mut breaker, CircuitState::HalfOpen);
+        Ok(())
+    }
+
+    /// Counts the outcome of an admitted query.
+    pub(crate) fn record<R>(&self, result: &sqlx::Result<R>) {
+        let mut breaker = self.lock();
+        match result {
+            Err(e) if is_outage(e) => {
+                breaker.failures += 1;
+                let trip = breaker.state == CircuitState::HalfOpen
+                    || breaker.failures >= self.failure_threshold;
+                if trip && breaker.state != CircuitState::Open {
+                    breaker.since = Instant::now();
+                    self.transition(&mut breaker, CircuitState::Open);
+                }
+            }
+            _ => {
+                breaker.failures = 0;
+                self.transition(&mut breaker, CircuitState::Closed);
+            }
+        }
+    }
+
+    fn transition(&self, breaker: &mut Breaker, to: CircuitState) {
+        if breaker.state == to {
+            return;
+        }
+        let change = CircuitChange {
+            from: breaker.state,
+            to,
+            failures: breaker.failures,
+        };
+        breaker.state = to;
+        // Sending only fails when nobody is subscribed.
+        let _ = self.changes.send(change);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Breaker> {
+        self.breaker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,499 @@
+//! Change data capture from a logical replication slot (feature `cdc`).
+//!
+//! A [`ChangeStream`] reads committed inserts, updates and deletes from a
+//! slot through the SQL replication functions, so no replication connection
+//! is needed. Changes are decoded from `pgoutput` or `wal2json` and mapped
+//! onto the ORM's registered schemas: values get JSON types from the
+//! column's declared type, encrypted columns are decrypted, masked ones
+//! masked and hashed ones dropped. Changes to unregistered tables are
+//! skipped.
+//!
+//! The server must run with `wal_level = logical`; `pgoutput` also needs a
+//! publication (`CREATE PUBLICATION app FOR ALL TABLES`).
+//!
+//! # Example
+//! ```ignore
+//! use slintrust::cdc::{ChangeStream, Plugin};
+//!
+//! let mut changes = ChangeStream::new(&orm, "app_cdc", Plugin::PgOutput {
+//!     publication: "app".into(),
+//! });
+//! changes.create_slot().await?;
+//! loop {
+//!     let change = changes.next_change().await?;
+//!     if change.table == "users" {
+//!         search_index.upsert(change.decode::<User>()?).await?;
+//!     }
+//! }
+//! ```
+
+use crate::OrmStruct;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use sqlx::Row;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Output plugin the slot decodes with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Plugin {
+    /// The built-in plugin, reading tables of `publication`.
+    PgOutput { publication: String },
+    /// The `wal2json` extension, format version 2.
+    Wal2Json,
+}
+
+impl Plugin {
+    fn name(&self) -> &'static str {
+        match self {
+            Plugin::PgOutput { .. } => "pgoutput",
+            Plugin::Wal2Json => "wal2json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// One row change read from the slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// Name of the registered schema the change belongs to.
+    pub table: String,
+    /// The row after the change. For deletes, the old row's replica
+    /// identity (its primary key unless the table says otherwise).
+    pub row: Value,
+    /// Log position of the change, e.g. `0/16B3748`.
+    pub lsn: String,
+}
+
+impl Change {
+    /// The row as the table's model.
+    pub fn decode<T: DeserializeOwned>(&self) -> sqlx::Result<T> {
+        serde_json::from_value(self.row.clone()).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "serde_json".into(),
+            source: Box::new(e),
+        })
+    }
+}
+
+/// A table announced by a `pgoutput` relation message.
+struct Relation {
+    namespace: String,
+    name: String,
+    columns: Vec<String>,
+}
+
+/// Reads changes from one logical replication slot.
+pub struct ChangeStream {
+    orm: OrmStruct,
+    slot: String,
+    plugin: Plugin,
+    relations: HashMap<u32, Relation>,
+    buffer: VecDeque<Change>,
+    batch_size: i32,
+    poll_interval: Duration,
+}
+
+impl ChangeStream {
+    pub fn new(orm: &OrmStruct, slot: &str, plugin: Plugin) -> Self {
+        Self {
+            orm: orm.clone(),
+            slot: slot.to_string(),
+            plugin,
+            relations: HashMap::new(),
+            buffer: VecDeque::new(),
+            batch_size: 1000,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// How many changes [`poll`](Self::poll) reads at most; whole
+    /// transactions are always read, so a batch can run over.
+    pub fn batch_size(mut self, n: i32) -> Self {
+        self.batch_size = n;
+        self
+    }
+
+    /// How long [`next_change`](Self::next_change) sleeps when the slot
+    /// has nothing new.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Creates the slot unless it already exists. Changes committed from
+    /// then on are kept for this stream until read.
+    pub async fn create_slot(&self) -> sqlx::Result<()> {
+        self.orm
+            .statement(
+                "SELECT pg_create_logical_replication_slot($1, $2) \
+             WHERE NOT EXISTS (SELECT 1 FROM pg_replication_slots WHERE slot_name = $1)",
+            )
+            .bind(&self.slot)
+            .bind(self.plugin.name())
+            .execute(self.orm.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the slot. An abandoned slot keeps the server from recycling
+    /// WAL, so drop slots that are no longer read.
+    pub async fn drop_slot(&self) -> sqlx::Result<()> {
+        self.orm
+            .statement(
+                "SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots \
+             WHERE slot_name = $1",
+            )
+            .bind(&self.slot)
+            .execute(self.orm.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Reads and consumes the next batch of changes; empty when the slot is
+    /// caught up. Consumed changes are not delivered again, even if the
+    /// caller fails to process them.
+    pub async fn poll(&mut self) -> sqlx::Result<Vec<Change>> {
+        // Changes read ahead by `next_change` come first.
+        let mut changes: Vec<Change> = self.buffer.drain(..).collect();
+        match self.plugin.clone() {
+            Plugin::PgOutput { publication } => {
+                let rows = self
+                    .orm
+                    .statement(
+                        "SELECT lsn::TEXT, data FROM pg_logical_slot_get_binary_changes(\
+                     $1, NULL, $2, 'proto_version', '1', 'publication_names', $3)",
+                    )
+                    .bind(&self.slot)
+                    .bind(self.batch_size)
+                    .bind(publication)
+                    .fetch_all(self.orm.pool())
+                    .await?;
+                for row in rows {
+                    let data: Vec<u8> = row.try_get(1)?;
+                    if let Some(change) = self.decode_pgoutput(row.try_get(0)?, &data)? {
+                        changes.push(change);
+                    }
+                }
+            }
+            Plugin::Wal2Json => {
+                let rows = self
+                    .orm
+                    .statement(
+                        "SELECT lsn::TEXT, data FROM pg_logical_slot_get_changes(\
+                     $1, NULL, $2, 'format-version', '2')",
+                    )
+                    .bind(&self.slot)
+                    .bind(self.batch_size)
+                    .fetch_all(self.orm.pool())
+                    .await?;
+                for row in rows {
+                    let data: String = row.try_get(1)?;
+                    if let Some(change) = self.decode_wal2json(row.try_get(0)?, &data)? {
+                        changes.push(change);
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// The next change, polling the slot until there is one.
+    pub async fn next_change(&mut self) -> sqlx::Result<Change> {
+        loop {
+            if let Some(change) = self.buffer.pop_front() {
+                return Ok(change);
+            }
+            let changes = self.poll().await?;
+            if changes.is_empty() {
+                tokio::time::sleep(self.poll_interval).await;
+            }
+            self.buffer = changes.into();
+        }
+    }
+
+    fn decode_pgoutput(&mut self, lsn: String, data: &[u8]) -> sqlx::Result<Option<Change>> {
+        let mut msg = Reader { buf: data };
+        let kind = match msg.u8()? {
+            b'R' => {
+                let oid = msg.u32()?;
+                let namespace = msg.cstr()?;
+                let name = msg.cstr()?;
+                msg.u8()?; // replica identity setting
+                let count = msg.u16()?;
+                let mut columns = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    msg.u8()?; // flags
+                    columns.push(msg.cstr()?);
+                    msg.u32()?; // type oid
+                    msg.u32()?; // type modifier
+                }
+                self.relations.insert(
+                    oid,
+                    Relation {
+                        namespace,
+                        name,
+                        columns,
+                    },
+                );
+                return Ok(None);
+            }
+            b'I' => ChangeKind::Insert,
+            b'U' => ChangeKind::Update,
+            b'D' => ChangeKind::Delete,
+            // Begin, commit, origin, type and truncate messages.
+            _ => return Ok(None),
+        };
+
+        let oid = msg.u32()?;
+        let relation = self
+            .relations
+            .get(&oid)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("pgoutput: unknown relation {}", oid)))?;
+        let mut tag = msg.u8()?;
+        if kind == ChangeKind::Update && (tag == b'K' || tag == b'O') {
+            // The old key or row; the new row follows.
+            msg.tuple(&relation.columns)?;
+            tag = msg.u8()?;
+        }
+        if !matches!(tag, b'N' | b'K' | b'O') {
+            return Err(sqlx::Error::Protocol(format!(
+                "pgoutput: unexpected tuple tag `{}`",
+                tag as char
+            )));
+        }
+        let mut values = msg.tuple(&relation.columns)?;
+        if tag == b'K' {
+            // Key tuples send NULL for every column outside the key.
+            values.retain(|_, v| !v.is_null());
+        }
+        let (namespace, name) = (relation.namespace.clone(), relation.name.clone());
+        self.change(kind, &namespace, &name, values, lsn)
+    }
+
+    fn decode_wal2json(&self, lsn: String, data: &str) -> sqlx::Result<Option<Change>> {
+        let msg: Value = serde_json::from_str(data).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "data".into(),
+            source: Box::new(e),
+        })?;
+        let (kind, field) = match msg["action"].as_str() {
+            Some("I") => (ChangeKind::Insert, "columns"),
+            Some("U") => (ChangeKind::Update, "columns"),
+            Some("D") => (ChangeKind::Delete, "identity"),
+            // Begin, commit, truncate and message actions.
+            _ => return Ok(None),
+        };
+        let values = msg[field]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| Some((c["name"].as_str()?.to_string(), c["value"].clone())))
+            .collect();
+        self.change(
+            kind,
+            msg["schema"].as_str().unwrap_or_default(),
+            msg["table"].as_str().unwrap_or_default(),
+            values,
+            lsn,
+        )
+    }
+
+    /// Maps a decoded row onto the registered schema of `namespace.name`,
+    /// or `None` when the table isn't registered.
+    fn change(
+        &self,
+        kind: ChangeKind,
+        namespace: &str,
+        name: &str,
+        values: Map<String, Value>,
+        lsn: String,
+    ) -> sqlx::Result<Option<Change>> {
+        let qualified = format!("{}.{}", namespace, name);
+        let Some(schema) = self
+            .orm
+            .schemas()
+            .iter()
+            .find(|s| s.name == name || s.name == qualified)
+        else {
+            return Ok(None);
+        };
+
+        let mut row = Value::Object(
+            values
+                .into_iter()
+                .map(|(column, value)| {
+                    let value = match (value, schema.column(&column)) {
+                        (Value::String(text), Some(c)) => typed_value(c.sql_type, text),
+                        (value, _) => value,
+                    };
+                    (column, value)
+                })
+                .collect(),
+        );
+        self.orm.reveal(schema.name, &mut row, false)?;
+        self.orm.strip_hashed(schema.name, &mut row);
+        Ok(Some(Change {
+            kind,
+            table: schema.name.to_string(),
+            row,
+            lsn,
+        }))
+    }
+}
+
+/// JSON value for the text form of a column of type `sql_type`; text that
+/// doesn't parse as the type is kept as a string.
+fn typed_value(sql_type: &str, text: String) -> Value {
+    let ty = sql_type.to_ascii_uppercase();
+    let parsed = if ["INT", "BIGINT", "SMALLINT", "SERIAL", "BIGSERIAL"]
+        .iter()
+        .any(|t| ty.starts_with(t))
+    {
+        text.parse::<i64>().ok().map(Value::from)
+    } else if ["REAL", "DOUBLE", "FLOAT", "NUMERIC", "DECIMAL"]
+        .iter()
+        .any(|t| ty.starts_with(t))
+    {
+        text.parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+    } else if ty.starts_with("BOOL") {
+        Some(Value::Bool(text == "t" || text == "true"))
+    } else if ty.starts_with("JSON") {
+        serde_json::from_str(&text).ok()
+    } else {
+        None
+    };
+    parsed.unwrap_or(Value::String(text))
+}
+
+/// Cursor over a `pgoutput` message.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> sqlx::Result<&'a [u8]> {
+        if self.buf.len() < n {
+            return Err(sqlx::Error::Protocol("pgoutput: message truncated".into()));
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> sqlx::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> sqlx::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> sqlx::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn cstr(&mut self) -> sqlx::Result<String> {
+        let end = self
+            .buf
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| sqlx::Error::Protocol("pgoutput: unterminated string".into()))?;
+        let s = String::from_utf8_lossy(self.take(end)?).into_owned();
+        self.take(1)?;
+        Ok(s)
+    }
+
+    /// Tuple data keyed by `columns`. Unchanged TOASTed values are left out.
+    fn tuple(&mut self, columns: &[String]) -> sqlx::Result<Map<String, Value>> {
+        let count = self.u16()? as usize;
+        let mut values = Map::new();
+        for i in 0..count {
+            let value = match self.u8()? {
+                b'n' => Value::Null,
+                b'u' => continue,
+                b't' => {
+                    let len = self.u32()? as usize;
+                    Value::String(String::from_utf8_lossy(self.take(len)?).into_owned())
+                }
+                other => {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "pgoutput: unsupported column kind `{}`",
+                        other as char
+                    )));
+                }
+            };
+            let name = columns
+                .get(i)
+                .ok_or_else(|| sqlx::Error::Protocol("pgoutput: too many columns".into()))?;
+            values.insert(name.clone(), value);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reader_takes_big_endian_integers_and_c_strings() {
+        let data = b"\x07\x00\x02\x00\x00\x01\x00public\0users\0";
+        let mut msg = Reader { buf: data };
+        assert_eq!(msg.u8().unwrap(), 7);
+        assert_eq!(msg.u16().unwrap(), 2);
+        assert_eq!(msg.u32().unwrap(), 256);
+        assert_eq!(msg.cstr().unwrap(), "public");
+        assert_eq!(msg.cstr().unwrap(), "users");
+        assert!(msg.buf.is_empty());
+    }
+
+    #[test]
+    fn reader_fails_on_truncated_messages() {
+        let mut msg = Reader { buf: b"\x00\x01" };
+        assert!(matches!(msg.u32(), Err(sqlx::Error::Protocol(_))));
+        let mut msg = Reader {
+            buf: b"no terminator",
+        };
+        assert!(matches!(msg.cstr(), Err(sqlx::Error::Protocol(_))));
+    }
+
+    #[test]
+    fn tuples_key_values_by_column_and_skip_unchanged_toast() {
+        let mut data = b"\x00\x03t\x00\x00\x00\x0242nu".to_vec();
+        data.extend(b"trailing");
+        let columns = ["id", "name", "body"].map(String::from);
+        let mut msg = Reader { buf: &data };
+        let values = msg.tuple(&columns).unwrap();
+        assert_eq!(Value::Object(values), json!({ "id": "42", "name": null }));
+        assert_eq!(msg.buf, b"trailing");
+    }
+
+    #[test]
+    fn tuples_reject_unknown_kinds_and_extra_columns() {
+        let columns = ["id".to_string()];
+        let mut msg = Reader { buf: b"\x00\x01b" };
+        assert!(matches!(msg.tuple(&columns), Err(sqlx::Error::Protocol(_))));
+        let mut msg = Reader { buf: b"\x00\x02nn" };
+        assert!(matches!(msg.tuple(&columns), Err(sqlx::Error::Protocol(_))));
+    }
+
+    #[test]
+    fn text_values_take_the_column_type() {
+        assert_eq!(typed_value("BIGINT", "42".into()), json!(42));
+        assert_eq!(typed_value("numeric(10,2)", "1.5".into()), json!(1.5));
+        assert_eq!(typed_value("BOOLEAN", "t".into()), json!(true));
+        assert_eq!(typed_value("JSONB", r#"{"a":1}"#.into()), json!({ "a": 1 }));
+        assert_eq!(typed_value("INTEGER", "n/a".into()), json!("n/a"));
+        assert_eq!(typed_value("TEXT", "42".into()), json!("42"));
+    }
+}
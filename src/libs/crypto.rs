@@ -0,0 +1,92 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM cipher for `#[slint(encrypted)]` columns.
+///
+/// Values are serialized to JSON, encrypted under a fresh random nonce, and
+/// stored as base64 `nonce || ciphertext` text, so any field type round-trips.
+#[derive(Clone)]
+pub struct FieldCipher {
+    cipher: Aes256Gcm,
+}
+
+impl FieldCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    /// Encrypts a column value. `null` is stored as-is.
+    pub fn encrypt(&self, value: &Value) -> sqlx::Result<Value> {
+        if value.is_null() {
+            return Ok(Value::Null);
+        }
+        let plaintext = value.to_string();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| sqlx::Error::Encode("failed to encrypt column value".into()))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(Value::String(STANDARD.encode(payload)))
+    }
+
+    /// Reverses [`encrypt`](Self::encrypt). `null` is returned as-is.
+    pub fn decrypt(&self, value: &Value) -> sqlx::Result<Value> {
+        let encoded = match value {
+            Value::Null => return Ok(Value::Null),
+            Value::String(s) => s,
+            _ => return Err(decrypt_error()),
+        };
+        let payload = STANDARD.decode(encoded).map_err(|_| decrypt_error())?;
+        if payload.len() < NONCE_LEN {
+            return Err(decrypt_error());
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| decrypt_error())?;
+        serde_json::from_slice(&plaintext).map_err(|_| decrypt_error())
+    }
+}
+
+fn decrypt_error() -> sqlx::Error {
+    sqlx::Error::Decode("failed to decrypt column value".into())
+}
+
+/// Hashes a `#[slint(hashed = "argon2")]` value into a PHC string
+/// (`$argon2id$v=19$...`) with a random salt. `null` is stored as-is.
+pub fn hash_value(value: &Value) -> sqlx::Result<Value> {
+    let plaintext = match value {
+        Value::Null => return Ok(Value::Null),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let salt = SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
+    let hash = Argon2::default()
+        .hash_password(plaintext.as_bytes(), &salt)
+        .map_err(|e| sqlx::Error::Encode(format!("failed to hash column value: {}", e).into()))?;
+    Ok(Value::String(hash.to_string()))
+}
+
+/// Checks `candidate` against a hash produced by [`hash_value`].
+pub fn verify_hash(hash: &str, candidate: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(candidate.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
@@ -0,0 +1,240 @@
+use crate::{ColumnSchema, TableSchema};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Rows `Table::import_csv` inserts per statement unless configured.
+pub const DEFAULT_CSV_BATCH: usize = 1000;
+
+/// How `Table::import_csv` reads a file and maps it onto the table.
+///
+/// By default fields are comma-separated, every header must name a column
+/// of the table, and rows are inserted 1000 at a time.
+#[derive(Debug, Clone)]
+pub struct CsvImportOptions {
+    pub(crate) delimiter: u8,
+    pub(crate) batch_size: usize,
+    /// Header to column renames.
+    pub(crate) columns: HashMap<String, String>,
+    pub(crate) skip_unknown: bool,
+    pub(crate) ignore_conflicts: bool,
+}
+
+impl Default for CsvImportOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            batch_size: DEFAULT_CSV_BATCH,
+            columns: HashMap::new(),
+            skip_unknown: false,
+            ignore_conflicts: false,
+        }
+    }
+}
+
+impl CsvImportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Field separator, e.g. `b';'` or `b'\t'`.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Rows inserted per statement (at least 1).
+    pub fn batch_size(mut self, rows: usize) -> Self {
+        self.batch_size = rows.max(1);
+        self
+    }
+
+    /// Loads the field under `header` into `column`.
+    pub fn map_column(mut self, header: &str, column: &str) -> Self {
+        self.columns.insert(header.to_string(), column.to_string());
+        self
+    }
+
+    /// Ignores fields whose header isn't a column of the table instead of
+    /// failing the import.
+    pub fn skip_unknown_columns(mut self) -> Self {
+        self.skip_unknown = true;
+        self
+    }
+
+    /// Skips rows that conflict with existing ones (`ON CONFLICT DO NOTHING`)
+    /// instead of failing the import.
+    pub fn ignore_conflicts(mut self) -> Self {
+        self.ignore_conflicts = true;
+        self
+    }
+
+    /// The column each field of a file with `headers` is loaded into;
+    /// `None` for skipped fields. `serial` and `generated` columns are
+    /// always skipped, so an exported file loads back as-is.
+    pub(crate) fn targets(
+        &self,
+        headers: &[Field],
+        schema: &TableSchema,
+    ) -> sqlx::Result<Vec<Option<&'static ColumnSchema>>> {
+        headers
+            .iter()
+            .map(|Field { text: header, .. }| {
+                let name = self.columns.get(header).unwrap_or(header);
+                match schema.columns.iter().find(|c| c.name == name) {
+                    Some(c) if c.serial || c.generated.is_some() => Ok(None),
+                    Some(c) => Ok(Some(c)),
+                    None if self.skip_unknown => Ok(None),
+                    None => Err(sqlx::Error::InvalidArgument(format!(
+                        "CSV field `{}` is not a column of `{}`",
+                        header, schema.name
+                    ))),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A field of a CSV record. `quoted` tells an empty string (`""`) from a
+/// missing value (an empty unquoted field).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Field {
+    pub(crate) text: String,
+    pub(crate) quoted: bool,
+}
+
+/// Reads the records of a CSV file one at a time. A record ends at a line
+/// break outside quotes; blank lines are skipped.
+pub(crate) struct RecordReader<R> {
+    reader: R,
+    delimiter: char,
+    line: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> RecordReader<R> {
+    pub(crate) fn new(reader: R, delimiter: u8) -> Self {
+        Self {
+            reader,
+            delimiter: delimiter as char,
+            line: 0,
+        }
+    }
+
+    /// The next record, or `None` at the end of the file.
+    pub(crate) async fn next(&mut self) -> sqlx::Result<Option<Vec<Field>>> {
+        let mut text = String::new();
+        let mut start = self.line + 1;
+        loop {
+            if self.reader.read_line(&mut text).await? == 0 {
+                if text.is_empty() {
+                    return Ok(None);
+                }
+                return Err(parse_error(start, "unterminated quoted field"));
+            }
+            self.line += 1;
+            // Escaped quotes come in pairs, so an odd count means a quoted
+            // field continues on the next line.
+            if text.matches('"').count() % 2 == 1 {
+                continue;
+            }
+            let record = text.trim_end_matches(['\r', '\n']);
+            if record.is_empty() {
+                text.clear();
+                start = self.line + 1;
+                continue;
+            }
+            return parse_record(record, self.delimiter)
+                .map(Some)
+                .map_err(|e| parse_error(start, e));
+        }
+    }
+}
+
+/// Splits one record into fields. Quotes only delimit a field that starts
+/// with one, and `""` inside it is a literal quote.
+fn parse_record(record: &str, delimiter: char) -> Result<Vec<Field>, &'static str> {
+    let mut fields = Vec::new();
+    let mut chars = record.chars().peekable();
+    loop {
+        let mut text = String::new();
+        let quoted = chars.peek() == Some(&'"');
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        text.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => text.push(c),
+                    None => return Err("unterminated quoted field"),
+                }
+            }
+        }
+        loop {
+            match chars.next() {
+                Some(c) if c == delimiter => break,
+                Some(_) if quoted => return Err("text after a closing quote"),
+                Some(c) => text.push(c),
+                None => {
+                    fields.push(Field { text, quoted });
+                    return Ok(fields);
+                }
+            }
+        }
+        fields.push(Field { text, quoted });
+    }
+}
+
+fn parse_error(line: usize, message: &str) -> sqlx::Error {
+    sqlx::Error::Decode(format!("CSV line {}: {}", line, message).into())
+}
+
+/// Row object for one CSV record. Empty unquoted fields are left out, so
+/// the column takes its `DEFAULT` (`NULL` when it has none); `""` is an
+/// empty string.
+pub(crate) fn record_row(targets: &[Option<&'static ColumnSchema>], record: Vec<Field>) -> Value {
+    let row = targets
+        .iter()
+        .zip(record)
+        .filter_map(|(column, field)| {
+            let column = (*column)?;
+            (field.quoted || !field.text.is_empty())
+                .then(|| (column.name.to_string(), Value::String(field.text)))
+        })
+        .collect();
+    Value::Object(row)
+}
+
+/// The header line of an export.
+pub(crate) fn header_line(header: &[String]) -> String {
+    let fields: Vec<String> = header.iter().map(|name| quote(name.clone())).collect();
+    fields.join(",") + "\n"
+}
+
+/// The line of one row object, its values in `header` order.
+pub(crate) fn row_line(header: &[String], row: &Value) -> String {
+    let fields: Vec<String> = header.iter().map(|column| field(row.get(column))).collect();
+    fields.join(",") + "\n"
+}
+
+/// CSV text of a value: NULL is an empty field, strings are written as-is
+/// and arrays and objects as JSON.
+fn field(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => quote(s.clone()),
+        Some(other) => quote(other.to_string()),
+    }
+}
+
+/// Quotes text holding a comma, quote or line break, and the empty string
+/// so it reads back as `""` rather than NULL.
+fn quote(text: String) -> String {
+    if text.is_empty() || text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{ColumnSchema, TableSchema};
+
+/// Output format of `OrmStruct::schema_diagram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A Mermaid `erDiagram`, rendered by GitHub and most doc sites.
+    Mermaid,
+    /// A Graphviz `digraph`, for `dot -Tsvg`.
+    Dot,
+}
+
+/// Entity-relationship diagram of `schemas`: one entity per table with its
+/// columns and key markers, and one edge per `foreign_key`. References to
+/// tables outside `schemas` are still drawn, as bare entities.
+pub fn schema_diagram(schemas: &[TableSchema], format: Format) -> String {
+    match format {
+        Format::Mermaid => mermaid(schemas),
+        Format::Dot => dot(schemas),
+    }
+}
+
+/// Mermaid identifiers are words, so `VARCHAR(255)` becomes `VARCHAR_255`.
+fn mermaid_word(s: &str) -> String {
+    let word: String = s
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    word.trim_matches('_').to_string()
+}
+
+fn key_markers(column: &ColumnSchema) -> Vec<&'static str> {
+    let mut markers = Vec::new();
+    if column.primary {
+        markers.push("PK");
+    }
+    if column.foreign_key.is_some() {
+        markers.push("FK");
+    }
+    if column.unique {
+        markers.push("UK");
+    }
+    markers
+}
+
+fn mermaid(schemas: &[TableSchema]) -> String {
+    let mut out = String::from("erDiagram\n");
+    for schema in schemas {
+        out.push_str(&format!("    {} {{\n", mermaid_word(schema.name)));
+        for c in schema.columns {
+            out.push_str(&format!(
+                "        {} {}",
+                mermaid_word(c.sql_type),
+                mermaid_word(c.name)
+            ));
+            let markers = key_markers(c);
+            if !markers.is_empty() {
+                out.push_str(&format!(" {}", markers.join(", ")));
+            }
+            out.push('\n');
+        }
+        out.push_str("    }\n");
+    }
+    for schema in schemas {
+        for c in schema.columns {
+            let Some((parent, _)) = c.references() else {
+                continue;
+            };
+            // A required reference means exactly one parent, else zero or one.
+            let parent_side = if c.not_null { "||" } else { "|o" };
+            out.push_str(&format!(
+                "    {} {}--o{{ {} : \"{}\"\n",
+                mermaid_word(parent),
+                parent_side,
+                mermaid_word(schema.name),
+                c.name
+            ));
+        }
+    }
+    out
+}
+
+/// Escapes text for a Graphviz record label.
+fn dot_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if matches!(ch, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+fn dot(schemas: &[TableSchema]) -> String {
+    let mut out = String::from("digraph schema {\n    rankdir=LR;\n    node [shape=record];\n");
+    for schema in schemas {
+        let columns: String = schema
+            .columns
+            .iter()
+            .map(|c| {
+                let markers = key_markers(c);
+                let markers = if markers.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", markers.join(", "))
+                };
+                format!(
+                    "{} : {}{}\\l",
+                    dot_escape(c.name),
+                    dot_escape(c.sql_type),
+                    markers
+                )
+            })
+            .collect();
+        out.push_str(&format!(
+            "    \"{}\" [label=\"{{{}|{}}}\"];\n",
+            schema.name,
+            dot_escape(schema.name),
+            columns
+        ));
+    }
+    for schema in schemas {
+        for c in schema.columns {
+            let Some((parent, column)) = c.references() else {
+                continue;
+            };
+            let label = match column {
+                Some(column) => format!("{} -> {}", c.name, column),
+                None => c.name.to_string(),
+            };
+            out.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                schema.name,
+                parent,
+                label.replace('"', "\\\"")
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::tests::{column, table};
+
+    static USERS: [ColumnSchema; 2] = [
+        ColumnSchema {
+            primary: true,
+            ..column("id")
+        },
+        ColumnSchema {
+            sql_type: "VARCHAR(255)",
+            unique: true,
+            ..column("email")
+        },
+    ];
+    static POSTS: [ColumnSchema; 3] = [
+        ColumnSchema {
+            primary: true,
+            ..column("id")
+        },
+        ColumnSchema {
+            foreign_key: Some("users.id"),
+            ..column("author_id")
+        },
+        ColumnSchema {
+            foreign_key: Some("categories"),
+            not_null: false,
+            ..column("category_id")
+        },
+    ];
+
+    fn schemas() -> [TableSchema; 2] {
+        [table("users", &USERS), table("posts", &POSTS)]
+    }
+
+    #[test]
+    fn mermaid_draws_entities_keys_and_references() {
+        assert_eq!(
+            schema_diagram(&schemas(), Format::Mermaid),
+            "erDiagram\n\
+             \x20   users {\n\
+             \x20       TEXT id PK\n\
+             \x20       VARCHAR_255 email UK\n\
+             \x20   }\n\
+             \x20   posts {\n\
+             \x20       TEXT id PK\n\
+             \x20       TEXT author_id FK\n\
+             \x20       TEXT category_id FK\n\
+             \x20   }\n\
+             \x20   users ||--o{ posts : \"author_id\"\n\
+             \x20   categories |o--o{ posts : \"category_id\"\n"
+        );
+    }
+
+    #[test]
+    fn dot_escapes_record_labels() {
+        let dot = schema_diagram(&schemas(), Format::Dot);
+        assert!(dot.starts_with("digraph schema {\n"));
+        assert!(dot.contains(
+            "    \"users\" [label=\"{users|id : TEXT (PK)\\l\
+             email : VARCHAR(255) (UK)\\l}\"];\n"
+        ));
+        assert!(dot.contains("    \"posts\" -> \"users\" [label=\"author_id -> id\"];\n"));
+        assert!(dot.contains("    \"posts\" -> \"categories\" [label=\"category_id\"];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn dot_escape_backslashes_record_syntax() {
+        assert_eq!(dot_escape("a|b{c}<d>\"e\\"), "a\\|b\\{c\\}\\<d\\>\\\"e\\\\");
+    }
+}
@@ -0,0 +1,56 @@
+use crate::ColumnSchema;
+use crate::hstore::{hstore_literal, is_hstore};
+use serde_json::Value;
+
+/// Rows `OrmStruct::restore_table_json` inserts per statement, at most.
+pub const RESTORE_BATCH: usize = 500;
+
+/// What `OrmStruct::restore_table_json` does with a row whose key (or other
+/// unique column) is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Fail the restore.
+    #[default]
+    Error,
+    /// Keep the existing row (`ON CONFLICT DO NOTHING`).
+    Skip,
+    /// Overwrite the existing row with the dumped one, matched on the
+    /// table's key column.
+    Overwrite,
+}
+
+/// A dumped value prepared for binding into `column`: objects and arrays,
+/// which `bind_json` can't bind, become the text Postgres parses for the
+/// column's type.
+pub(crate) fn restore_value(column: &ColumnSchema, value: Value) -> Value {
+    #[cfg(feature = "geo")]
+    if crate::geo::is_geometry(column)
+        && let Some(ewkt) = crate::geo::geometry_ewkt(&value)
+    {
+        return Value::String(ewkt);
+    }
+    match value {
+        Value::Array(_) if column.sql_type.ends_with("[]") => Value::String(array_literal(&value)),
+        Value::Object(map) if is_hstore(column) => Value::String(hstore_literal(&map)),
+        Value::Array(_) | Value::Object(_) => Value::String(value.to_string()),
+        value => value,
+    }
+}
+
+/// Postgres array literal of a JSON array, e.g. `{1,NULL,"a \"b\""}`.
+fn array_literal(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(array_literal).collect();
+            format!("{{{}}}", items.join(","))
+        }
+        Value::Null => "NULL".to_string(),
+        Value::String(s) => quote_element(s),
+        Value::Object(_) => quote_element(&value.to_string()),
+        other => other.to_string(),
+    }
+}
+
+fn quote_element(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
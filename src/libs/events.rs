@@ -0,0 +1,139 @@
+use crate::SlintModel;
+use crate::stats::Operation;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Events buffered per subscriber before the slowest one starts missing
+/// them (its next `recv` returns `RecvError::Lagged`).
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// A row written by an ORM insert or upsert.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowInserted<T = Value> {
+    pub table: String,
+    /// Rust model of the table; empty for tables without a registered
+    /// schema.
+    pub model: &'static str,
+    pub row: T,
+}
+
+/// A row as it reads after an ORM update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowUpdated<T = Value> {
+    pub table: String,
+    /// Rust model of the table; empty for tables without a registered
+    /// schema.
+    pub model: &'static str,
+    pub row: T,
+}
+
+/// A row removed by an ORM delete, as it read before the delete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowDeleted {
+    pub table: String,
+    /// Rust model of the table; empty for tables without a registered
+    /// schema.
+    pub model: &'static str,
+    pub row: Value,
+}
+
+/// A write made through the ORM, received from `OrmStruct::subscribe`.
+///
+/// Rows are JSON objects decrypted and masked like query results, without
+/// hashed columns. Use [`inserted`](Self::inserted) and
+/// [`updated`](Self::updated) to get them as a model.
+///
+/// # Example
+/// ```ignore
+/// let mut events = orm.subscribe();
+/// while let Ok(event) = events.recv().await {
+///     if let Some(RowUpdated { row: user, .. }) = event.updated::<User>() {
+///         cache.invalidate(&user.id);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrmEvent {
+    Inserted(RowInserted),
+    Updated(RowUpdated),
+    Deleted(RowDeleted),
+}
+
+impl OrmEvent {
+    /// The table written to.
+    pub fn table(&self) -> &str {
+        match self {
+            OrmEvent::Inserted(e) => &e.table,
+            OrmEvent::Updated(e) => &e.table,
+            OrmEvent::Deleted(e) => &e.table,
+        }
+    }
+
+    /// The inserted row as a `T`, if this is an insert into `T`'s table.
+    /// Tables are matched by model, so renamed tables (see
+    /// `OrmStruct::with_naming`) are found too.
+    pub fn inserted<T>(&self) -> Option<RowInserted<T>>
+    where
+        T: SlintModel + DeserializeOwned,
+    {
+        match self {
+            OrmEvent::Inserted(e) if e.model == T::slint_schema().model => Some(RowInserted {
+                table: e.table.clone(),
+                model: e.model,
+                row: serde_json::from_value(e.row.clone()).ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The updated row as a `T`, if this is an update of `T`'s table.
+    pub fn updated<T>(&self) -> Option<RowUpdated<T>>
+    where
+        T: SlintModel + DeserializeOwned,
+    {
+        match self {
+            OrmEvent::Updated(e) if e.model == T::slint_schema().model => Some(RowUpdated {
+                table: e.table.clone(),
+                model: e.model,
+                row: serde_json::from_value(e.row.clone()).ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The deleted row, if this is a delete from `T`'s table.
+    pub fn deleted<T: SlintModel>(&self) -> Option<&RowDeleted> {
+        match self {
+            OrmEvent::Deleted(e) if e.model == T::slint_schema().model => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Which kind of write `OrmStruct::emit_rows` reports.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Change {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Change {
+    /// The operation the write counts as in `OrmStruct::query_stats`.
+    pub(crate) fn operation(self) -> Operation {
+        match self {
+            Change::Insert => Operation::Insert,
+            Change::Update => Operation::Update,
+            Change::Delete => Operation::Delete,
+        }
+    }
+
+    pub(crate) fn event(self, table: &str, model: &'static str, row: Value) -> OrmEvent {
+        let table = table.to_string();
+        match self {
+            Change::Insert => OrmEvent::Inserted(RowInserted { table, model, row }),
+            Change::Update => OrmEvent::Updated(RowUpdated { table, model, row }),
+            Change::Delete => OrmEvent::Deleted(RowDeleted { table, model, row }),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{ColumnSchema, SqlValue};
+use geo_types::{Point, Polygon};
+use serde_json::{Value, json};
+
+/// Spatial reference of the `GEOMETRY` columns `#[slint]` declares for
+/// `geo_types` fields: WGS 84, with `x` as longitude and `y` as latitude.
+pub const GEO_SRID: i32 = 4326;
+
+/// Whether `column` holds PostGIS geometries.
+pub(crate) fn is_geometry(column: &ColumnSchema) -> bool {
+    column
+        .sql_type
+        .get(..8)
+        .is_some_and(|t| t.eq_ignore_ascii_case("GEOMETRY"))
+}
+
+/// EWKT of a `geo_types` point or polygon in its JSON form, e.g.
+/// `SRID=4326;POINT(13.4 52.5)`, which the column's placeholder cast
+/// parses. `None` for any other value.
+pub(crate) fn geometry_ewkt(value: &Value) -> Option<String> {
+    let wkt = if let Some(exterior) = value.get("exterior") {
+        let mut rings = vec![ring_wkt(exterior)?];
+        for ring in value.get("interiors")?.as_array()? {
+            rings.push(ring_wkt(ring)?);
+        }
+        format!("POLYGON({})", rings.join(","))
+    } else {
+        format!("POINT({})", coord_wkt(value)?)
+    };
+    Some(format!("SRID={};{}", GEO_SRID, wkt))
+}
+
+fn coord_wkt(coord: &Value) -> Option<String> {
+    Some(format!(
+        "{} {}",
+        coord.get("x")?.as_f64()?,
+        coord.get("y")?.as_f64()?
+    ))
+}
+
+fn ring_wkt(ring: &Value) -> Option<String> {
+    let coords = ring
+        .as_array()?
+        .iter()
+        .map(coord_wkt)
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("({})", coords.join(",")))
+}
+
+/// JSON form `geo_types` deserializes from of a geometry in (E)WKB, as
+/// PostGIS sends it; hex-encoded in the text format. Points and polygons
+/// are supported, other geometries are `None`.
+pub(crate) fn geometry_json(bytes: &[u8]) -> Option<Value> {
+    let mut reader = Wkb::new(bytes)?;
+    let kind = reader.u32()?;
+    if kind & 0x2000_0000 != 0 {
+        reader.u32()?; // SRID
+    }
+    // Z and M ordinates follow x and y, and are skipped.
+    let extra = usize::from(kind & 0x8000_0000 != 0) + usize::from(kind & 0x4000_0000 != 0);
+    let coord = |reader: &mut Wkb| -> Option<Value> {
+        let (x, y) = (reader.f64()?, reader.f64()?);
+        for _ in 0..extra {
+            reader.f64()?;
+        }
+        Some(json!({ "x": x, "y": y }))
+    };
+    match kind & 0xffff {
+        1 => coord(&mut reader),
+        3 => {
+            let mut rings = Vec::new();
+            for _ in 0..reader.u32()? {
+                let points = (0..reader.u32()?)
+                    .map(|_| coord(&mut reader))
+                    .collect::<Option<Vec<_>>>()?;
+                rings.push(Value::Array(points));
+            }
+            if rings.is_empty() {
+                rings.push(Value::Array(Vec::new()));
+            }
+            let exterior = rings.remove(0);
+            Some(json!({ "exterior": exterior, "interiors": rings }))
+        }
+        _ => None,
+    }
+}
+
+/// Bytes of a hex-encoded geometry.
+pub(crate) fn unhex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// `ST_DWithin` of geometry `column` and the point bound at `$index`,
+/// measured in meters on the spheroid.
+pub(crate) fn within_distance_sql(column: &str, index: usize, meters: f64) -> String {
+    format!(
+        "ST_DWithin({}::geography, ${}::geography, '{}'::float8)",
+        column, index, meters
+    )
+}
+
+/// Reader over WKB, whose first byte gives the byte order.
+struct Wkb<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Wkb<'a> {
+    fn new(bytes: &'a [u8]) -> Option<Self> {
+        let (order, bytes) = bytes.split_first()?;
+        Some(Self {
+            bytes,
+            little_endian: *order == 1,
+        })
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.bytes.split_first_chunk::<N>()?;
+        self.bytes = rest;
+        Some(*head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let b = self.take()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        let b = self.take()?;
+        Some(if self.little_endian {
+            f64::from_le_bytes(b)
+        } else {
+            f64::from_be_bytes(b)
+        })
+    }
+}
+
+/// Bound as EWKT, e.g. for `where_within_distance`.
+impl From<Point<f64>> for SqlValue {
+    fn from(v: Point<f64>) -> Self {
+        SqlValue::Text(format!("SRID={};POINT({} {})", GEO_SRID, v.x(), v.y()))
+    }
+}
+
+/// Bound as EWKT, comparing with `GEOMETRY` columns.
+impl From<Polygon<f64>> for SqlValue {
+    fn from(v: Polygon<f64>) -> Self {
+        serde_json::to_value(&v)
+            .ok()
+            .and_then(|v| geometry_ewkt(&v))
+            .map_or(SqlValue::Null, SqlValue::Text)
+    }
+}
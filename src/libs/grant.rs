@@ -0,0 +1,73 @@
+use crate::quote_ident;
+use std::fmt;
+
+/// A table privilege for [`OrmStruct::grant`](crate::OrmStruct::grant).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Privilege {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Truncate,
+    References,
+    Trigger,
+    /// Every table privilege (`ALL PRIVILEGES`).
+    All,
+}
+
+impl Privilege {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Privilege::Select => "SELECT",
+            Privilege::Insert => "INSERT",
+            Privilege::Update => "UPDATE",
+            Privilege::Delete => "DELETE",
+            Privilege::Truncate => "TRUNCATE",
+            Privilege::References => "REFERENCES",
+            Privilege::Trigger => "TRIGGER",
+            Privilege::All => "ALL PRIVILEGES",
+        }
+    }
+}
+
+impl fmt::Display for Privilege {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+/// Privileges on some tables for a role, granted by `migrate()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Grant {
+    pub(crate) role: String,
+    pub(crate) tables: Vec<String>,
+    pub(crate) privileges: Vec<Privilege>,
+}
+
+impl Grant {
+    /// `GRANT ... ON ... TO role` for those of the grant's tables that
+    /// `include` accepts; `None` when it accepts none.
+    pub(crate) fn statement(&self, include: impl Fn(&str) -> bool) -> Option<String> {
+        let tables: Vec<&str> = self
+            .tables
+            .iter()
+            .map(String::as_str)
+            .filter(|t| include(t))
+            .collect();
+        if tables.is_empty() {
+            return None;
+        }
+        let privileges: Vec<&str> = self.privileges.iter().map(Privilege::as_sql).collect();
+        let role = if self.role.eq_ignore_ascii_case("public") {
+            "PUBLIC".to_string()
+        } else {
+            quote_ident(&self.role)
+        };
+        Some(format!(
+            "GRANT {} ON TABLE {} TO {}",
+            privileges.join(", "),
+            tables.join(", "),
+            role
+        ))
+    }
+}
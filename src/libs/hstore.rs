@@ -0,0 +1,182 @@
+use crate::ColumnSchema;
+use serde_json::{Map, Value};
+
+/// Whether `column` is an `HSTORE`.
+pub(crate) fn is_hstore(column: &ColumnSchema) -> bool {
+    column.sql_type.eq_ignore_ascii_case("HSTORE")
+}
+
+/// A key-value field's JSON object as the text its column's placeholder
+/// cast parses: an hstore literal for `HSTORE` columns, the JSON itself for
+/// `JSON` / `JSONB` ones. Other values pass through unchanged.
+pub(crate) fn map_value(column: &ColumnSchema, value: Value) -> Value {
+    match value {
+        Value::Object(map) if is_hstore(column) => Value::String(hstore_literal(&map)),
+        Value::Object(_) | Value::Array(_)
+            if column.sql_type.eq_ignore_ascii_case("JSON")
+                || column.sql_type.eq_ignore_ascii_case("JSONB") =>
+        {
+            Value::String(value.to_string())
+        }
+        value => value,
+    }
+}
+
+/// hstore literal of `map`, e.g. `"color"=>"red", "size"=>NULL`. Values
+/// other than strings and nulls are written as their JSON text.
+pub(crate) fn hstore_literal(map: &Map<String, Value>) -> String {
+    let pairs: Vec<String> = map
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Null => "NULL".to_string(),
+                Value::String(s) => quote(s),
+                other => quote(&other.to_string()),
+            };
+            format!("{}=>{}", quote(key), value)
+        })
+        .collect();
+    pairs.join(", ")
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// JSON object of an hstore in its text form, as Postgres writes it.
+pub(crate) fn hstore_text_json(text: &str) -> Option<Value> {
+    let mut map = Map::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek().is_none() {
+            return Some(Value::Object(map));
+        }
+        let key = token(&mut chars)??;
+        skip_whitespace(&mut chars);
+        if (chars.next(), chars.next()) != (Some('='), Some('>')) {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        let value = token(&mut chars)?.map_or(Value::Null, Value::String);
+        map.insert(key, value);
+        skip_whitespace(&mut chars);
+        if chars.next().is_some_and(|c| c != ',') {
+            return None;
+        }
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars<'_>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// A quoted string or an unquoted word, the word `NULL` being `Some(None)`.
+fn token(chars: &mut Chars<'_>) -> Option<Option<String>> {
+    let mut s = String::new();
+    if chars.next_if_eq(&'"').is_some() {
+        loop {
+            match chars.next()? {
+                '"' => return Some(Some(s)),
+                '\\' => s.push(chars.next()?),
+                c => s.push(c),
+            }
+        }
+    }
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, ',' | '=')) {
+        s.push(c);
+    }
+    match s.as_str() {
+        "" => None,
+        _ if s.eq_ignore_ascii_case("NULL") => Some(None),
+        _ => Some(Some(s)),
+    }
+}
+
+/// JSON object of an hstore in its binary form: a pair count, then each
+/// key and value as a length-prefixed string, a length of -1 being NULL.
+pub(crate) fn hstore_binary_json(mut bytes: &[u8]) -> Option<Value> {
+    let mut map = Map::new();
+    for _ in 0..read_int(&mut bytes)? {
+        let key = read_string(&mut bytes)??;
+        let value = read_string(&mut bytes)?.map_or(Value::Null, Value::String);
+        map.insert(key, value);
+    }
+    Some(Value::Object(map))
+}
+
+fn read_int(bytes: &mut &[u8]) -> Option<i32> {
+    let (head, rest) = bytes.split_first_chunk::<4>()?;
+    *bytes = rest;
+    Some(i32::from_be_bytes(*head))
+}
+
+/// A length-prefixed string, `Some(None)` for NULL.
+fn read_string(bytes: &mut &[u8]) -> Option<Option<String>> {
+    let Ok(len) = usize::try_from(read_int(bytes)?) else {
+        return Some(None);
+    };
+    let (head, rest) = bytes.split_at_checked(len)?;
+    *bytes = rest;
+    String::from_utf8(head.to_vec()).ok().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn text_form_parses_quoted_and_bare_pairs() {
+        assert_eq!(
+            hstore_text_json(r#""color"=>"red", "size"=>NULL, bare=>word"#),
+            Some(json!({ "color": "red", "size": null, "bare": "word" }))
+        );
+        assert_eq!(hstore_text_json(""), Some(json!({})));
+        assert_eq!(hstore_text_json("  "), Some(json!({})));
+    }
+
+    #[test]
+    fn text_form_unescapes_quotes_and_backslashes() {
+        assert_eq!(
+            hstore_text_json(r#""a \"q\""=>"c:\\tmp", "k"=>"NULL""#),
+            Some(json!({ "a \"q\"": "c:\\tmp", "k": "NULL" }))
+        );
+    }
+
+    #[test]
+    fn text_form_rejects_malformed_input() {
+        assert_eq!(hstore_text_json(r#""a"=>"#), None);
+        assert_eq!(hstore_text_json(r#""a" "b""#), None);
+        assert_eq!(hstore_text_json(r#""a"=>"b" "c"=>"d""#), None);
+        assert_eq!(hstore_text_json(r#""a"=>"unterminated"#), None);
+        assert_eq!(hstore_text_json(r#"NULL=>"b""#), None);
+    }
+
+    #[test]
+    fn literals_read_back_as_text_form() {
+        let map = json!({ "quote\"": "back\\slash", "n": null, "num": 5 });
+        let literal = hstore_literal(map.as_object().unwrap());
+        assert_eq!(
+            hstore_text_json(&literal),
+            Some(json!({ "quote\"": "back\\slash", "n": null, "num": "5" }))
+        );
+    }
+
+    #[test]
+    fn binary_form_reads_length_prefixed_pairs() {
+        let mut bytes = 2i32.to_be_bytes().to_vec();
+        for s in ["k", "v", "n"] {
+            bytes.extend((s.len() as i32).to_be_bytes());
+            bytes.extend(s.as_bytes());
+        }
+        bytes.extend((-1i32).to_be_bytes());
+        assert_eq!(
+            hstore_binary_json(&bytes),
+            Some(json!({ "k": "v", "n": null }))
+        );
+        assert_eq!(hstore_binary_json(&bytes[..bytes.len() - 1]), None);
+    }
+}
@@ -0,0 +1,128 @@
+use rand::Rng;
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Generates keys for `#[slint(uuid)]` columns an insert leaves unset.
+///
+/// The ORM uses [`UuidV4`] unless a table opts into another strategy with
+/// `OrmStruct::with_id_strategy`.
+pub trait IdStrategy: Send + Sync {
+    fn generate(&self) -> Value;
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Random version 4 UUIDs, the default strategy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV4;
+
+impl IdStrategy for UuidV4 {
+    fn generate(&self) -> Value {
+        Value::String(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+/// ULIDs: 26-character, lexicographically sortable by creation time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ulid;
+
+impl IdStrategy for Ulid {
+    fn generate(&self) -> Value {
+        const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        // 48-bit millisecond timestamp followed by 80 random bits.
+        let random: u128 = rand::thread_rng().r#gen::<u128>() >> 48;
+        let bits = ((now_millis() as u128 & 0xFFFF_FFFF_FFFF) << 80) | random;
+        let id: String = (0..26)
+            .rev()
+            .map(|i| ALPHABET[((bits >> (i * 5)) & 0x1F) as usize] as char)
+            .collect();
+        Value::String(id)
+    }
+}
+
+/// Snowflake IDs: 64-bit integers made of a millisecond timestamp, a
+/// worker id and a per-millisecond sequence, sortable by creation time.
+/// Give every process writing the same table its own `worker_id`.
+#[derive(Debug)]
+pub struct Snowflake {
+    worker_id: u64,
+    epoch_millis: u64,
+    state: Mutex<(u64, u64)>,
+}
+
+impl Snowflake {
+    /// Twitter's epoch (2010-11-04), the conventional Snowflake origin.
+    pub const DEFAULT_EPOCH_MILLIS: u64 = 1_288_834_974_657;
+
+    /// A generator for `worker_id` (0–1023).
+    pub fn new(worker_id: u16) -> Self {
+        Self::with_epoch(worker_id, Self::DEFAULT_EPOCH_MILLIS)
+    }
+
+    /// A generator counting time from a custom epoch (Unix milliseconds).
+    pub fn with_epoch(worker_id: u16, epoch_millis: u64) -> Self {
+        assert!(worker_id < 1024, "Snowflake worker id must be below 1024");
+        Self {
+            worker_id: worker_id as u64,
+            epoch_millis,
+            state: Mutex::new((0, 0)),
+        }
+    }
+
+    /// The next id as an integer.
+    pub fn next_id(&self) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        let (last, sequence) = *state;
+        let mut now = now_millis().max(last);
+        let sequence = if now == last {
+            let next = (sequence + 1) & 0xFFF;
+            if next == 0 {
+                // Sequence exhausted for this millisecond; wait for the next.
+                while now <= last {
+                    now = now_millis();
+                }
+            }
+            next
+        } else {
+            0
+        };
+        *state = (now, sequence);
+        let timestamp = now.saturating_sub(self.epoch_millis) & 0x1FF_FFFF_FFFF;
+        ((timestamp << 22) | (self.worker_id << 12) | sequence) as i64
+    }
+}
+
+impl IdStrategy for Snowflake {
+    fn generate(&self) -> Value {
+        Value::from(self.next_id())
+    }
+}
+
+/// Random URL-safe ids of a fixed length (21 characters by default).
+#[derive(Debug, Clone, Copy)]
+pub struct NanoId {
+    pub length: usize,
+}
+
+impl Default for NanoId {
+    fn default() -> Self {
+        Self { length: 21 }
+    }
+}
+
+impl IdStrategy for NanoId {
+    fn generate(&self) -> Value {
+        const ALPHABET: &[u8; 64] =
+            b"_-0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut rng = rand::thread_rng();
+        let id: String = (0..self.length)
+            .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+            .collect();
+        Value::String(id)
+    }
+}
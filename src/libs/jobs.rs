@@ -0,0 +1,263 @@
+//! A job queue stored in Postgres.
+//!
+//! Jobs are rows of the `slint_jobs` table, created by [`migrate`]. They are
+//! enqueued inside the caller's transaction, so a job exists only if the
+//! work that scheduled it committed, and claimed by [`Worker`]s with
+//! `FOR UPDATE SKIP LOCKED`, so any number of workers can share a queue.
+//!
+//! # Example
+//! ```ignore
+//! use slintrust::jobs::{self, NewJob, Worker};
+//!
+//! jobs::migrate(&orm).await?;
+//!
+//! orm.session(|s| {
+//!     Box::pin(async move {
+//!         s.execute("INSERT INTO orders (id) VALUES ($1)", &[order_id.into()]).await?;
+//!         jobs::enqueue(s, NewJob::new("emails", json!({"order": order_id}))).await?;
+//!         Ok(())
+//!     })
+//! })
+//! .await?;
+//!
+//! Worker::new(&orm, "emails")
+//!     .run(|job| async move { send_email(job.payload).await })
+//!     .await?;
+//! ```
+
+use crate::{OrmStruct, Session};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::Row;
+use std::future::Future;
+use std::time::Duration;
+
+/// Name of the table holding queued jobs, before the ORM's table prefix
+/// (see `OrmOptions::table_prefix`).
+pub const JOBS_TABLE: &str = "slint_jobs";
+
+/// Error type returned by job handlers.
+pub type JobError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Creates the jobs table and its claim index if they don't exist.
+pub async fn migrate(orm: &OrmStruct) -> sqlx::Result<()> {
+    let table = orm.table_name(JOBS_TABLE);
+    let statements = [
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY, \
+             queue TEXT NOT NULL, \
+             payload JSONB NOT NULL, \
+             attempts INTEGER NOT NULL DEFAULT 0, \
+             max_attempts INTEGER NOT NULL, \
+             run_at TIMESTAMPTZ NOT NULL DEFAULT now(), \
+             last_error TEXT, \
+             failed_at TIMESTAMPTZ, \
+             created_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+            table
+        ),
+        format!(
+            "CREATE INDEX IF NOT EXISTS {table}_claim ON {table} (queue, run_at) \
+             WHERE failed_at IS NULL",
+            table = table
+        ),
+    ];
+    for sql in &statements {
+        orm.raw(sql).await?;
+    }
+    Ok(())
+}
+
+/// A job to enqueue.
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    queue: String,
+    payload: Value,
+    max_attempts: i32,
+    run_at: Option<DateTime<Utc>>,
+}
+
+impl NewJob {
+    /// A job for `queue`, run as soon as a worker is free and attempted up
+    /// to 5 times.
+    pub fn new(queue: &str, payload: Value) -> Self {
+        Self {
+            queue: queue.to_string(),
+            payload,
+            max_attempts: 5,
+            run_at: None,
+        }
+    }
+
+    pub fn max_attempts(mut self, n: i32) -> Self {
+        self.max_attempts = n;
+        self
+    }
+
+    /// Don't run the job before `at`.
+    pub fn run_at(mut self, at: DateTime<Utc>) -> Self {
+        self.run_at = Some(at);
+        self
+    }
+}
+
+/// Adds `job` to its queue as part of the session's transaction and returns
+/// its id. Workers see it once the transaction commits.
+pub async fn enqueue(session: &mut Session, job: NewJob) -> sqlx::Result<i64> {
+    let sql = format!(
+        "INSERT INTO {} (queue, payload, max_attempts, run_at) \
+         VALUES ($1, $2::JSONB, $3, COALESCE($4, now())) RETURNING id",
+        session.orm().table_name(JOBS_TABLE)
+    );
+    session
+        .orm()
+        .statement(&sql)
+        .bind(job.queue)
+        .bind(job.payload.to_string())
+        .bind(job.max_attempts)
+        .bind(job.run_at)
+        .fetch_one(session.connection())
+        .await?
+        .try_get(0)
+}
+
+/// A claimed job, passed to the worker's handler.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: i64,
+    pub queue: String,
+    pub payload: Value,
+    /// Failed attempts before this one.
+    pub attempts: i32,
+}
+
+/// Claims and runs the jobs of one queue.
+pub struct Worker {
+    orm: OrmStruct,
+    queue: String,
+    poll_interval: Duration,
+    retry_delay: Duration,
+}
+
+impl Worker {
+    pub fn new(orm: &OrmStruct, queue: &str) -> Self {
+        Self {
+            orm: orm.clone(),
+            queue: queue.to_string(),
+            poll_interval: Duration::from_secs(1),
+            retry_delay: Duration::from_secs(5),
+        }
+    }
+
+    /// How long [`run`](Self::run) sleeps when the queue is empty.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Delay before the first retry of a failed job; doubled for each
+    /// further attempt.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Claims one due job and runs `handler` on it. The job's row stays
+    /// locked while the handler runs; it is deleted on success, and on
+    /// failure rescheduled with backoff or, after its last attempt, marked
+    /// failed and kept for inspection. Returns whether a job was found.
+    pub async fn run_once<F, Fut>(&self, handler: F) -> sqlx::Result<bool>
+    where
+        F: FnOnce(Job) -> Fut,
+        Fut: Future<Output = Result<(), JobError>>,
+    {
+        let table = self.orm.table_name(JOBS_TABLE);
+        let mut tx = self.orm.pool().begin().await?;
+        let sql = format!(
+            "SELECT id, queue, payload::TEXT, attempts, max_attempts FROM {} \
+             WHERE queue = $1 AND failed_at IS NULL AND run_at <= now() \
+             ORDER BY run_at, id LIMIT 1 FOR UPDATE SKIP LOCKED",
+            table
+        );
+        let Some(row) = self
+            .orm
+            .statement(&sql)
+            .bind(&self.queue)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        let payload: String = row.try_get(2)?;
+        let max_attempts: i32 = row.try_get(4)?;
+        let job = Job {
+            id: row.try_get(0)?,
+            queue: row.try_get(1)?,
+            payload: serde_json::from_str(&payload).map_err(|e| sqlx::Error::ColumnDecode {
+                index: "payload".into(),
+                source: Box::new(e),
+            })?,
+            attempts: row.try_get(3)?,
+        };
+        let id = job.id;
+        let attempts = job.attempts + 1;
+
+        match handler(job).await {
+            Ok(()) => {
+                self.orm
+                    .statement(&format!("DELETE FROM {} WHERE id = $1", table))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            Err(e) if attempts >= max_attempts => {
+                let sql = format!(
+                    "UPDATE {} SET attempts = $2, last_error = $3, failed_at = now() WHERE id = $1",
+                    table
+                );
+                self.orm
+                    .statement(&sql)
+                    .bind(id)
+                    .bind(attempts)
+                    .bind(e.to_string())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            Err(e) => {
+                let delay = self
+                    .retry_delay
+                    .saturating_mul(2u32.saturating_pow(attempts as u32 - 1));
+                let sql = format!(
+                    "UPDATE {} SET attempts = $2, last_error = $3, \
+                     run_at = now() + make_interval(secs => $4) WHERE id = $1",
+                    table
+                );
+                self.orm
+                    .statement(&sql)
+                    .bind(id)
+                    .bind(attempts)
+                    .bind(e.to_string())
+                    .bind(delay.as_secs_f64())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Runs jobs until a database error occurs, sleeping for the poll
+    /// interval whenever the queue is empty.
+    pub async fn run<F, Fut>(&self, handler: F) -> sqlx::Result<()>
+    where
+        F: Fn(Job) -> Fut,
+        Fut: Future<Output = Result<(), JobError>>,
+    {
+        loop {
+            if !self.run_once(&handler).await? {
+                tokio::time::sleep(self.poll_interval).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::time::Duration;
+
+/// What `OrmStruct::migrate` changed, for deploy logs.
+///
+/// Its `Display` form lists the changes and then every statement with its
+/// run time:
+///
+/// ```text
+/// created table orders
+/// added column users.phone
+/// 3 statements in 12.4ms
+///   4.1ms  CREATE TABLE IF NOT EXISTS orders (...)
+///   ...
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub tables_created: Vec<String>,
+    /// `(table, column)` pairs.
+    pub columns_added: Vec<(String, String)>,
+    pub indexes_created: Vec<String>,
+    /// `(table, column)` pairs whose foreign key was added.
+    pub foreign_keys_added: Vec<(String, String)>,
+    /// Tables dropped by `OrmStruct::migrate_destructive`.
+    pub tables_dropped: Vec<String>,
+    /// `(table, column)` pairs dropped by `OrmStruct::migrate_destructive`.
+    pub columns_dropped: Vec<(String, String)>,
+    /// Set for a dry run: the statements were planned but not run, and the
+    /// changes listed were not made.
+    pub dry_run: bool,
+    /// Every statement run, in order, including those that changed nothing
+    /// (`CREATE TABLE IF NOT EXISTS` on an existing table).
+    pub statements: Vec<ExecutedStatement>,
+}
+
+/// A statement run by a migration and how long it took.
+#[derive(Debug, Clone)]
+pub struct ExecutedStatement {
+    pub sql: String,
+    pub duration: Duration,
+}
+
+impl MigrationReport {
+    /// Whether the migration changed the schema.
+    pub fn has_changes(&self) -> bool {
+        !self.tables_created.is_empty()
+            || !self.columns_added.is_empty()
+            || !self.indexes_created.is_empty()
+            || !self.foreign_keys_added.is_empty()
+            || !self.tables_dropped.is_empty()
+            || !self.columns_dropped.is_empty()
+    }
+
+    /// Total time spent running statements.
+    pub fn duration(&self) -> Duration {
+        self.statements.iter().map(|s| s.duration).sum()
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dry_run {
+            writeln!(f, "dry run, nothing was executed")?;
+        }
+        for table in &self.tables_created {
+            writeln!(f, "created table {}", table)?;
+        }
+        for (table, column) in &self.columns_added {
+            writeln!(f, "added column {}.{}", table, column)?;
+        }
+        for index in &self.indexes_created {
+            writeln!(f, "created index {}", index)?;
+        }
+        for (table, column) in &self.foreign_keys_added {
+            writeln!(f, "added foreign key {}.{}", table, column)?;
+        }
+        for table in &self.tables_dropped {
+            writeln!(f, "dropped table {}", table)?;
+        }
+        for (table, column) in &self.columns_dropped {
+            writeln!(f, "dropped column {}.{}", table, column)?;
+        }
+        if !self.has_changes() {
+            writeln!(f, "schema up to date")?;
+        }
+        if self.dry_run {
+            writeln!(f, "{} statements", self.statements.len())?;
+            for statement in &self.statements {
+                writeln!(f, "  {}", statement.sql)?;
+            }
+            return Ok(());
+        }
+        writeln!(
+            f,
+            "{} statements in {:.1?}",
+            self.statements.len(),
+            self.duration()
+        )?;
+        for statement in &self.statements {
+            writeln!(f, "  {:>8.1?}  {}", statement.duration, statement.sql)?;
+        }
+        Ok(())
+    }
+}
+
+/// Safety switches of `OrmStruct::migrate_destructive`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DestructiveOptions {
+    pub(crate) allow_data_loss: bool,
+    pub(crate) dry_run: bool,
+}
+
+impl DestructiveOptions {
+    /// Refuses to drop anything until configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Confirms that dropping tables and columns, and the data in them, is
+    /// intended.
+    pub fn allow_data_loss(mut self) -> Self {
+        self.allow_data_loss = true;
+        self
+    }
+
+    /// Plans the migration and returns its report without running anything.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+}
+
+/// Name of the index a `CREATE [UNIQUE] INDEX [IF NOT EXISTS] name ON ...`
+/// statement creates.
+pub(crate) fn created_index(statement: &str) -> Option<&str> {
+    let rest = statement
+        .strip_prefix("CREATE INDEX ")
+        .or_else(|| statement.strip_prefix("CREATE UNIQUE INDEX "))?;
+    let rest = rest.strip_prefix("IF NOT EXISTS ").unwrap_or(rest);
+    rest.split_whitespace().next()
+}
@@ -1,10 +1,68 @@
+pub mod batch;
+pub mod breaker;
+#[cfg(feature = "cdc")]
+pub mod cdc;
+pub mod crypto;
+#[cfg(feature = "csv")]
+pub mod csv_io;
+pub mod diagram;
+pub mod dump;
+pub mod events;
+#[cfg(feature = "geo")]
+pub mod geo;
+pub mod grant;
+pub mod hstore;
+pub mod id;
+pub mod jobs;
+#[cfg(feature = "migrate")]
+pub mod migration;
+pub mod naming;
 pub mod new_orm;
+pub mod op;
+pub mod options;
 pub mod orm;
+#[cfg(feature = "parquet")]
+pub mod parquet_io;
+#[cfg(feature = "query-builder")]
 pub mod query_builder;
+pub mod range;
+pub mod read_only;
+pub mod row;
 pub mod schema;
+pub mod session;
+pub mod shard;
+pub mod stats;
+pub mod value;
 
 // Re-export them for easier access from main.rs
+pub use batch::*;
+pub use breaker::*;
+pub use crypto::*;
+#[cfg(feature = "csv")]
+pub use csv_io::*;
+pub use diagram::*;
+pub use dump::*;
+pub use events::*;
+#[cfg(feature = "geo")]
+pub use geo::*;
+pub use grant::*;
+pub use id::*;
+#[cfg(feature = "migrate")]
+pub use migration::*;
+pub use naming::*;
 pub use new_orm::*;
+pub use op::*;
+pub use options::*;
 pub use orm::*;
+#[cfg(feature = "parquet")]
+pub use parquet_io::*;
+#[cfg(feature = "query-builder")]
 pub use query_builder::*;
+pub use range::*;
+pub use read_only::*;
+pub use row::*;
 pub use schema::*;
+pub use session::*;
+pub use shard::*;
+pub use stats::*;
+pub use value::*;
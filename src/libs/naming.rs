@@ -0,0 +1,142 @@
+/// Derives table names from model struct names, for models whose
+/// `#[slint]` attribute doesn't give a `table_name`. Configure one with
+/// `OrmStruct::with_naming`.
+pub trait NamingStrategy: Send + Sync {
+    fn table_name(&self, model: &str) -> String;
+}
+
+/// The macro's own convention: the struct name lowercased
+/// (`UserProfile` → `userprofile`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lowercase;
+
+impl NamingStrategy for Lowercase {
+    fn table_name(&self, model: &str) -> String {
+        model.to_lowercase()
+    }
+}
+
+/// Configurable naming: snake_case words, a pluralized last word and a
+/// prefix, each optional.
+///
+/// # Example
+/// ```
+/// # use slintrust::*;
+/// # let (url, schemas) = ("postgres://localhost/app".to_string(), Vec::new());
+/// let rules = NamingRules::new().snake_case().plural().prefix("app_");
+/// assert_eq!(rules.table_name("UserProfile"), "app_user_profiles");
+/// let orm = OrmStruct::new(url, schemas).with_naming(rules);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NamingRules {
+    snake_case: bool,
+    plural: bool,
+    prefix: String,
+}
+
+impl NamingRules {
+    /// Lowercased struct names, like [`Lowercase`], until configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Split words at case changes: `UserProfile` → `user_profile`.
+    pub fn snake_case(mut self) -> Self {
+        self.snake_case = true;
+        self
+    }
+
+    /// Pluralize the last word with English rules: `category` →
+    /// `categories`, `address` → `addresses`, `user` → `users`.
+    pub fn plural(mut self) -> Self {
+        self.plural = true;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+}
+
+impl NamingStrategy for NamingRules {
+    fn table_name(&self, model: &str) -> String {
+        let name = if self.snake_case {
+            snake_case(model)
+        } else {
+            model.to_lowercase()
+        };
+        let name = if self.plural { pluralize(&name) } else { name };
+        format!("{}{}", self.prefix, name)
+    }
+}
+
+/// `HTTPRequestLog` → `http_request_log`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            // A new word starts after a lowercase letter or digit, or at the
+            // last capital of an acronym followed by a lowercase letter.
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn pluralize(word: &str) -> String {
+    let ends_with_consonant_y =
+        word.ends_with('y') && !word[..word.len() - 1].ends_with(['a', 'e', 'i', 'o', 'u']);
+    if ends_with_consonant_y {
+        format!("{}ies", &word[..word.len() - 1])
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|e| word.ends_with(e))
+    {
+        format!("{}es", word)
+    } else {
+        format!("{}s", word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snake_case_splits_words_acronyms_and_digits() {
+        assert_eq!(snake_case("User"), "user");
+        assert_eq!(snake_case("UserProfile"), "user_profile");
+        assert_eq!(snake_case("HTTPRequestLog"), "http_request_log");
+        assert_eq!(snake_case("Oauth2Token"), "oauth2_token");
+        assert_eq!(snake_case("ApiV2"), "api_v2");
+    }
+
+    #[test]
+    fn pluralize_follows_english_endings() {
+        assert_eq!(pluralize("user"), "users");
+        assert_eq!(pluralize("category"), "categories");
+        assert_eq!(pluralize("day"), "days");
+        assert_eq!(pluralize("address"), "addresses");
+        assert_eq!(pluralize("box"), "boxes");
+        assert_eq!(pluralize("batch"), "batches");
+        assert_eq!(pluralize("wish"), "wishes");
+    }
+
+    #[test]
+    fn rules_pluralize_only_the_last_word() {
+        let rules = NamingRules::new().snake_case().plural().prefix("app_");
+        assert_eq!(rules.table_name("UserCategory"), "app_user_categories");
+        assert_eq!(
+            NamingRules::new().table_name("UserCategory"),
+            "usercategory"
+        );
+        assert_eq!(Lowercase.table_name("UserCategory"), "usercategory");
+    }
+}
@@ -1,118 +1,697 @@
-use crate::OrmStruct;
+use crate::crypto::verify_hash;
+#[cfg(feature = "csv")]
+use crate::csv_io::{self, CsvImportOptions};
+use crate::events::Change;
+use crate::op::list;
+use crate::orm::{PgQuery, bind_json, project_rows};
+#[cfg(feature = "parquet")]
+use crate::parquet_io::{self, PARQUET_BATCH};
+use crate::range::element_placeholder;
+use crate::row::row_to_json;
+use crate::schema::history_table;
+use crate::stats::Operation;
+use crate::{
+    ColumnSchema, Insertable, Op, OrmStruct, ReadOnlyTable, SlintModel, SqlValue, TableSchema,
+    quote_literal,
+};
+use chrono::{DateTime, Utc};
+#[cfg(any(feature = "csv", feature = "parquet"))]
+use futures_util::TryStreamExt;
+#[cfg(feature = "parquet")]
+use parquet::arrow::AsyncArrowWriter;
+#[cfg(feature = "parquet")]
+use parquet::basic::Compression;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::Value;
-use sqlx::{Column, Row};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+#[cfg(feature = "csv")]
+use tokio::io::{AsyncBufRead, AsyncWrite, AsyncWriteExt};
 
 /// A typed handle to a database table.
+///
+/// Like [`OrmStruct`], a `Table` keeps its settings behind a shared [`Arc`],
+/// so cloning it is cheap. Handles are `Send + Sync + 'static` whatever the
+/// model type, and can be stored in web-framework state.
 pub struct Table<T> {
+    inner: Arc<TableInner>,
+    // `fn() -> T` keeps the handle `Send + Sync` without requiring it of `T`.
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Clone)]
+struct TableInner {
     orm: OrmStruct,
     name: String,
     key_column: String,
-    _marker: std::marker::PhantomData<T>,
+    unmasked: bool,
+    scopes: Vec<Condition>,
+    allowed: AllowList,
+}
+
+impl<T> Clone for Table<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            _marker: PhantomData,
+        }
+    }
 }
 
 impl<T> Table<T>
 where
     T: Serialize + DeserializeOwned + Send + Sync,
 {
-    /// Create a new table handle.
+    /// Create a new table handle. The ORM handle is shared, not copied.
     /// ```
-    /// let user_table = Table::<User>::new(orm.clone(), "users");
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     name: String,
+    /// #     email: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![User::slint_schema()]);
+    /// let user_table = Table::<User>::new(&orm, "users", "id");
     /// ```
     pub fn new(orm: &OrmStruct, name: &str, key_column: &str) -> Self {
-        Self::with_key(orm.to_owned(), name, key_column)
+        Self::with_key(orm.clone(), name, key_column)
     }
 
     /// Create a new table handle with a custom key column.
     /// ```
-    /// let user_table = Table::<User>::with_key(orm.as_ref(), "users", "user_id");
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     name: String,
+    /// #     email: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![User::slint_schema()]);
+    /// let user_table = Table::<User>::with_key(orm.clone(), "users", "email");
     /// ```
     pub fn with_key(orm: OrmStruct, name: &str, key_column: &str) -> Self {
         Self {
-            orm,
-            name: name.to_string(),
-            key_column: key_column.to_string(),
-            _marker: std::marker::PhantomData,
+            inner: Arc::new(TableInner {
+                name: orm.table_name(name),
+                orm,
+                key_column: key_column.to_string(),
+                unmasked: false,
+                scopes: Vec::new(),
+                allowed: AllowList::default(),
+            }),
+            _marker: PhantomData,
         }
     }
 
-    /// Insert a new record into the table.
+    /// A handle to the same table, with its scopes, that can only read.
+    /// See [`ReadOnlyTable`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let reports = ReportService::new(user_table.read_only());
+    /// ```
+    pub fn read_only(&self) -> ReadOnlyTable<T> {
+        ReadOnlyTable::new(self.clone())
+    }
+
+    /// A handle to the same table whose queries return `#[slint(masked)]`
+    /// columns unredacted. Reserve this for privileged call sites.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let raw = user_table.with_unmasked().query().where_clause("id", "=", id).first().await?;
+    /// ```
+    pub fn with_unmasked(&self) -> Self {
+        let mut table = self.clone();
+        Arc::make_mut(&mut table.inner).unmasked = true;
+        table
+    }
+
+    /// Register a default `column op value` condition, added to every
+    /// SELECT, UPDATE and DELETE issued through this handle, its queries and
+    /// its records. Repeated calls are combined with `AND`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let orders = Table::<Order>::from_schema(&orm).scope("tenant_id", "=", tenant_id);
+    /// ```
+    pub fn scope(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        Arc::make_mut(&mut self.inner)
+            .scopes
+            .push(Condition::new(column, op.into(), value.into()));
+        self
+    }
+
+    /// Register a default SQL predicate without bind values, such as a
+    /// soft-delete filter.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     name: String,
+    /// #     email: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![User::slint_schema()]);
+    /// # let user_table = Table::<User>::from_schema(&orm);
+    /// let live_users = user_table.scope_raw("deleted_at IS NULL");
+    /// ```
+    pub fn scope_raw(mut self, predicate: &str) -> Self {
+        Arc::make_mut(&mut self.inner)
+            .scopes
+            .push(Condition::Raw(predicate.to_string()));
+        self
+    }
+
+    /// A handle to the same table without any registered scopes, for admin
+    /// tools and maintenance jobs that must see every row.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let everyone = user_table.unscoped().get_all().await?;
+    /// ```
+    pub fn unscoped(&self) -> Self {
+        let mut table = self.clone();
+        Arc::make_mut(&mut table.inner).scopes.clear();
+        table
+    }
+
+    /// Restrict the columns that externally supplied filters may name
+    /// (`apply_filter_json`, `apply_query_string`, `try_where_clause`) to
+    /// `columns`. Without it, any readable schema column is allowed.
     ///
     /// # Example
     /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     name: String,
+    /// #     email: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![User::slint_schema()]);
+    /// let users = Table::<User>::from_schema(&orm)
+    ///     .allow_filtering(&["name", "email"])
+    ///     .allow_sorting(&["name", "created_at"]);
+    /// ```
+    pub fn allow_filtering(mut self, columns: &[&str]) -> Self {
+        Arc::make_mut(&mut self.inner).allowed.filtering =
+            Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Restrict the columns that externally supplied sort keys may name
+    /// (`apply_query_string`, `try_order_by`) to `columns`.
+    pub fn allow_sorting(mut self, columns: &[&str]) -> Self {
+        Arc::make_mut(&mut self.inner).allowed.sorting =
+            Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Create a table handle from the model's `#[slint]` schema, using its
+    /// table name and primary-key column.
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     name: String,
+    /// #     email: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![User::slint_schema()]);
+    /// let user_table = Table::<User>::from_schema(&orm);
+    /// ```
+    pub fn from_schema(orm: &OrmStruct) -> Self
+    where
+        T: SlintModel,
+    {
+        let schema = orm.schema_of::<T>();
+        let key_column = schema
+            .key_column()
+            .expect("Schema has no primary key or `id` column");
+        Self::with_key(orm.clone(), schema.name, key_column)
+    }
+
+    /// Insert a new record into the table.
+    ///
+    /// # Example
+    /// ```ignore
     /// let user_table = Table::<User>::new(&orm, "users");
     /// user_table.insert(&new_user).await?;
     /// ```
     pub async fn insert(&self, item: &T) -> sqlx::Result<()> {
-        self.orm.insert(&self.name, item).await
+        self.inner.orm.insert(&self.inner.name, item).await
     }
 
-    /// Get a single record matching a filter.
-    /// Currently supports only single-column equality filters.
+    /// Insert a record and return the ID the database assigned to the
+    /// table's `#[slint(serial)]` column.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let id = order_table.insert_returning(&order).await?;
+    /// ```
+    pub async fn insert_returning(&self, item: &T) -> sqlx::Result<i64> {
+        self.inner
+            .orm
+            .insert_returning(&self.inner.name, item)
+            .await
+    }
+
+    /// Insert a record from the model's generated `New<Model>` builder.
+    /// Optional columns left unset are generated (`uuid`) or take their
+    /// database `DEFAULT`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// user_table.insert_new(&NewUser::new("Ada".into(), "ada@mail.com".into())).await?;
+    /// ```
+    pub async fn insert_new<N>(&self, item: &N) -> sqlx::Result<()>
+    where
+        N: Insertable<Model = T>,
+    {
+        self.inner.orm.insert(&self.inner.name, item).await
+    }
+
+    /// Insert a record unless it conflicts with an existing row
+    /// (`ON CONFLICT DO NOTHING`). Returns whether a row was inserted.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let inserted = user_table.insert_ignore(&new_user).await?;
+    /// ```
+    pub async fn insert_ignore(&self, item: &T) -> sqlx::Result<bool> {
+        self.inner.orm.insert_ignore(&self.inner.name, item).await
+    }
+
+    /// Insert many records in batched statements, skipping conflicting ones.
+    /// Returns the number of rows inserted.
+    pub async fn insert_many_ignore(&self, items: &[T]) -> sqlx::Result<u64> {
+        self.inner
+            .orm
+            .insert_many_ignore(&self.inner.name, items)
+            .await
+    }
+
+    /// Insert or update many records in batched statements, matching existing
+    /// rows on `conflict_columns`. Returns the number of rows written.
     ///
     /// # Example
+    /// ```ignore
+    /// user_table.upsert_many(&users, &["email"]).await?;
     /// ```
-    /// let user = user_table.get(json!({"id": 1})).await?;
+    pub async fn upsert_many(&self, items: &[T], conflict_columns: &[&str]) -> sqlx::Result<u64> {
+        self.inner
+            .orm
+            .upsert_many(&self.inner.name, items, conflict_columns)
+            .await
+    }
+
+    /// Get a single record matching a filter.
+    /// Every key of the filter object is an equality condition on a column
+    /// of the table; conditions are combined with `AND`. Values are bound
+    /// like inserted values, so strings, numbers and booleans are all
+    /// accepted.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let user = user_table.get(json!({"id": 42, "active": true})).await?;
     /// ```
     pub async fn get(&self, filter: Value) -> sqlx::Result<Option<Record<T>>> {
-        let map = filter.as_object().expect("Filter must be an object");
-        if map.len() != 1 {
-            panic!("Only single-column filter supported");
+        let map = filter
+            .as_object()
+            .ok_or_else(|| sqlx::Error::InvalidArgument("filter must be a JSON object".into()))?;
+        if map.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "filter must contain at least one column".into(),
+            ));
         }
-        let (column, value) = map.iter().next().unwrap();
-        let obj = self
-            .orm
-            .first::<T>(&self.name, column, value.as_str().unwrap())
-            .await?;
-        Ok(obj.map(|o| {
-            Record::new(
-                self.name.clone(),
-                o,
-                self.key_column.clone(),
-                self.orm.clone(),
-            )
-        }))
+        self.query().where_json(map)?.first().await
     }
 
     /// Get all records from the table.
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// let users = user_table.get_all().await?;
     /// ```
     pub async fn get_all(&self) -> sqlx::Result<Vec<Record<T>>> {
-        let all = self.orm.get_all::<T>(&self.name).await?;
-        Ok(all
-            .into_iter()
-            .map(|o| {
-                Record::new(
-                    self.name.clone(),
-                    o,
-                    self.key_column.clone(),
-                    self.orm.clone(),
-                )
+        self.query().get().await
+    }
+
+    /// Get the record whose key column equals `key`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let user = user_table.find(42).await?;
+    /// ```
+    pub async fn find(&self, key: impl Into<SqlValue>) -> sqlx::Result<Option<Record<T>>> {
+        self.query().where_key(key).first().await
+    }
+
+    /// Delete the record whose key column equals `key` in one statement,
+    /// returning whether there was one. Records outside the table's scopes
+    /// are left alone.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let deleted = user_table.delete_by_key(42).await?;
+    /// ```
+    pub async fn delete_by_key(&self, key: impl Into<SqlValue>) -> sqlx::Result<bool> {
+        let query = self.query().where_key(key);
+        let returning = self.inner.orm.event_returning(&self.inner.name);
+        let sql = format!(
+            "DELETE FROM {} WHERE {}{}",
+            self.inner.name,
+            query.where_sql(0).unwrap_or_default(),
+            returning.as_deref().unwrap_or("")
+        );
+        let mut statement = self.inner.orm.statement(&sql);
+        for value in condition_values(query.scopes.iter().chain(&query.wheres)) {
+            statement = value.clone().bind(statement);
+        }
+        let deleted = self
+            .inner
+            .orm
+            .execute_reporting(
+                &self.inner.name,
+                statement,
+                returning.is_some(),
+                Change::Delete,
+            )
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// Update every record matching `filter` with `changes` in one statement
+    /// and return the `returning` columns of each changed row (all columns
+    /// when empty). Both objects must name columns of the table, and
+    /// `filter` at least one.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let changed = user_table
+    ///     .update_where_returning(json!({"active": false}), json!({"status": "archived"}), &["id", "updated_at"])
+    ///     .await?;
+    /// ```
+    pub async fn update_where_returning(
+        &self,
+        filter: Value,
+        changes: Value,
+        returning: &[&str],
+    ) -> sqlx::Result<Vec<Value>> {
+        let filter = filter
+            .as_object()
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| {
+                sqlx::Error::InvalidArgument("filter must be a non-empty JSON object".into())
+            })?;
+        self.query()
+            .where_json(filter)?
+            .update_returning(changes, returning)
+            .await
+    }
+
+    /// Every record below `id` in a tree stored as a self-referencing
+    /// `parent_column`, at any depth (the record itself is not included).
+    ///
+    /// # Example
+    /// ```ignore
+    /// let subcategories = category_table.descendants_of("electronics", "parent_id").await?;
+    /// ```
+    pub async fn descendants_of(
+        &self,
+        id: impl Into<SqlValue>,
+        parent_column: &str,
+    ) -> sqlx::Result<Vec<Record<T>>> {
+        let sql = format!(
+            "WITH RECURSIVE tree AS (\
+             SELECT * FROM {table} WHERE {parent} = $1 \
+             UNION \
+             SELECT c.* FROM {table} c JOIN tree p ON c.{parent} = p.{key}\
+             ) SELECT {columns} FROM tree{scopes}",
+            table = self.inner.name,
+            parent = parent_column,
+            key = self.inner.key_column,
+            columns = self.inner.orm.select_list(&self.inner.name),
+            scopes = self.scope_sql(),
+        );
+        self.fetch_tree(&sql, id.into()).await
+    }
+
+    /// Every record above `id` in a tree stored as a self-referencing
+    /// `parent_column`, from its parent up to the root.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let chain_of_command = employee_table.ancestors_of(42, "manager_id").await?;
+    /// ```
+    pub async fn ancestors_of(
+        &self,
+        id: impl Into<SqlValue>,
+        parent_column: &str,
+    ) -> sqlx::Result<Vec<Record<T>>> {
+        let sql = format!(
+            "WITH RECURSIVE tree AS (\
+             SELECT p.* FROM {table} p JOIN {table} c ON c.{parent} = p.{key} WHERE c.{key} = $1 \
+             UNION \
+             SELECT p.* FROM {table} p JOIN tree c ON c.{parent} = p.{key}\
+             ) SELECT {columns} FROM tree{scopes}",
+            table = self.inner.name,
+            parent = parent_column,
+            key = self.inner.key_column,
+            columns = self.inner.orm.select_list(&self.inner.name),
+            scopes = self.scope_sql(),
+        );
+        self.fetch_tree(&sql, id.into()).await
+    }
+
+    /// ` WHERE ...` for the registered scopes, numbered after the tree's `$1`.
+    fn scope_sql(&self) -> String {
+        let conds = conditions_sql(&self.inner.scopes, 1, &self.inner.orm, &self.inner.name);
+        if conds.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conds.join(" AND "))
+        }
+    }
+
+    // UNION (not UNION ALL) in the recursive term stops on cyclic data.
+    async fn fetch_tree(&self, sql: &str, id: SqlValue) -> sqlx::Result<Vec<Record<T>>> {
+        let mut query = id.bind(self.inner.orm.statement(sql));
+        for value in condition_values(&self.inner.scopes) {
+            query = value.clone().bind(query);
+        }
+        let orm = &self.inner.orm;
+        let rows = orm
+            .timed(
+                &self.inner.name,
+                Operation::Select,
+                query.fetch_all(orm.pool()),
+            )
+            .await?;
+        rows.iter()
+            .map(|r| {
+                orm.decode_with::<T>(&self.inner.name, r, self.inner.unmasked)
+                    .map(|o| self.record(o))
             })
-            .collect())
+            .collect()
+    }
+
+    fn record(&self, value: T) -> Record<T> {
+        Record::new(
+            self.inner.name.clone(),
+            value,
+            self.inner.key_column.clone(),
+            self.inner.orm.clone(),
+        )
+        .with_scopes(self.inner.scopes.clone())
     }
 
     /// Create a query builder for advanced queries.
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// let users = user_table
     ///     .query()
-    ///     .where_clause("age", ">", "18")
-    ///     .order_by("name", "ASC")
+    ///     .where_clause("age", ">", 18)
+    ///     .order_by("name", Direction::Asc)
     ///     .limit(10)
     ///     .offset(5)
     ///     .distinct()
     ///     .group_by(&["department"])
-    ///     .having("count", ">", "1")
+    ///     .having("count", ">", 1)
     ///     .get()
     ///     .await?;
     /// ```
     pub fn query(&self) -> Query<'_, T> {
-        Query::new(self.name.clone(), self.key_column.clone(), &self.orm)
+        let mut query = Query::new(
+            self.inner.name.clone(),
+            self.inner.key_column.clone(),
+            &self.inner.orm,
+        );
+        query.unmasked = self.inner.unmasked;
+        query.scopes = self.inner.scopes.clone();
+        query.allowed = self.inner.allowed.clone();
+        query
+    }
+
+    /// Query the table as it was at `at`, from the history kept for
+    /// `#[slint(versioned)]` tables. Meant for reads: writes through the
+    /// returned query still apply to the current rows.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let yesterday = Utc::now() - chrono::Duration::days(1);
+    /// let old = users.as_of(yesterday).where_clause("id", "=", "42").first().await?;
+    /// ```
+    pub fn as_of(&self, at: DateTime<Utc>) -> Query<'_, T> {
+        let at = quote_literal(&at.to_rfc3339());
+        let mut query = self.query();
+        query.source = Some(format!(
+            "(SELECT * FROM {} WHERE slint_valid_from <= {at}::TIMESTAMPTZ \
+             AND (slint_valid_to IS NULL OR slint_valid_to > {at}::TIMESTAMPTZ))",
+            history_table(&self.inner.name),
+            at = at
+        ));
+        query
+    }
+
+    /// Stream the rows `query` returns to `writer` as CSV, with a header
+    /// of the selected columns. NULLs are empty fields and empty strings
+    /// `""`, so the two read back apart; arrays and JSON values are written
+    /// as JSON. Masked columns are redacted unless the table is
+    /// [`with_unmasked`](Self::with_unmasked). Returns the number of rows
+    /// written.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut file = tokio::fs::File::create("users.csv").await?;
+    /// users.export_csv(&mut file, users.query().where_clause("active", "=", true)).await?;
+    /// ```
+    #[cfg(feature = "csv")]
+    pub async fn export_csv<W>(&self, writer: &mut W, query: Query<'_, T>) -> sqlx::Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let sql = query.build_sql();
+        let mut statement = query.orm.statement(&sql);
+        for val in query.bind_values() {
+            statement = val.bind(statement);
+        }
+        let started = std::time::Instant::now();
+        let mut rows = statement.fetch(query.orm.pool());
+        let mut header = None;
+        let mut written = 0;
+        while let Some(row) = rows.try_next().await? {
+            let header = match &mut header {
+                Some(header) => header,
+                None => {
+                    let columns: Vec<String> = row
+                        .columns()
+                        .iter()
+                        .map(|c| sqlx::Column::name(c).to_string())
+                        .collect();
+                    writer
+                        .write_all(csv_io::header_line(&columns).as_bytes())
+                        .await?;
+                    header.insert(columns)
+                }
+            };
+            let row = query
+                .orm
+                .row_json_with(&query.table_name, &row, query.unmasked)?;
+            writer
+                .write_all(csv_io::row_line(header, &row).as_bytes())
+                .await?;
+            written += 1;
+        }
+        if header.is_none() {
+            // Nothing matched: the columns a plain select would return.
+            let columns = query
+                .orm
+                .find_schema(&query.table_name)
+                .map_or_else(Vec::new, |s| {
+                    s.columns
+                        .iter()
+                        .filter(|c| c.hashed.is_none())
+                        .map(|c| c.name.to_string())
+                        .collect()
+                });
+            writer
+                .write_all(csv_io::header_line(&columns).as_bytes())
+                .await?;
+        }
+        writer.flush().await?;
+        query.orm.record_stat(
+            &query.table_name,
+            Operation::Select,
+            started.elapsed(),
+            written,
+        );
+        Ok(written)
+    }
+
+    /// Load a CSV file with a header row into the table, reading it a
+    /// record at a time and inserting `options.batch_size` rows per
+    /// statement. Fields are matched to columns by header (see
+    /// [`CsvImportOptions::map_column`]); empty fields take the column's
+    /// `DEFAULT` and quoted empty fields (`""`) are empty strings. Values
+    /// are hashed and encrypted like any insert. Returns the number of rows
+    /// inserted.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let file = tokio::io::BufReader::new(tokio::fs::File::open("users.csv").await?);
+    /// let options = CsvImportOptions::new().map_column("E-mail", "email").ignore_conflicts();
+    /// let loaded = users.import_csv(file, options).await?;
+    /// ```
+    #[cfg(feature = "csv")]
+    pub async fn import_csv<R>(&self, reader: R, options: CsvImportOptions) -> sqlx::Result<u64>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let orm = &self.inner.orm;
+        let suffix = if options.ignore_conflicts {
+            " ON CONFLICT DO NOTHING"
+        } else {
+            ""
+        };
+        let mut reader = csv_io::RecordReader::new(reader, options.delimiter);
+        let Some(headers) = reader.next().await? else {
+            return Ok(0);
+        };
+        let targets = options.targets(&headers, orm.schema_for(&self.inner.name))?;
+        let mut batch = Vec::with_capacity(options.batch_size);
+        let mut inserted = 0;
+        while let Some(record) = reader.next().await? {
+            batch.push(csv_io::record_row(&targets, record));
+            if batch.len() == options.batch_size {
+                inserted += orm.insert_rows(&self.inner.name, &batch, suffix).await?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            inserted += orm.insert_rows(&self.inner.name, &batch, suffix).await?;
+        }
+        Ok(inserted)
     }
 }
 
@@ -122,7 +701,10 @@ pub struct Record<T> {
     pub value: T,
     key_column: String,
     orm: OrmStruct,
+    /// The key as the value serialized it: a string, a number, ...
     id: serde_json::Value,
+    scopes: Vec<Condition>,
+    related: HashMap<String, Vec<Value>>,
 }
 
 impl<T> Record<T>
@@ -136,121 +718,1187 @@ where
             .unwrap()
             .clone();
         Self {
-            table_name,
+            table_name: orm.table_name(&table_name),
             value,
             key_column,
             orm,
             id,
+            scopes: Vec::new(),
+            related: HashMap::new(),
+        }
+    }
+
+    /// Carries the owning table's scopes, so instance updates and deletes
+    /// cannot reach rows outside them.
+    fn with_scopes(mut self, scopes: Vec<Condition>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// ` AND ...` for the table's scopes, numbered after `offset`.
+    fn scope_sql(&self, offset: usize) -> String {
+        conditions_sql(&self.scopes, offset, &self.orm, &self.table_name)
+            .iter()
+            .map(|c| format!(" AND {}", c))
+            .collect()
+    }
+
+    /// `key = $index`, with the placeholder cast to the key column's type.
+    fn key_condition(&self, index: usize) -> String {
+        format!(
+            "{} = {}",
+            self.key_column,
+            self.orm
+                .placeholder(&self.table_name, &self.key_column, index)
+        )
+    }
+
+    /// Binds the record's key for [`key_condition`](Self::key_condition).
+    /// Keys of registered columns are bound as text for the placeholder's
+    /// cast; others keep their type, so integer keys still compare with
+    /// integer columns.
+    fn bind_key<'q>(&self, query: PgQuery<'q>) -> PgQuery<'q> {
+        let registered = self
+            .orm
+            .find_schema(&self.table_name)
+            .is_some_and(|s| s.column(&self.key_column).is_some());
+        if registered {
+            bind_json(query, self.id.clone())
+        } else {
+            SqlValue::from_key(self.id.clone()).bind(query)
         }
     }
 
     /// Update the current record with changes.
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// record.update(json!({"name": "Joe"})).await?;
     /// ```
     pub async fn update(&self, updates: serde_json::Value) -> sqlx::Result<T> {
-        let map = updates.as_object().expect("updates must be an object");
+        let mut map = updates
+            .as_object()
+            .expect("updates must be an object")
+            .clone();
+        self.orm.stamp_update(&self.table_name, &mut map);
         let mut sets = Vec::new();
-        let mut values: Vec<String> = Vec::new();
-        for (key, value) in map {
-            sets.push(format!("{} = ${}", key, values.len() + 1));
-            values.push(value.as_str().expect("value must be string").to_string());
+        let mut values = Vec::new();
+        for (key, value) in &map {
+            let placeholder = self
+                .orm
+                .placeholder(&self.table_name, key, values.len() + 1);
+            sets.push(format!("{} = {}", key, placeholder));
+            values.push(
+                self.orm
+                    .encode_column(&self.table_name, key, value.clone())?,
+            );
         }
+        let returning = self.orm.event_returning(&self.table_name);
         let sql = format!(
-            "UPDATE {} SET {} WHERE {} = ${}",
+            "UPDATE {} SET {} WHERE {}{}{}",
             self.table_name,
             sets.join(", "),
-            self.key_column,
-            values.len() + 1
+            self.key_condition(values.len() + 1),
+            self.scope_sql(values.len() + 1),
+            returning.as_deref().unwrap_or("")
         );
-        let mut query = sqlx::query(&sql);
-        for value in &values {
-            query = query.bind(value);
+        let mut query = self.orm.statement(&sql);
+        for value in values {
+            query = bind_json(query, value);
         }
-        query = query.bind(self.id.as_str().unwrap());
-        query.execute(self.orm.pool.as_ref().unwrap()).await?;
+        query = self.bind_key(query);
+        for value in condition_values(&self.scopes) {
+            query = value.clone().bind(query);
+        }
+        self.orm
+            .execute_reporting(&self.table_name, query, returning.is_some(), Change::Update)
+            .await?;
 
         // Fetch the updated record
-        let updated = self
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {}",
+            self.orm.select_list(&self.table_name),
+            self.table_name,
+            self.key_condition(1)
+        );
+        let query = self.bind_key(self.orm.statement(&sql));
+        let row = self
             .orm
-            .query(&self.table_name)
-            .r#where(&self.key_column, "=", self.id.as_str().unwrap())
-            .fetch_one()
+            .timed(
+                &self.table_name,
+                Operation::Select,
+                query.fetch_one(self.orm.pool()),
+            )
             .await?;
-        Ok(updated)
+        self.orm.decode::<T>(&self.table_name, &row)
     }
 
-    /// Delete the current record from the table.
+    /// Updates the columns whose values differ between `updated` and the
+    /// record's value, leaving every other column as it is, and returns the
+    /// row as stored. When the two are equal nothing is written and the
+    /// record's value is returned.
     ///
     /// # Example
+    /// ```ignore
+    /// let mut user = record.value.clone();
+    /// user.name = "Joe".into();
+    /// let user = record.update_from(&user).await?; // UPDATE ... SET name = $1
     /// ```
-    /// record.delete().await?;
+    pub async fn update_from(&self, updated: &T) -> sqlx::Result<T> {
+        let to_object = |value: &T| match serde_json::to_value(value) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Err(sqlx::Error::InvalidArgument(
+                "update_from needs a model that serializes to an object".into(),
+            )),
+            Err(e) => Err(sqlx::Error::Encode(Box::new(e))),
+        };
+        let current = to_object(&self.value)?;
+        let changes: serde_json::Map<String, Value> = to_object(updated)?
+            .into_iter()
+            .filter(|(column, value)| current.get(column) != Some(value))
+            .collect();
+        if changes.is_empty() {
+            return from_json(Value::Object(current));
+        }
+        self.update(Value::Object(changes)).await
+    }
+
+    /// Loads the `relation` children of every record in one query and
+    /// attaches them, instead of one query per record. `relation` names the
+    /// child table, which must have a `#[slint(foreign_key = "...")]` column
+    /// referring to this table; read the children with
+    /// [`related`](Self::related).
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut users = user_table.get_all().await?;
+    /// Record::preload(&mut users, "posts").await?;
+    /// for user in &users {
+    ///     let posts: Vec<Post> = user.related("posts")?;
+    /// }
     /// ```
-    pub async fn delete(&self) -> sqlx::Result<()> {
-        let id = serde_json::to_value(&self.value)
-            .unwrap()
-            .get(&self.key_column)
-            .expect("key field required")
-            .as_str()
-            .expect("key must be string")
-            .to_string();
+    pub async fn preload(records: &mut [Record<T>], relation: &str) -> sqlx::Result<()> {
+        let Some(first) = records.first() else {
+            return Ok(());
+        };
+        let orm = first.orm.clone();
+        let parent_table = first.table_name.clone();
+        let child = orm.find_schema(relation).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("unknown relation `{}`", relation))
+        })?;
+        let (fk, parent_column) = child
+            .columns
+            .iter()
+            .find_map(|c| match c.references() {
+                Some((table, column)) if orm.table_name(table) == parent_table => Some((c, column)),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                sqlx::Error::InvalidArgument(format!(
+                    "`{}` has no foreign key referring to `{}`",
+                    relation, parent_table
+                ))
+            })?;
+        let parent_column = parent_column.unwrap_or(&first.key_column).to_string();
 
-        self.orm
-            .delete(&self.table_name, &self.key_column, &id)
-            .await
-    }
-}
+        let mut keys = Vec::with_capacity(records.len());
+        for record in records.iter() {
+            let value = serde_json::to_value(&record.value)
+                .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+            keys.push(key_text(value.get(&parent_column)));
+        }
+        let params: Vec<String> = keys.iter().flatten().cloned().collect();
 
-/// Query builder for advanced queries with WHERE, LIMIT, ORDER BY.
-pub struct Query<'a, T> {
-    table_name: String,
-    key_column: String,
-    orm: &'a OrmStruct,
-    wheres: Vec<(String, String, String)>,
-    limit: Option<u32>,
-    offset: Option<u32>,
-    order_by: Option<(String, String)>,
-    distinct: bool,
-    group_by: Vec<String>,
-    havings: Vec<(String, String, String)>,
-    _marker: std::marker::PhantomData<T>,
-}
+        // Keys are bound as text and cast to the foreign key's type, so the
+        // column's index stays usable.
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} = ANY($1::{}[])",
+            child.select_list(),
+            child.name,
+            fk.name,
+            fk.sql_type
+        );
+        let query = orm.statement(&sql).bind(params);
+        let rows = orm
+            .timed(child.name, Operation::Select, query.fetch_all(orm.pool()))
+            .await?;
+        let mut children: HashMap<String, Vec<Value>> = HashMap::new();
+        for row in &rows {
+            let value = orm.row_json(child.name, row)?;
+            if let Some(key) = key_text(value.get(fk.name)) {
+                children.entry(key).or_default().push(value);
+            }
+        }
 
-impl<'a, T> Query<'a, T>
-where
-    T: Serialize + DeserializeOwned + Send + Sync,
-{
-    pub fn new(table_name: String, key_column: String, orm: &'a OrmStruct) -> Self {
-        Self {
-            table_name,
-            key_column,
-            orm,
-            wheres: Vec::new(),
-            limit: None,
-            offset: None,
-            order_by: None,
-            distinct: false,
-            group_by: Vec::new(),
-            havings: Vec::new(),
-            _marker: std::marker::PhantomData,
+        for (record, key) in records.iter_mut().zip(keys) {
+            let found = key.and_then(|k| children.get(&k).cloned());
+            record
+                .related
+                .insert(child.name.to_string(), found.unwrap_or_default());
         }
+        Ok(())
     }
 
-    pub fn where_clause(mut self, column: &str, op: &str, value: &str) -> Self {
-        self.wheres
-            .push((column.to_string(), op.to_string(), value.to_string()));
-        self
+    /// The `relation` children attached by [`preload`](Self::preload) or
+    /// `Query::with_related`, deserialized into `C`.
+    pub fn related<C>(&self, relation: &str) -> sqlx::Result<Vec<C>>
+    where
+        C: DeserializeOwned,
+    {
+        let values = self
+            .related
+            .get(&self.orm.table_name(relation))
+            .ok_or_else(|| {
+                sqlx::Error::InvalidArgument(format!("relation `{}` was not preloaded", relation))
+            })?;
+        values.iter().map(|v| from_json(v.clone())).collect()
     }
 
-    pub fn limit(mut self, n: u32) -> Self {
-        self.limit = Some(n);
-        self
+    /// Checks `candidate` against the stored hash in a
+    /// `#[slint(hashed = "argon2")]` column of this record. The hash itself
+    /// is never selected by default, so it is fetched here by key.
+    pub async fn verify_hashed(&self, column: &str, candidate: &str) -> sqlx::Result<bool> {
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {}",
+            column,
+            self.table_name,
+            self.key_condition(1)
+        );
+        let query = self.bind_key(self.orm.statement(&sql));
+        let row = self
+            .orm
+            .timed(
+                &self.table_name,
+                Operation::Select,
+                query.fetch_optional(self.orm.pool()),
+            )
+            .await?;
+        let hash = match row {
+            Some(row) => row.try_get::<Option<String>, _>(0)?,
+            None => None,
+        };
+        Ok(hash.is_some_and(|hash| verify_hash(&hash, candidate)))
     }
 
-    pub fn order_by(mut self, column: &str, direction: &str) -> Self {
-        self.order_by = Some((column.to_string(), direction.to_string()));
+    /// Delete the current record from the table.
+    ///
+    /// # Example
+    /// ```ignore
+    /// record.delete().await?;
+    /// ```
+    pub async fn delete(&self) -> sqlx::Result<()> {
+        let returning = self.orm.event_returning(&self.table_name);
+        let sql = format!(
+            "DELETE FROM {} WHERE {}{}{}",
+            self.table_name,
+            self.key_condition(1),
+            self.scope_sql(1),
+            returning.as_deref().unwrap_or("")
+        );
+        let mut query = self.bind_key(self.orm.statement(&sql));
+        for value in condition_values(&self.scopes) {
+            query = value.clone().bind(query);
+        }
+        self.orm
+            .execute_reporting(&self.table_name, query, returning.is_some(), Change::Delete)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Decodes `%XX` escapes and `+` (space) in a query-string component.
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// A key as text, for matching parent and child keys of any type.
+fn key_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// Where `%` wildcards are placed around a `like`/`ilike` pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wildcard {
+    /// `%pattern%` — contains.
+    #[default]
+    Both,
+    /// `%pattern` — ends with.
+    Prefix,
+    /// `pattern%` — starts with.
+    Suffix,
+    /// The pattern is used as given.
+    None,
+}
+
+impl Wildcard {
+    pub fn apply(self, pattern: &str) -> String {
+        match self {
+            Wildcard::Both => format!("%{}%", pattern),
+            Wildcard::Prefix => format!("%{}", pattern),
+            Wildcard::Suffix => format!("{}%", pattern),
+            Wildcard::None => pattern.to_string(),
+        }
+    }
+}
+
+/// Sort direction for `Query::order_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    pub(crate) fn keyword(self) -> &'static str {
+        match self {
+            Direction::Asc => "ASC",
+            Direction::Desc => "DESC",
+        }
+    }
+}
+
+/// Placement of NULLs in an ordered result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone)]
+struct OrderBy {
+    column: String,
+    direction: Direction,
+    nulls: Option<Nulls>,
+    /// Orders by `similarity(column, $n)` with this text bound, rather
+    /// than by the column itself.
+    similar_to: Option<SqlValue>,
+}
+
+impl OrderBy {
+    /// The sort key, with its placeholder (if any) numbered `index`.
+    fn to_sql(&self, index: usize) -> String {
+        let key = match self.similar_to {
+            Some(_) => format!("similarity({}, ${})", self.column, index),
+            None => self.column.clone(),
+        };
+        let mut sql = format!("{} {}", key, self.direction.keyword());
+        match self.nulls {
+            Some(Nulls::First) => sql.push_str(" NULLS FIRST"),
+            Some(Nulls::Last) => sql.push_str(" NULLS LAST"),
+            None => {}
+        }
+        sql
+    }
+}
+
+/// How `group_by` columns are grouped: plainly, or with the subtotal rows
+/// of `ROLLUP` / `CUBE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Grouping {
+    #[default]
+    Plain,
+    Rollup,
+    Cube,
+}
+
+impl Grouping {
+    fn to_sql(self, columns: &[String]) -> String {
+        let columns = columns.join(", ");
+        match self {
+            Grouping::Plain => columns,
+            Grouping::Rollup => format!("ROLLUP ({})", columns),
+            Grouping::Cube => format!("CUBE ({})", columns),
+        }
+    }
+}
+
+/// Columns external input may filter or sort on; `None` allows every
+/// readable schema column.
+#[derive(Debug, Clone, Default)]
+struct AllowList {
+    filtering: Option<Vec<String>>,
+    sorting: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Access {
+    Filter,
+    Sort,
+}
+
+/// Set operation used to combine two queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Union,
+    UnionAll,
+    Intersect,
+    IntersectAll,
+    Except,
+    ExceptAll,
+}
+
+impl SetOp {
+    fn as_sql(self) -> &'static str {
+        match self {
+            SetOp::Union => "UNION",
+            SetOp::UnionAll => "UNION ALL",
+            SetOp::Intersect => "INTERSECT",
+            SetOp::IntersectAll => "INTERSECT ALL",
+            SetOp::Except => "EXCEPT",
+            SetOp::ExceptAll => "EXCEPT ALL",
+        }
+    }
+}
+
+/// A single `WHERE` condition of a query or table scope.
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    /// `column op $n`; `typed` casts the placeholder to the column's
+    /// declared type, as JSON filters bind text.
+    Compare {
+        column: String,
+        op: String,
+        value: SqlValue,
+        typed: bool,
+    },
+    /// `column @> $n`, with the placeholder cast to the range column's
+    /// element type.
+    RangeContains { column: String, value: SqlValue },
+    /// `ST_DWithin` of geometry `column` and the point bound at `$n`.
+    #[cfg(feature = "geo")]
+    WithinDistance {
+        column: String,
+        point: SqlValue,
+        meters: f64,
+    },
+    /// `column = ANY($n)`, with `values` bound as one array; `negated`
+    /// renders `column <> ALL($n)` instead.
+    Any {
+        column: String,
+        values: SqlValue,
+        negated: bool,
+    },
+    /// `column % $n AND similarity(column, $n) >= $n+1`, from `pg_trgm`,
+    /// with the text and the minimum bound.
+    Similar {
+        column: String,
+        text: SqlValue,
+        min: SqlValue,
+    },
+    /// A fixed predicate without bind values.
+    Raw(String),
+}
+
+impl Condition {
+    /// `column op value`, bound with the value's own type.
+    fn new(column: &str, op: Op, value: SqlValue) -> Condition {
+        let column = column.to_string();
+        match op {
+            Op::IsNull | Op::IsNotNull => Condition::Raw(format!("{} {}", column, op)),
+            Op::In | Op::NotIn => match list(value) {
+                SqlValue::List(values) if values.is_empty() => {
+                    Condition::Raw(if op == Op::In { "FALSE" } else { "TRUE" }.to_string())
+                }
+                values => Condition::Any {
+                    column,
+                    values,
+                    negated: op == Op::NotIn,
+                },
+            },
+            op => Condition::Compare {
+                column,
+                op: op.to_string(),
+                value,
+                typed: false,
+            },
+        }
+    }
+
+    fn to_sql(&self, index: usize, orm: &OrmStruct, table_name: &str) -> String {
+        match self {
+            Condition::Compare {
+                column,
+                op,
+                typed: true,
+                ..
+            } => format!(
+                "{} {} {}",
+                column,
+                op,
+                orm.placeholder(table_name, unqualified(column), index)
+            ),
+            Condition::Compare { column, op, .. } => format!("{} {} ${}", column, op, index),
+            Condition::RangeContains { column, .. } => format!(
+                "{} @> {}",
+                column,
+                element_placeholder(orm, table_name, unqualified(column), index)
+            ),
+            #[cfg(feature = "geo")]
+            Condition::WithinDistance { column, meters, .. } => {
+                crate::geo::within_distance_sql(column, index, *meters)
+            }
+            Condition::Any {
+                column,
+                negated: false,
+                ..
+            } => format!("{} = ANY(${})", column, index),
+            Condition::Any { column, .. } => format!("{} <> ALL(${})", column, index),
+            Condition::Similar { column, .. } => format!(
+                "({column} % ${index} AND similarity({column}, ${index}) >= ${})",
+                index + 1
+            ),
+            Condition::Raw(sql) => format!("({})", sql),
+        }
+    }
+
+    /// Bind values, in placeholder order.
+    fn values(&self) -> Vec<&SqlValue> {
+        match self {
+            Condition::Compare { value, .. }
+            | Condition::RangeContains { value, .. }
+            | Condition::Any { values: value, .. } => vec![value],
+            #[cfg(feature = "geo")]
+            Condition::WithinDistance { point, .. } => vec![point],
+            Condition::Similar { text, min, .. } => vec![text, min],
+            Condition::Raw(_) => Vec::new(),
+        }
+    }
+
+    /// Prefixes an unqualified column with `table`, for use in joins.
+    fn qualified(&self, table: &str) -> Condition {
+        self.renamed(|column| {
+            if column.contains('.') {
+                column.to_string()
+            } else {
+                format!("{}.{}", table, column)
+            }
+        })
+    }
+
+    /// The condition with its column replaced by `rename(column)`.
+    fn renamed(&self, mut rename: impl FnMut(&str) -> String) -> Condition {
+        let mut condition = self.clone();
+        match &mut condition {
+            Condition::Compare { column, .. }
+            | Condition::RangeContains { column, .. }
+            | Condition::Any { column, .. }
+            | Condition::Similar { column, .. } => *column = rename(column),
+            #[cfg(feature = "geo")]
+            Condition::WithinDistance { column, .. } => *column = rename(column),
+            Condition::Raw(_) => {}
+        }
+        condition
+    }
+}
+
+/// `column` without the table it is qualified with, if any.
+fn unqualified(column: &str) -> &str {
+    column.rsplit_once('.').map_or(column, |(_, column)| column)
+}
+
+/// Renders conditions with placeholders numbered from `offset + 1`.
+fn conditions_sql<'c>(
+    conditions: impl IntoIterator<Item = &'c Condition>,
+    offset: usize,
+    orm: &OrmStruct,
+    table_name: &str,
+) -> Vec<String> {
+    let mut next = offset;
+    conditions
+        .into_iter()
+        .map(|c| {
+            let sql = c.to_sql(next + 1, orm, table_name);
+            next += c.values().len();
+            sql
+        })
+        .collect()
+}
+
+/// Bind values of `conditions`, in placeholder order.
+fn condition_values<'c>(
+    conditions: impl IntoIterator<Item = &'c Condition>,
+) -> impl Iterator<Item = &'c SqlValue> {
+    conditions.into_iter().flat_map(Condition::values)
+}
+
+/// Query builder for advanced queries with WHERE, LIMIT, ORDER BY.
+pub struct Query<'a, T> {
+    table_name: String,
+    key_column: String,
+    orm: &'a OrmStruct,
+    scopes: Vec<Condition>,
+    wheres: Vec<Condition>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    order_by: Vec<OrderBy>,
+    distinct: bool,
+    group_by: Vec<String>,
+    grouping: Grouping,
+    havings: Vec<Condition>,
+    aggregates: Vec<String>,
+    compounds: Vec<(SetOp, Query<'a, T>)>,
+    related: Vec<&'static str>,
+    allowed: AllowList,
+    unmasked: bool,
+    /// Replaces the table in `FROM`, e.g. with a history snapshot
+    /// subquery, aliased as the table.
+    source: Option<String>,
+    _marker: std::marker::PhantomData<T>,
+}
+
+// Manual impl: the model type itself need not be `Clone`.
+impl<T> Clone for Query<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            table_name: self.table_name.clone(),
+            key_column: self.key_column.clone(),
+            orm: self.orm,
+            scopes: self.scopes.clone(),
+            wheres: self.wheres.clone(),
+            limit: self.limit,
+            offset: self.offset,
+            order_by: self.order_by.clone(),
+            distinct: self.distinct,
+            group_by: self.group_by.clone(),
+            grouping: self.grouping,
+            havings: self.havings.clone(),
+            aggregates: self.aggregates.clone(),
+            compounds: self.compounds.clone(),
+            related: self.related.clone(),
+            allowed: self.allowed.clone(),
+            unmasked: self.unmasked,
+            source: self.source.clone(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Query<'a, T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    pub fn new(table_name: String, key_column: String, orm: &'a OrmStruct) -> Self {
+        Self {
+            table_name: orm.table_name(&table_name),
+            key_column,
+            orm,
+            scopes: Vec::new(),
+            wheres: Vec::new(),
+            limit: None,
+            offset: None,
+            order_by: Vec::new(),
+            distinct: false,
+            group_by: Vec::new(),
+            grouping: Grouping::Plain,
+            havings: Vec::new(),
+            aggregates: Vec::new(),
+            compounds: Vec::new(),
+            related: Vec::new(),
+            allowed: AllowList::default(),
+            unmasked: false,
+            source: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Add a `column op value` condition. The value is bound with its own
+    /// type, so `where_clause("age", ">", 18)` compares against an integer.
+    /// See [`Op`] for the operators.
+    pub fn where_clause(
+        mut self,
+        column: &str,
+        op: impl Into<Op>,
+        value: impl Into<SqlValue>,
+    ) -> Self {
+        self.wheres
+            .push(Condition::new(column, op.into(), value.into()));
+        self
+    }
+
+    /// Match the row whose key column equals `key`. Keys of registered
+    /// tables are bound as text and cast to the column's type, so `"42"`
+    /// and `42` both find an `INTEGER` key.
+    pub fn where_key(mut self, key: impl Into<SqlValue>) -> Self {
+        let registered = self
+            .orm
+            .find_schema(&self.table_name)
+            .is_some_and(|s| s.column(&self.key_column).is_some());
+        let key = key.into();
+        self.wheres.push(Condition::Compare {
+            column: self.key_column.clone(),
+            op: "=".to_string(),
+            value: if registered { key.into_text() } else { key },
+            typed: registered,
+        });
+        self
+    }
+
+    /// Match rows whose `column` equals any of `values`, bound as one array
+    /// (`column = ANY($n)`). No values match no rows.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let open = tickets.query().where_in("status", &["new", "open"]).get().await?;
+    /// ```
+    pub fn where_in<V>(mut self, column: &str, values: &[V]) -> Self
+    where
+        V: Clone + Into<SqlValue>,
+    {
+        if values.is_empty() {
+            self.wheres.push(Condition::Raw("FALSE".to_string()));
+            return self;
+        }
+        self.wheres.push(Condition::Any {
+            column: column.to_string(),
+            values: SqlValue::List(values.iter().cloned().map(Into::into).collect()),
+            negated: false,
+        });
+        self
+    }
+
+    /// Match rows whose `column` equals none of `values`, bound as one
+    /// array (`column <> ALL($n)`). No values filter nothing out; as with
+    /// SQL's `NOT IN`, rows whose column is NULL never match.
+    pub fn not_in<V>(mut self, column: &str, values: &[V]) -> Self
+    where
+        V: Clone + Into<SqlValue>,
+    {
+        if !values.is_empty() {
+            self.wheres.push(Condition::Any {
+                column: column.to_string(),
+                values: SqlValue::List(values.iter().cloned().map(Into::into).collect()),
+                negated: true,
+            });
+        }
+        self
+    }
+
+    /// `column <> value`.
+    pub fn where_not(self, column: &str, value: impl Into<SqlValue>) -> Self {
+        self.where_clause(column, "<>", value)
+    }
+
+    /// `column IS DISTINCT FROM value`: unlike [`where_not`](Self::where_not)
+    /// NULL compares as a value, so NULL columns match any non-NULL `value`
+    /// and `SqlValue::Null` matches every non-NULL column.
+    pub fn is_distinct_from(mut self, column: &str, value: impl Into<SqlValue>) -> Self {
+        self.wheres.push(Condition::Compare {
+            column: column.to_string(),
+            op: "IS DISTINCT FROM".to_string(),
+            value: value.into(),
+            typed: true,
+        });
+        self
+    }
+
+    /// Match rows whose `INET` / `CIDR` column lies inside the network
+    /// `cidr`, e.g. `"10.0.0.0/8"` (`column <<= cidr`).
+    pub fn where_ip_contained_in(mut self, column: &str, cidr: &str) -> Self {
+        self.wheres.push(Condition::Compare {
+            column: column.to_string(),
+            op: "<<=".to_string(),
+            value: cidr.into(),
+            typed: true,
+        });
+        self
+    }
+
+    /// Match rows whose `HSTORE` or `JSONB` column has the key `key`
+    /// (`column ? key`).
+    ///
+    /// # Example
+    /// ```ignore
+    /// let sized = products.query().where_has_key("attributes", "size").get().await?;
+    /// ```
+    pub fn where_has_key(mut self, column: &str, key: &str) -> Self {
+        self.wheres.push(Condition::Compare {
+            column: column.to_string(),
+            op: "?".to_string(),
+            value: key.into(),
+            typed: false,
+        });
+        self
+    }
+
+    /// Fuzzy text match with `pg_trgm`: rows whose `column` is at least
+    /// `min_similarity` (0 to 1) similar to `text`, most similar first.
+    /// The `%` operator lets a [`trigram`](crate::ColumnSchema::trigram)
+    /// index narrow the rows, but also drops those below
+    /// `pg_trgm.similarity_threshold` (0.3 unless changed), so lower
+    /// minimums need that setting lowered as well. A `min_similarity` that
+    /// isn't a finite number fails with `InvalidArgument`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let matches = users.query().fuzzy("name", "gracee", 0.4)?.limit(10).get().await?;
+    /// ```
+    pub fn fuzzy(mut self, column: &str, text: &str, min_similarity: f64) -> sqlx::Result<Self> {
+        if !min_similarity.is_finite() {
+            return Err(sqlx::Error::InvalidArgument(format!(
+                "fuzzy: minimum similarity must be a finite number, got {}",
+                min_similarity
+            )));
+        }
+        self.wheres.push(Condition::Similar {
+            column: column.to_string(),
+            text: text.into(),
+            min: min_similarity.into(),
+        });
+        self.order_by.push(OrderBy {
+            column: column.to_string(),
+            direction: Direction::Desc,
+            nulls: None,
+            similar_to: Some(text.into()),
+        });
+        Ok(self)
+    }
+
+    /// Match rows whose range column contains `value` (`column @> value`).
+    ///
+    /// # Example
+    /// ```ignore
+    /// let current = prices.query().where_range_contains("valid_period", Utc::now()).first().await?;
+    /// ```
+    pub fn where_range_contains(mut self, column: &str, value: impl Into<SqlValue>) -> Self {
+        self.wheres.push(Condition::RangeContains {
+            column: column.to_string(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Match rows whose range column overlaps `range` (`column && range`),
+    /// e.g. bookings clashing with a requested stay.
+    pub fn where_range_overlaps(mut self, column: &str, range: impl Into<SqlValue>) -> Self {
+        self.wheres.push(Condition::Compare {
+            column: column.to_string(),
+            op: "&&".to_string(),
+            value: range.into(),
+            typed: true,
+        });
+        self
+    }
+
+    /// Match rows whose geometry column lies within `meters` of `point`
+    /// (longitude `x`, latitude `y`), measured on the spheroid.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let nearby = shops.query().where_within_distance("location", Point::new(13.40, 52.52), 500.0).get().await?;
+    /// ```
+    #[cfg(feature = "geo")]
+    pub fn where_within_distance(
+        mut self,
+        column: &str,
+        point: geo_types::Point<f64>,
+        meters: f64,
+    ) -> Self {
+        self.wheres.push(Condition::WithinDistance {
+            column: column.to_string(),
+            point: point.into(),
+            meters,
+        });
+        self
+    }
+
+    /// Equality conditions from a JSON filter object, bound like inserted
+    /// values and cast to each column's declared type. Every key must be a
+    /// column of the table.
+    fn where_json(mut self, filter: &serde_json::Map<String, Value>) -> sqlx::Result<Self> {
+        self.orm
+            .schema_for(&self.table_name)
+            .check_columns(filter.keys())?;
+        for (column, value) in filter {
+            self.wheres.push(Condition::Compare {
+                column: column.clone(),
+                op: "=".to_string(),
+                value: SqlValue::from_json(value.clone()),
+                typed: true,
+            });
+        }
+        Ok(self)
+    }
+
+    /// Add the conditions of a filter document, as sent by an HTTP client.
+    /// Each key is a column of the table's schema; its value is either a
+    /// plain value (equality) or an object of `operator: value` pairs:
+    ///
+    /// | operator | SQL |
+    /// | --- | --- |
+    /// | `eq`, `ne` | `=`, `<>` |
+    /// | `gt`, `gte`, `lt`, `lte` | `>`, `>=`, `<`, `<=` |
+    /// | `like`, `ilike` | `LIKE` / `ILIKE` with `%value%` |
+    /// | `null` | `IS NULL` for `true`, `IS NOT NULL` for `false` |
+    ///
+    /// Unknown columns and operators are rejected, as are hashed and
+    /// encrypted columns and those outside `Table::allow_filtering`, so user
+    /// input never reaches the SQL text.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let users = user_table
+    ///     .query()
+    ///     .apply_filter_json(json!({"name": {"ilike": "ada"}, "age": {"gte": 18}}))?
+    ///     .get()
+    ///     .await?;
+    /// ```
+    pub fn apply_filter_json(mut self, filter: Value) -> sqlx::Result<Self> {
+        let Value::Object(filter) = filter else {
+            return Err(sqlx::Error::InvalidArgument(
+                "filter must be a JSON object".into(),
+            ));
+        };
+        for (column, spec) in filter {
+            let col = self.checked_column(&column, Access::Filter)?;
+            let ops = match spec {
+                Value::Object(ops) => ops,
+                value => std::iter::once(("eq".to_string(), value)).collect(),
+            };
+            for (op, value) in ops {
+                let condition = match op.as_str() {
+                    "null" => match value {
+                        Value::Bool(true) => Condition::Raw(format!("{} IS NULL", col.name)),
+                        Value::Bool(false) => Condition::Raw(format!("{} IS NOT NULL", col.name)),
+                        _ => {
+                            return Err(sqlx::Error::InvalidArgument(format!(
+                                "`null` filter on `{}` must be true or false",
+                                column
+                            )));
+                        }
+                    },
+                    "like" | "ilike" => {
+                        let Value::String(pattern) = value else {
+                            return Err(sqlx::Error::InvalidArgument(format!(
+                                "`{}` filter on `{}` must be a string",
+                                op, column
+                            )));
+                        };
+                        Condition::Compare {
+                            column: col.name.to_string(),
+                            op: op.to_uppercase(),
+                            value: Wildcard::Both.apply(&pattern).into(),
+                            typed: false,
+                        }
+                    }
+                    _ => {
+                        let sql_op = match op.as_str() {
+                            "eq" => "=",
+                            "ne" => "<>",
+                            "gt" => ">",
+                            "gte" => ">=",
+                            "lt" => "<",
+                            "lte" => "<=",
+                            _ => {
+                                return Err(sqlx::Error::InvalidArgument(format!(
+                                    "unknown filter operator `{}`",
+                                    op
+                                )));
+                            }
+                        };
+                        Condition::Compare {
+                            column: col.name.to_string(),
+                            op: sql_op.to_string(),
+                            value: SqlValue::from_json(value),
+                            typed: true,
+                        }
+                    }
+                };
+                self.wheres.push(condition);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Apply a URL query string such as
+    /// `?sort=-created_at,name&limit=20&offset=40&name__ilike=ada&age__gte=18`:
+    ///
+    /// - `sort`: comma-separated columns, `-` for descending;
+    /// - `limit` / `offset`: non-negative integers;
+    /// - `column=value` or `column__op=value`: filters, with the operators of
+    ///   [`apply_filter_json`](Self::apply_filter_json).
+    ///
+    /// Every column is checked against the table's schema and its
+    /// `allow_filtering` / `allow_sorting` lists first.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let users = user_table.query().apply_query_string(request.uri().query().unwrap_or(""))?.get().await?;
+    /// ```
+    pub fn apply_query_string(mut self, query_string: &str) -> sqlx::Result<Self> {
+        let mut filter = serde_json::Map::new();
+        let pairs = query_string
+            .trim_start_matches('?')
+            .split('&')
+            .filter(|p| !p.is_empty());
+        for pair in pairs {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let (key, value) = (percent_decode(key), percent_decode(value));
+            match key.as_str() {
+                "sort" => {
+                    for key in value.split(',').filter(|k| !k.is_empty()) {
+                        let (column, direction) = match key.strip_prefix('-') {
+                            Some(column) => (column, Direction::Desc),
+                            None => (key, Direction::Asc),
+                        };
+                        let column = self.checked_column(column, Access::Sort)?.name;
+                        self = self.order_by(column, direction);
+                    }
+                }
+                "limit" | "offset" => {
+                    let n: u32 = value.parse().map_err(|_| {
+                        sqlx::Error::InvalidArgument(format!(
+                            "`{}` must be a non-negative integer",
+                            key
+                        ))
+                    })?;
+                    self = if key == "limit" {
+                        self.limit(n)
+                    } else {
+                        self.offset(n)
+                    };
+                }
+                _ => {
+                    let (column, op) = key.split_once("__").unwrap_or((&key, "eq"));
+                    let value = match (op, value.as_str()) {
+                        ("null", "true") => Value::Bool(true),
+                        ("null", "false") => Value::Bool(false),
+                        _ => Value::String(value),
+                    };
+                    let ops = filter
+                        .entry(column.to_string())
+                        .or_insert_with(|| Value::Object(serde_json::Map::new()));
+                    if let Value::Object(ops) = ops {
+                        ops.insert(op.to_string(), value);
+                    }
+                }
+            }
+        }
+        self.apply_filter_json(Value::Object(filter))
+    }
+
+    /// The schema column `column`, unless it is unknown, cannot be exposed
+    /// (hashed or encrypted) or is missing from the table's allow-list.
+    fn checked_column(&self, column: &str, access: Access) -> sqlx::Result<&'static ColumnSchema> {
+        let allowed = match access {
+            Access::Filter => &self.allowed.filtering,
+            Access::Sort => &self.allowed.sorting,
+        };
+        self.orm
+            .schema_for(&self.table_name)
+            .columns
+            .iter()
+            .filter(|c| c.hashed.is_none() && !c.encrypted)
+            .filter(|c| {
+                allowed
+                    .as_ref()
+                    .is_none_or(|a| a.iter().any(|n| n == c.name))
+            })
+            .find(|c| c.name == column)
+            .ok_or_else(|| {
+                let action = match access {
+                    Access::Filter => "filter on",
+                    Access::Sort => "sort by",
+                };
+                sqlx::Error::InvalidArgument(format!("cannot {} column `{}`", action, column))
+            })
+    }
+
+    /// [`where_clause`](Self::where_clause) for a column and operator that
+    /// come from outside the program: the column must pass the table's
+    /// filter allow-list and the operator must parse as an [`Op`] other
+    /// than [`Op::Other`].
+    pub fn try_where_clause(
+        self,
+        column: &str,
+        op: &str,
+        value: impl Into<SqlValue>,
+    ) -> sqlx::Result<Self> {
+        let column = self.checked_column(column, Access::Filter)?.name;
+        let op: Op = op.parse()?;
+        Ok(self.where_clause(column, op, value))
+    }
+
+    /// [`order_by`](Self::order_by) for a column that comes from outside
+    /// the program, checked against the table's sort allow-list.
+    pub fn try_order_by(self, column: &str, direction: Direction) -> sqlx::Result<Self> {
+        let column = self.checked_column(column, Access::Sort)?.name;
+        Ok(self.order_by(column, direction))
+    }
+
+    /// `column LIKE %pattern%`, matching the old `QueryBuilder::like`.
+    pub fn like(self, column: &str, pattern: &str) -> Self {
+        self.like_with(column, pattern, Wildcard::Both)
+    }
+
+    /// `column LIKE pattern` with explicit wildcard placement.
+    pub fn like_with(self, column: &str, pattern: &str, wildcard: Wildcard) -> Self {
+        self.where_clause(column, "LIKE", wildcard.apply(pattern))
+    }
+
+    /// Case-insensitive `column ILIKE %pattern%`.
+    pub fn ilike(self, column: &str, pattern: &str) -> Self {
+        self.ilike_with(column, pattern, Wildcard::Both)
+    }
+
+    /// `column NOT LIKE %pattern%`, the negation of [`like`](Self::like).
+    pub fn not_like(self, column: &str, pattern: &str) -> Self {
+        self.where_clause(column, "NOT LIKE", Wildcard::Both.apply(pattern))
+    }
+
+    /// Case-insensitive `column ILIKE pattern` with explicit wildcard placement.
+    pub fn ilike_with(self, column: &str, pattern: &str, wildcard: Wildcard) -> Self {
+        self.where_clause(column, "ILIKE", wildcard.apply(pattern))
+    }
+
+    pub fn limit(mut self, n: u32) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Sort by `column`. Repeated calls add further sort keys in call order.
+    pub fn order_by(mut self, column: &str, direction: Direction) -> Self {
+        self.order_by.push(OrderBy {
+            column: column.to_string(),
+            direction,
+            nulls: None,
+            similar_to: None,
+        });
+        self
+    }
+
+    /// Like `order_by`, with explicit `NULLS FIRST` / `NULLS LAST` placement.
+    pub fn order_by_nulls(mut self, column: &str, direction: Direction, nulls: Nulls) -> Self {
+        self.order_by.push(OrderBy {
+            column: column.to_string(),
+            direction,
+            nulls: Some(nulls),
+            similar_to: None,
+        });
         self
     }
 
@@ -266,121 +1914,899 @@ where
 
     pub fn group_by(mut self, columns: &[&str]) -> Self {
         self.group_by = columns.iter().map(|c| c.to_string()).collect();
+        self.grouping = Grouping::Plain;
+        self
+    }
+
+    /// `GROUP BY ROLLUP (columns)`: the groups of `columns`, plus a
+    /// subtotal row for each prefix of them and a grand total. Columns a
+    /// row is totalled over read as NULL, so declare them as `Option`s in
+    /// the result struct; select `grouping(column)` to tell a subtotal from
+    /// a group whose value is NULL.
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Sales { region: Option<String>, product: Option<String>, total: f64 }
+    ///
+    /// let report = order_table
+    ///     .query()
+    ///     .group_by_rollup(&["region", "product"])
+    ///     .select_aggregates(&[("sum(amount)::FLOAT8", "total")])
+    ///     .get_as::<Sales>()
+    ///     .await?;
+    /// ```
+    pub fn group_by_rollup(mut self, columns: &[&str]) -> Self {
+        self.group_by = columns.iter().map(|c| c.to_string()).collect();
+        self.grouping = Grouping::Rollup;
+        self
+    }
+
+    /// `GROUP BY CUBE (columns)`: like
+    /// [`group_by_rollup`](Self::group_by_rollup), with a subtotal row for
+    /// every combination of `columns` rather than each prefix.
+    pub fn group_by_cube(mut self, columns: &[&str]) -> Self {
+        self.group_by = columns.iter().map(|c| c.to_string()).collect();
+        self.grouping = Grouping::Cube;
         self
     }
 
-    pub fn having(mut self, column: &str, op: &str, value: &str) -> Self {
+    pub fn having(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
         self.havings
-            .push((column.to_string(), op.to_string(), value.to_string()));
+            .push(Condition::new(column, op.into(), value.into()));
         self
     }
 
-    pub async fn get(self) -> sqlx::Result<Vec<Record<T>>> {
+    /// Select aggregate expressions, each with the alias it is returned
+    /// under. The `group_by` columns are selected first, so the result can be
+    /// read with `get_as` into a struct holding both.
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct PerDepartment { department: String, cnt: i64 }
+    ///
+    /// let rows = user_table
+    ///     .query()
+    ///     .group_by(&["department"])
+    ///     .select_aggregates(&[("count(*)", "cnt")])
+    ///     .get_as::<PerDepartment>()
+    ///     .await?;
+    /// ```
+    pub fn select_aggregates(mut self, aggregates: &[(&str, &str)]) -> Self {
+        self.aggregates.extend(
+            aggregates
+                .iter()
+                .map(|(expr, alias)| format!("{} AS {}", expr, alias)),
+        );
+        self
+    }
+
+    /// Preload the `C` children of every fetched record (see
+    /// [`Record::preload`]), read back with `record.related::<C>(table)`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let users = user_table.query().with_related::<Post>().get().await?;
+    /// let posts: Vec<Post> = users[0].related("posts")?;
+    /// ```
+    pub fn with_related<C>(mut self) -> Self
+    where
+        C: SlintModel,
+    {
+        self.related.push(self.orm.schema_of::<C>().name);
+        self
+    }
+
+    /// Join each row with the `U` rows where `left_column` of this table
+    /// equals `right_column` of `U`'s table; read the results with
+    /// [`JoinQuery::get_pairs`]. The query's conditions, ordering, limit
+    /// and `distinct` are kept. Their columns are this table's unless
+    /// qualified with `U`'s table (`posts.title`); the tables are aliased
+    /// `l` and `r`, so a model joined with itself refers to its right side
+    /// as `r.column`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let pairs: Vec<(User, Post)> = user_table
+    ///     .query()
+    ///     .where_clause("users.active", "=", true)
+    ///     .inner_join::<Post>("id", "user_id")
+    ///     .get_pairs()
+    ///     .await?;
+    /// ```
+    pub fn inner_join<U>(self, left_column: &str, right_column: &str) -> JoinQuery<'a, T, U>
+    where
+        U: SlintModel + DeserializeOwned,
+    {
+        let right = self.orm.schema_of::<U>();
+        JoinQuery {
+            query: self,
+            right,
+            left_column: left_column.to_string(),
+            right_column: right_column.to_string(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Add everything `other` specifies to this query: its conditions are
+    /// `AND`ed on, its sort keys follow this query's, and its limit and
+    /// offset win when set. Masked columns stay as this query has them, so
+    /// merging in a query from an unmasked table doesn't reveal them.
+    /// Queries on different tables can't be merged and fail with
+    /// `InvalidArgument`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let base = user_table.query().where_clause("active", "=", true);
+    /// let total = base.clone().count().await?;
+    /// let page = base
+    ///     .merge(user_table.query().order_by("name", Direction::Asc).limit(20).offset(40))?
+    ///     .get()
+    ///     .await?;
+    /// ```
+    pub fn merge(mut self, other: Query<'a, T>) -> sqlx::Result<Self> {
+        if self.table_name != other.table_name {
+            return Err(sqlx::Error::InvalidArgument(format!(
+                "cannot merge a query on `{}` into one on `{}`",
+                other.table_name, self.table_name
+            )));
+        }
+        for scope in other.scopes {
+            if !self.scopes.contains(&scope) {
+                self.scopes.push(scope);
+            }
+        }
+        self.wheres.extend(other.wheres);
+        self.limit = other.limit.or(self.limit);
+        self.offset = other.offset.or(self.offset);
+        self.order_by.extend(other.order_by);
+        self.distinct |= other.distinct;
+        for column in other.group_by {
+            if !self.group_by.contains(&column) {
+                self.group_by.push(column);
+            }
+        }
+        if self.grouping == Grouping::Plain {
+            self.grouping = other.grouping;
+        }
+        self.havings.extend(other.havings);
+        self.aggregates.extend(other.aggregates);
+        self.compounds.extend(other.compounds);
+        self.related.extend(other.related);
+        Ok(self)
+    }
+
+    /// Combine with another query on the same model: `(self) UNION (other)`.
+    /// Bind parameters of both halves are renumbered automatically.
+    pub fn union(self, other: Query<'a, T>) -> Self {
+        self.combine(SetOp::Union, other)
+    }
+
+    /// `(self) UNION ALL (other)`, keeping duplicate rows.
+    pub fn union_all(self, other: Query<'a, T>) -> Self {
+        self.combine(SetOp::UnionAll, other)
+    }
+
+    /// `(self) INTERSECT (other)`.
+    pub fn intersect(self, other: Query<'a, T>) -> Self {
+        self.combine(SetOp::Intersect, other)
+    }
+
+    /// `(self) EXCEPT (other)`.
+    pub fn except(self, other: Query<'a, T>) -> Self {
+        self.combine(SetOp::Except, other)
+    }
+
+    /// Combine with another query using any set operation.
+    pub fn combine(mut self, op: SetOp, other: Query<'a, T>) -> Self {
+        self.compounds.push((op, other));
+        self
+    }
+
+    /// Apply `changes` to every row matched by this query's `where_clause`
+    /// conditions (and the table's scopes) and return the `returning`
+    /// columns of each changed row (all but hashed columns when empty).
+    /// Ordering, limits and grouping are ignored. A query without
+    /// conditions is an `InvalidArgument` error rather than an update of
+    /// the whole table.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let changed = user_table
+    ///     .query()
+    ///     .where_clause("last_login", "<", cutoff)
+    ///     .update_returning(json!({"active": false}), &["id"])
+    ///     .await?;
+    /// ```
+    pub async fn update_returning(
+        self,
+        changes: Value,
+        returning: &[&str],
+    ) -> sqlx::Result<Vec<Value>> {
+        let mut changes = changes
+            .as_object()
+            .filter(|m| !m.is_empty())
+            .cloned()
+            .ok_or_else(|| {
+                sqlx::Error::InvalidArgument("changes must be a non-empty JSON object".into())
+            })?;
+        // Scopes alone would update every row they let through.
+        if self.wheres.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "update_returning needs at least one where condition".into(),
+            ));
+        }
+        if let Some(schema) = self.orm.find_schema(&self.table_name) {
+            schema.check_columns(changes.keys())?;
+        }
+        let returning_sql = self.orm.returning_clause(&self.table_name, returning)?;
+        self.orm.stamp_update(&self.table_name, &mut changes);
+
+        let sets: Vec<String> = changes
+            .keys()
+            .enumerate()
+            .map(|(i, column)| {
+                let placeholder = self.orm.placeholder(&self.table_name, column, i + 1);
+                format!("{} = {}", column, placeholder)
+            })
+            .collect();
+        let mut sql = format!("UPDATE {} SET {}", self.table_name, sets.join(", "));
+        if let Some(conds) = self.where_sql(changes.len()) {
+            sql.push_str(&format!(" WHERE {}", conds));
+        }
+        // Subscribers get whole rows; the caller's columns are picked out after.
+        let event_returning = self.orm.event_returning(&self.table_name);
+        sql.push_str(event_returning.as_deref().unwrap_or(&returning_sql));
+
+        let mut query = self.orm.statement(&sql);
+        for (column, v) in &changes {
+            let v = self
+                .orm
+                .encode_column(&self.table_name, column, v.clone())?;
+            query = bind_json(query, v);
+        }
+        for val in condition_values(self.scopes.iter().chain(&self.wheres)) {
+            query = val.clone().bind(query);
+        }
+
+        let rows = self
+            .orm
+            .timed(
+                &self.table_name,
+                Operation::Update,
+                query.fetch_all(self.orm.pool()),
+            )
+            .await?;
+        let results = rows
+            .iter()
+            .map(|r| self.orm.row_json_with(&self.table_name, r, self.unmasked))
+            .collect::<sqlx::Result<Vec<_>>>()?;
+        if event_returning.is_none() {
+            return Ok(results);
+        }
+        let events = rows
+            .iter()
+            .map(|r| self.orm.row_json(&self.table_name, r))
+            .collect::<sqlx::Result<Vec<_>>>()?;
+        self.orm
+            .emit_rows(Change::Update, &self.table_name, &events);
+        Ok(project_rows(results, returning))
+    }
+
+    /// The `AND`-joined scopes and `where_clause` conditions, numbered from
+    /// `offset + 1`.
+    fn where_sql(&self, offset: usize) -> Option<String> {
+        let conds = conditions_sql(
+            self.scopes.iter().chain(&self.wheres),
+            offset,
+            self.orm,
+            &self.table_name,
+        );
+        if conds.is_empty() {
+            return None;
+        }
+        Some(conds.join(" AND "))
+    }
+
+    /// Number of placeholders used by `where_sql`.
+    fn where_bind_count(&self) -> usize {
+        condition_values(self.scopes.iter().chain(&self.wheres)).count()
+    }
+
+    fn build_sql(&self) -> String {
+        self.build_sql_at(0)
+    }
+
+    /// The SELECT this query runs and its bind values in placeholder order,
+    /// without executing it. Useful for logging, snapshot tests or pasting
+    /// into `psql`.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     name: String,
+    /// #     email: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![User::slint_schema()]);
+    /// # let user_table = Table::<User>::from_schema(&orm);
+    /// let (sql, params) = user_table.query().where_clause("name", "=", "Ada").to_sql();
+    /// assert_eq!(sql, "SELECT * FROM users WHERE name = $1");
+    /// assert_eq!(params, vec![SqlValue::from("Ada")]);
+    /// ```
+    pub fn to_sql(&self) -> (String, Vec<SqlValue>) {
+        (self.build_sql(), self.bind_values())
+    }
+
+    /// The ` ORDER BY ... LIMIT ... OFFSET ...` tail, with placeholders
+    /// numbered from `offset + 1`.
+    fn page_sql(&self, offset: usize) -> String {
+        let mut sql = String::new();
+        if !self.order_by.is_empty() {
+            let mut next = offset;
+            let keys: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|key| {
+                    if key.similar_to.is_some() {
+                        next += 1;
+                    }
+                    key.to_sql(next)
+                })
+                .collect();
+            sql.push_str(&format!(" ORDER BY {}", keys.join(", ")));
+        }
+
+        if let Some(lim) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", lim));
+        }
+
+        if let Some(off) = self.offset {
+            sql.push_str(&format!(" OFFSET {}", off));
+        }
+        sql
+    }
+
+    /// Renders the query with placeholders numbered from `offset + 1`.
+    fn build_sql_at(&self, offset: usize) -> String {
+        let select_list = if self.aggregates.is_empty() {
+            self.orm.select_list(&self.table_name)
+        } else {
+            self.group_by
+                .iter()
+                .chain(self.aggregates.iter())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
         let select_clause = if self.distinct {
-            "SELECT DISTINCT *"
+            format!("SELECT DISTINCT {}", select_list)
         } else {
-            "SELECT *"
+            format!("SELECT {}", select_list)
         };
-        let mut sql = format!("{} FROM {}", select_clause, self.table_name);
+        let mut sql = format!("{} FROM {}", select_clause, self.source_sql());
 
-        if !self.wheres.is_empty() {
-            // Generate numbered placeholders $1, $2, $3...
-            let conds: Vec<String> = self
-                .wheres
-                .iter()
-                .enumerate()
-                .map(|(i, (c, op, _))| format!("{} {} ${}", c, op, i + 1))
-                .collect();
-            sql.push_str(&format!(" WHERE {}", conds.join(" AND ")));
+        if let Some(conds) = self.where_sql(offset) {
+            sql.push_str(&format!(" WHERE {}", conds));
         }
 
         if !self.group_by.is_empty() {
-            sql.push_str(&format!(" GROUP BY {}", self.group_by.join(", ")));
+            sql.push_str(&format!(
+                " GROUP BY {}",
+                self.grouping.to_sql(&self.group_by)
+            ));
         }
 
         if !self.havings.is_empty() {
-            let conds: Vec<String> = self
-                .havings
-                .iter()
-                .enumerate()
-                .map(|(i, (c, op, _))| format!("{} {} ${}", c, op, self.wheres.len() + i + 1))
-                .collect();
+            let conds = conditions_sql(
+                &self.havings,
+                offset + self.where_bind_count(),
+                self.orm,
+                &self.table_name,
+            );
             sql.push_str(&format!(" HAVING {}", conds.join(" AND ")));
         }
 
-        if let Some((col, dir)) = &self.order_by {
-            sql.push_str(&format!(" ORDER BY {} {}", col, dir));
+        let next = offset + self.where_bind_count() + condition_values(&self.havings).count();
+        sql.push_str(&self.page_sql(next));
+
+        if self.compounds.is_empty() {
+            return sql;
         }
 
-        if let Some(lim) = self.limit {
-            sql.push_str(&format!(" LIMIT {}", lim));
+        let mut next = next + self.order_values().count();
+        let mut compound = format!("({})", sql);
+        for (op, other) in &self.compounds {
+            compound.push_str(&format!(" {} ({})", op.as_sql(), other.build_sql_at(next)));
+            next += other.bind_values().len();
         }
+        compound
+    }
 
-        if let Some(off) = self.offset {
-            sql.push_str(&format!(" OFFSET {}", off));
+    /// The `FROM` item: the table, or the snapshot set by `Table::as_of`
+    /// aliased as the table.
+    fn source_sql(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{} AS {}", source, unqualified(&self.table_name)),
+            None => self.table_name.clone(),
         }
+    }
 
-        let mut query = sqlx::query(&sql);
-        for (_, _, val) in &self.wheres {
-            query = query.bind(val);
+    /// Bind values of the `ORDER BY` keys, in placeholder order.
+    fn order_values(&self) -> impl Iterator<Item = &SqlValue> {
+        self.order_by
+            .iter()
+            .filter_map(|key| key.similar_to.as_ref())
+    }
+
+    /// Bind values in placeholder order, including those of combined queries.
+    fn bind_values(&self) -> Vec<SqlValue> {
+        let mut values: Vec<SqlValue> = condition_values(self.scopes.iter().chain(&self.wheres))
+            .chain(condition_values(&self.havings))
+            .chain(self.order_values())
+            .cloned()
+            .collect();
+        for (_, other) in &self.compounds {
+            values.extend(other.bind_values());
         }
-        for (_, _, val) in &self.havings {
-            query = query.bind(val);
+        values
+    }
+
+    async fn fetch_rows(&self) -> sqlx::Result<Vec<PgRow>> {
+        let sql = self.build_sql();
+        let mut query = self.orm.statement(&sql);
+        for val in self.bind_values() {
+            query = val.bind(query);
         }
+        self.orm
+            .timed(
+                &self.table_name,
+                Operation::Select,
+                query.fetch_all(self.orm.pool()),
+            )
+            .await
+    }
 
-        let rows = query.fetch_all(self.orm.pool()).await?;
+    pub async fn get(self) -> sqlx::Result<Vec<Record<T>>> {
+        let rows = self.fetch_rows().await?;
         let mut results = Vec::with_capacity(rows.len());
 
         for r in rows {
-            let mut map = serde_json::Map::new();
-            for col in r.columns() {
-                let col_name = col.name();
-                let value = match r.try_get::<Option<i64>, _>(col_name) {
-                    Ok(Some(v)) => Value::from(v),
-                    Ok(None) => Value::Null,
-                    Err(_) => match r.try_get::<Option<f64>, _>(col_name) {
-                        Ok(Some(v)) => Value::from(v),
-                        Ok(None) => Value::Null,
-                        Err(_) => match r.try_get::<Option<bool>, _>(col_name) {
-                            Ok(Some(v)) => Value::from(v),
-                            Ok(None) => Value::Null,
-                            Err(_) => match r.try_get::<Option<String>, _>(col_name) {
-                                Ok(Some(v)) => Value::from(v),
-                                Ok(None) => Value::Null,
-                                Err(_) => Value::Null, // fallback
-                            },
-                        },
-                    },
-                };
-                map.insert(col_name.to_string(), value);
-            }
-            let obj = serde_json::from_value::<T>(Value::Object(map)).map_err(|e| {
-                sqlx::Error::ColumnDecode {
-                    index: "serde_json".into(),
-                    source: Box::new(e),
-                }
-            })?;
-            results.push(Record::new(
-                self.table_name.clone(),
-                obj,
-                self.key_column.clone(),
-                self.orm.clone(),
-            ));
+            let obj = self
+                .orm
+                .decode_with::<T>(&self.table_name, &r, self.unmasked)?;
+            results.push(
+                Record::new(
+                    self.table_name.clone(),
+                    obj,
+                    self.key_column.clone(),
+                    self.orm.clone(),
+                )
+                .with_scopes(self.scopes.clone()),
+            );
+        }
+        for relation in &self.related {
+            Record::preload(&mut results, relation).await?;
         }
         Ok(results)
     }
 
+    /// Run the query and deserialize each row into `R` instead of the
+    /// table's model, for grouped or aggregate results.
+    pub async fn get_as<R>(self) -> sqlx::Result<Vec<R>>
+    where
+        R: DeserializeOwned,
+    {
+        let rows = self.fetch_rows().await?;
+        rows.iter()
+            .map(|r| {
+                self.orm
+                    .decode_with::<R>(&self.table_name, r, self.unmasked)
+            })
+            .collect()
+    }
+
+    /// Stream the query's rows into a Parquet file at `path`, replacing
+    /// it, with the Arrow schema [`arrow_schema`](crate::arrow_schema)
+    /// derives from the table. Rows are converted and written
+    /// [`PARQUET_BATCH`](crate::PARQUET_BATCH) at a time, Snappy-compressed.
+    /// Masked columns are redacted unless the table is
+    /// [`with_unmasked`](Table::with_unmasked). Returns the number of rows
+    /// written.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orders.query().where_clause("status", "=", "paid").export_parquet("paid_orders.parquet").await?;
+    /// ```
+    #[cfg(feature = "parquet")]
+    pub async fn export_parquet(self, path: impl AsRef<std::path::Path>) -> sqlx::Result<u64> {
+        if !self.aggregates.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "only queries returning table rows can be exported to Parquet".into(),
+            ));
+        }
+        let schema = Arc::new(parquet_io::arrow_schema(
+            self.orm.schema_for(&self.table_name),
+        ));
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let file = tokio::fs::File::create(path).await?;
+        let mut writer = AsyncArrowWriter::try_new(file, schema.clone(), Some(properties))
+            .map_err(parquet_io::parquet_error)?;
+
+        let sql = self.build_sql();
+        let mut query = self.orm.statement(&sql);
+        for val in self.bind_values() {
+            query = val.bind(query);
+        }
+        let started = std::time::Instant::now();
+        let mut rows = query.fetch(self.orm.pool());
+        let mut batch = Vec::with_capacity(PARQUET_BATCH);
+        let mut written = 0;
+        while let Some(row) = rows.try_next().await? {
+            batch.push(
+                self.orm
+                    .row_json_with(&self.table_name, &row, self.unmasked)?,
+            );
+            if batch.len() == PARQUET_BATCH {
+                written += parquet_io::write_batch(&mut writer, &schema, &mut batch).await?;
+            }
+        }
+        written += parquet_io::write_batch(&mut writer, &schema, &mut batch).await?;
+        writer.close().await.map_err(parquet_io::parquet_error)?;
+        self.orm.record_stat(
+            &self.table_name,
+            Operation::Select,
+            started.elapsed(),
+            written,
+        );
+        Ok(written)
+    }
+
     pub async fn first(self) -> sqlx::Result<Option<Record<T>>> {
         let query = self.limit(1).get().await?;
         Ok(query.into_iter().next())
     }
 
+    /// Runs the query's aggregates once per step of the series from `from`
+    /// to `to`, `interval` apart (see
+    /// [`OrmStruct::series_dates`](crate::OrmStruct::series_dates)). Each
+    /// row is counted in the bucket whose step its `column` falls in. The
+    /// table is left-joined to the series, so empty buckets are returned
+    /// too, and the query's conditions only decide which rows are counted.
+    /// Rows come back ordered by bucket, with the step start selected as
+    /// `bucket` before the `group_by` columns and aggregates.
+    ///
+    /// Count a column (`count(id)`) rather than `count(*)`, which gives
+    /// empty buckets a count of 1. Other aggregates are NULL in them.
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Daily { bucket: DateTime<Utc>, signups: i64 }
+    ///
+    /// let days = user_table
+    ///     .query()
+    ///     .where_clause("verified", Op::Eq, true)
+    ///     .select_aggregates(&[("count(id)", "signups")])
+    ///     .get_series::<Daily>("created_at", month_start, month_end, "1 day")
+    ///     .await?;
+    /// ```
+    pub async fn get_series<R>(
+        self,
+        column: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: &str,
+    ) -> sqlx::Result<Vec<R>>
+    where
+        R: DeserializeOwned,
+    {
+        let table = &self.table_name;
+        let wheres: Vec<Condition> = self
+            .scopes
+            .iter()
+            .chain(&self.wheres)
+            .map(|c| c.qualified(table))
+            .collect();
+        let mut on = vec![format!(
+            "{table}.{column} >= series.bucket AND {table}.{column} < series.bucket + $3::INTERVAL"
+        )];
+        on.extend(conditions_sql(&wheres, 3, self.orm, table));
+        let select_list: Vec<&str> = std::iter::once("series.bucket AS bucket")
+            .chain(self.group_by.iter().map(String::as_str))
+            .chain(self.aggregates.iter().map(String::as_str))
+            .collect();
+        let group_by: Vec<&str> = std::iter::once("series.bucket")
+            .chain(self.group_by.iter().map(String::as_str))
+            .collect();
+        let mut sql = format!(
+            "SELECT {} FROM generate_series($1::TIMESTAMPTZ, $2::TIMESTAMPTZ, $3::INTERVAL) \
+             AS series(bucket) LEFT JOIN {} ON {} GROUP BY {}",
+            select_list.join(", "),
+            self.source_sql(),
+            on.join(" AND "),
+            group_by.join(", ")
+        );
+        if !self.havings.is_empty() {
+            let offset = 3 + self.where_bind_count();
+            let conds = conditions_sql(&self.havings, offset, self.orm, table);
+            sql.push_str(&format!(" HAVING {}", conds.join(" AND ")));
+        }
+        sql.push_str(" ORDER BY series.bucket");
+
+        let mut query = self.orm.statement(&sql).bind(from).bind(to).bind(interval);
+        for value in condition_values(wheres.iter().chain(&self.havings)) {
+            query = value.clone().bind(query);
+        }
+        let rows = self
+            .orm
+            .timed(table, Operation::Select, query.fetch_all(self.orm.pool()))
+            .await?;
+        rows.iter()
+            .map(|r| self.orm.decode_with::<R>(table, r, self.unmasked))
+            .collect()
+    }
+
+    /// Number of rows the query matches, ignoring its ordering, limit and
+    /// offset (the total for a paginated listing).
+    pub async fn count(mut self) -> sqlx::Result<i64> {
+        self.order_by.clear();
+        self.limit = None;
+        self.offset = None;
+        let sql = format!("SELECT count(*) FROM ({}) AS counted", self.build_sql());
+        let mut query = self.orm.statement(&sql);
+        for val in self.bind_values() {
+            query = val.bind(query);
+        }
+        self.orm
+            .timed(
+                &self.table_name,
+                Operation::Select,
+                query.fetch_one(self.orm.pool()),
+            )
+            .await?
+            .try_get(0)
+    }
+
+    /// The first matching value, or `sqlx::Error::RowNotFound` when no row
+    /// matches. Match on that variant to tell a missing row from a failed
+    /// query, or use [`first_value_opt`](Self::first_value_opt).
     pub async fn first_value(self) -> Result<T, sqlx::Error> {
-        let query = self.limit(1).get().await?;
-        Ok(query
+        self.first_value_opt()
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// The first matching value, if any.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let Some(user) = user_table.query().where_clause("email", Op::Eq, email).first_value_opt().await? else {
+    ///     return Ok(None);
+    /// };
+    /// ```
+    pub async fn first_value_opt(self) -> sqlx::Result<Option<T>> {
+        Ok(self.first().await?.map(|record| record.value))
+    }
+}
+
+/// A [`Query`] joined with a second model, see [`Query::inner_join`].
+pub struct JoinQuery<'a, T, U> {
+    query: Query<'a, T>,
+    right: TableSchema,
+    left_column: String,
+    right_column: String,
+    _marker: std::marker::PhantomData<U>,
+}
+
+impl<T, U> JoinQuery<'_, T, U>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+    U: DeserializeOwned,
+{
+    // The tables are aliased `l` and `r`, so a table can be joined with
+    // itself, and every column is selected as `"l.<column>"` or
+    // `"r.<column>"`, so columns both tables share come back separately and
+    // each side decodes on its own.
+    fn build_sql(&self) -> sqlx::Result<String> {
+        let query = &self.query;
+        let unsupported = [
+            (!query.group_by.is_empty(), "group_by"),
+            (!query.havings.is_empty(), "having"),
+            (!query.aggregates.is_empty(), "aggregates"),
+            (!query.compounds.is_empty(), "union, intersect and except"),
+        ];
+        if let Some((_, part)) = unsupported.iter().find(|(used, _)| *used) {
+            return Err(sqlx::Error::InvalidArgument(format!(
+                "{} can't be used in a join",
+                part
+            )));
+        }
+        let left = query.orm.find_schema(&query.table_name).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("table `{}` is not registered", query.table_name))
+        })?;
+        let columns: Vec<String> = [("l", left), ("r", &self.right)]
             .into_iter()
-            .next()
-            .ok_or_else(|| sqlx::Error::RowNotFound)?
-            .value)
+            .flat_map(|(side, schema)| {
+                schema
+                    .columns
+                    .iter()
+                    .filter(|c| c.hashed.is_none())
+                    .map(move |c| {
+                        format!("{side}.{column} AS \"{side}.{column}\"", column = c.name)
+                    })
+            })
+            .collect();
+        let mut sql = format!(
+            "SELECT {}{} FROM {} AS l INNER JOIN {} AS r ON l.{} = r.{}",
+            if query.distinct { "DISTINCT " } else { "" },
+            columns.join(", "),
+            query.source.as_deref().unwrap_or(&query.table_name),
+            self.right.name,
+            unqualified(&self.left_column),
+            unqualified(&self.right_column)
+        );
+        let mut next = 0;
+        let conds: Vec<String> = self
+            .conditions()
+            .iter()
+            .map(|(c, table)| {
+                let sql = c.to_sql(next + 1, query.orm, table);
+                next += c.values().len();
+                sql
+            })
+            .collect();
+        if !conds.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conds.join(" AND ")));
+        }
+        let mut page = query.clone();
+        for key in &mut page.order_by {
+            key.column = self.aliased(&key.column).0;
+        }
+        sql.push_str(&page.page_sql(next));
+        Ok(sql)
+    }
+
+    /// The SELECT this join runs and its bind values, without executing
+    /// it. Grouping, `having`, aggregates and set operations can't be
+    /// joined and fail with `InvalidArgument`, as does a left table that
+    /// isn't registered.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "employees")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct Employee {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// #     manager_id: String,
+    /// # }
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), vec![Employee::slint_schema()]);
+    /// # let employees = Table::<Employee>::from_schema(&orm);
+    /// let (sql, params) = employees
+    ///     .query()
+    ///     .where_clause("r.id", "=", "42")
+    ///     .order_by("id", Direction::Asc)
+    ///     .inner_join::<Employee>("manager_id", "id")
+    ///     .to_sql()?;
+    /// assert_eq!(
+    ///     sql,
+    ///     "SELECT l.id AS \"l.id\", l.manager_id AS \"l.manager_id\", \
+    ///      r.id AS \"r.id\", r.manager_id AS \"r.manager_id\" \
+    ///      FROM employees AS l INNER JOIN employees AS r ON l.manager_id = r.id \
+    ///      WHERE r.id = $1 ORDER BY l.id ASC"
+    /// );
+    /// assert_eq!(params, vec![SqlValue::from("42")]);
+    /// # Ok::<(), sqlx::Error>(())
+    /// ```
+    pub fn to_sql(&self) -> sqlx::Result<(String, Vec<SqlValue>)> {
+        let sql = self.build_sql()?;
+        let values = self
+            .conditions()
+            .iter()
+            .flat_map(|(c, _)| c.values())
+            .chain(self.query.order_values())
+            .cloned()
+            .collect();
+        Ok((sql, values))
+    }
+
+    /// `column` under its side's alias, with the table it belongs to:
+    /// columns qualified with the right table's name (or `r.`) are the
+    /// right table's, others the left's. In a self-join the table's name
+    /// means the left side.
+    fn aliased(&self, column: &str) -> (String, &str) {
+        let left = (self.query.table_name.as_str(), "l");
+        let right = (self.right.name, "r");
+        let side = match column.rsplit_once('.') {
+            Some(("r", _)) => right,
+            Some((table, _))
+                if table != "l"
+                    && self.query.orm.table_name(table) == self.right.name
+                    && self.right.name != self.query.table_name =>
+            {
+                right
+            }
+            _ => left,
+        };
+        (format!("{}.{}", side.1, unqualified(column)), side.0)
     }
+
+    /// The table's scopes and the query's conditions, under the tables'
+    /// aliases, each with the table its column belongs to.
+    fn conditions(&self) -> Vec<(Condition, &str)> {
+        self.query
+            .scopes
+            .iter()
+            .map(|c| {
+                let condition = c.renamed(|column| format!("l.{}", unqualified(column)));
+                (condition, self.query.table_name.as_str())
+            })
+            .chain(self.query.wheres.iter().map(|c| {
+                let mut table = self.query.table_name.as_str();
+                let condition = c.renamed(|column| {
+                    let (column, side) = self.aliased(column);
+                    table = side;
+                    column
+                });
+                (condition, table)
+            }))
+            .collect()
+    }
+
+    /// Run the join and decode each row into both models.
+    pub async fn get_pairs(self) -> sqlx::Result<Vec<(T, U)>> {
+        let (sql, values) = self.to_sql()?;
+        let mut query = self.query.orm.statement(&sql);
+        for val in values {
+            query = val.bind(query);
+        }
+        let rows = self
+            .query
+            .orm
+            .timed(
+                &self.query.table_name,
+                Operation::Select,
+                query.fetch_all(self.query.orm.pool()),
+            )
+            .await?;
+
+        let orm = self.query.orm;
+        let unmasked = self.query.unmasked;
+        rows.iter()
+            .map(|row| {
+                let mut left = serde_json::Map::new();
+                let mut right = serde_json::Map::new();
+                if let Value::Object(map) = row_to_json(row) {
+                    for (alias, v) in map {
+                        match alias.split_once('.') {
+                            Some(("l", column)) => left.insert(column.to_string(), v),
+                            Some((_, column)) => right.insert(column.to_string(), v),
+                            None => None,
+                        };
+                    }
+                }
+                let mut left = Value::Object(left);
+                let mut right = Value::Object(right);
+                orm.reveal(&self.query.table_name, &mut left, unmasked)?;
+                orm.reveal(self.right.name, &mut right, unmasked)?;
+                Ok((from_json(left)?, from_json(right)?))
+            })
+            .collect()
+    }
+}
+
+fn from_json<R: DeserializeOwned>(value: Value) -> sqlx::Result<R> {
+    serde_json::from_value(value).map_err(|e| sqlx::Error::ColumnDecode {
+        index: "serde_json".into(),
+        source: Box::new(e),
+    })
 }
@@ -0,0 +1,213 @@
+use crate::SqlValue;
+use std::fmt;
+use std::str::FromStr;
+
+/// Operator of a `column op value` condition, accepted by
+/// `Query::where_clause`, `QueryBuilder::r#where` and the other methods
+/// that take one.
+///
+/// Strings convert too, so `where_clause("age", ">", 18)` and
+/// `where_clause("age", Op::Gt, 18)` are the same condition. Operators
+/// that aren't variants, such as `"@>"`, are kept as [`Op::Other`]; parse
+/// with [`str::parse`] instead to reject them.
+///
+/// # Example
+/// ```ignore
+/// let open = tickets.query()
+///     .where_clause("status", Op::In, vec!["new", "open"])
+///     .where_clause("closed_at", Op::IsNull, SqlValue::Null)
+///     .get()
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Like,
+    NotLike,
+    ILike,
+    NotILike,
+    /// `column = ANY(value)`; a value that isn't a list is a list of one.
+    /// An empty list matches no rows.
+    In,
+    /// `column <> ALL(value)`; an empty list matches every row.
+    NotIn,
+    /// `column IS NULL`; the value is not bound.
+    IsNull,
+    /// `column IS NOT NULL`; the value is not bound.
+    IsNotNull,
+    IsDistinctFrom,
+    IsNotDistinctFrom,
+    /// Any other operator, written into the SQL as given.
+    Other(String),
+}
+
+impl Op {
+    /// The operator's SQL, e.g. `<>` for [`Op::Ne`] and `= ANY` for
+    /// [`Op::In`].
+    pub fn as_sql(&self) -> &str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Gt => ">",
+            Op::Gte => ">=",
+            Op::Lt => "<",
+            Op::Lte => "<=",
+            Op::Like => "LIKE",
+            Op::NotLike => "NOT LIKE",
+            Op::ILike => "ILIKE",
+            Op::NotILike => "NOT ILIKE",
+            Op::In => "= ANY",
+            Op::NotIn => "<> ALL",
+            Op::IsNull => "IS NULL",
+            Op::IsNotNull => "IS NOT NULL",
+            Op::IsDistinctFrom => "IS DISTINCT FROM",
+            Op::IsNotDistinctFrom => "IS NOT DISTINCT FROM",
+            Op::Other(op) => op,
+        }
+    }
+
+    /// Whether the operator compares against a value, which all but the
+    /// null checks do.
+    pub fn takes_value(&self) -> bool {
+        !matches!(self, Op::IsNull | Op::IsNotNull)
+    }
+
+    /// `column op $index` and the value to bind for it, if any.
+    #[cfg(feature = "query-builder")]
+    pub(crate) fn render(
+        &self,
+        column: &str,
+        index: usize,
+        value: SqlValue,
+    ) -> (String, Option<SqlValue>) {
+        match self {
+            Op::IsNull | Op::IsNotNull => (format!("{} {}", column, self.as_sql()), None),
+            Op::In | Op::NotIn => match list(value) {
+                SqlValue::List(values) if values.is_empty() => {
+                    let all = if *self == Op::In { "FALSE" } else { "TRUE" };
+                    (all.to_string(), None)
+                }
+                values => (
+                    format!("{} {}(${})", column, self.as_sql(), index),
+                    Some(values),
+                ),
+            },
+            op => (
+                format!("{} {} ${}", column, op.as_sql(), index),
+                Some(value),
+            ),
+        }
+    }
+}
+
+/// `value` as a list, for `IN` / `NOT IN`.
+pub(crate) fn list(value: SqlValue) -> SqlValue {
+    match value {
+        SqlValue::List(_) => value,
+        value => SqlValue::List(vec![value]),
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+impl FromStr for Op {
+    type Err = sqlx::Error;
+
+    /// Parses an operator's SQL, case-insensitively, also accepting `!=`,
+    /// `IN` and `NOT IN`. Anything else is an `InvalidArgument` error.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let op = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        Ok(match op.to_uppercase().as_str() {
+            "=" => Op::Eq,
+            "<>" | "!=" => Op::Ne,
+            ">" => Op::Gt,
+            ">=" => Op::Gte,
+            "<" => Op::Lt,
+            "<=" => Op::Lte,
+            "LIKE" => Op::Like,
+            "NOT LIKE" => Op::NotLike,
+            "ILIKE" => Op::ILike,
+            "NOT ILIKE" => Op::NotILike,
+            "IN" | "= ANY" => Op::In,
+            "NOT IN" | "<> ALL" => Op::NotIn,
+            "IS NULL" => Op::IsNull,
+            "IS NOT NULL" => Op::IsNotNull,
+            "IS DISTINCT FROM" => Op::IsDistinctFrom,
+            "IS NOT DISTINCT FROM" => Op::IsNotDistinctFrom,
+            _ => {
+                return Err(sqlx::Error::InvalidArgument(format!(
+                    "unknown operator `{}`",
+                    s
+                )));
+            }
+        })
+    }
+}
+
+/// A known operator, or [`Op::Other`] holding `op` as written.
+impl From<&str> for Op {
+    fn from(op: &str) -> Self {
+        op.parse().unwrap_or_else(|_| Op::Other(op.to_string()))
+    }
+}
+
+impl From<String> for Op {
+    fn from(op: String) -> Self {
+        op.as_str().into()
+    }
+}
+
+impl From<&Op> for Op {
+    fn from(op: &Op) -> Self {
+        op.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_operators_ignoring_case_and_spacing() {
+        assert_eq!("=".parse::<Op>().unwrap(), Op::Eq);
+        assert_eq!("!=".parse::<Op>().unwrap(), Op::Ne);
+        assert_eq!("<>".parse::<Op>().unwrap(), Op::Ne);
+        assert_eq!("not  like".parse::<Op>().unwrap(), Op::NotLike);
+        assert_eq!("in".parse::<Op>().unwrap(), Op::In);
+        assert_eq!("NOT IN".parse::<Op>().unwrap(), Op::NotIn);
+        assert_eq!(" is not\tnull ".parse::<Op>().unwrap(), Op::IsNotNull);
+        assert_eq!(
+            "is distinct from".parse::<Op>().unwrap(),
+            Op::IsDistinctFrom
+        );
+    }
+
+    #[test]
+    fn parsing_rejects_unknown_operators() {
+        let err = "@>".parse::<Op>().unwrap_err();
+        assert!(matches!(err, sqlx::Error::InvalidArgument(_)));
+        assert!("= 1; DROP TABLE users".parse::<Op>().is_err());
+    }
+
+    #[test]
+    fn conversion_keeps_unknown_operators() {
+        assert_eq!(Op::from("@>"), Op::Other("@>".to_string()));
+        assert_eq!(Op::from(">="), Op::Gte);
+    }
+
+    #[test]
+    fn sql_round_trips_through_parsing() {
+        for op in [Op::Eq, Op::Ne, Op::ILike, Op::In, Op::NotIn, Op::IsNull] {
+            assert_eq!(op.as_sql().parse::<Op>().unwrap(), op);
+        }
+    }
+}
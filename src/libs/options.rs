@@ -0,0 +1,327 @@
+use crate::Direction;
+use crate::session::BoxFuture;
+use sqlx::PgConnection;
+use sqlx::postgres::PgConnectOptions;
+pub use sqlx::postgres::PgSslMode;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Callback run on every new pooled connection, see
+/// [`OrmOptions::after_connect`].
+pub(crate) type AfterConnect =
+    Arc<dyn for<'c> Fn(&'c mut PgConnection) -> BoxFuture<'c, sqlx::Result<()>> + Send + Sync>;
+
+/// ORM-wide settings, applied with `OrmStruct::with_options`.
+///
+/// Connection settings override what the database URL says, so secrets and
+/// certificates can stay out of it.
+///
+/// # Example
+/// ```
+/// # use slintrust::*;
+/// # use std::time::Duration;
+/// # let (url, schemas) = ("postgres://localhost/app".to_string(), Vec::new());
+/// let orm = OrmStruct::new(url, schemas).with_options(
+///     OrmOptions::new()
+///         .table_prefix("app1_")
+///         .ssl_mode(PgSslMode::VerifyFull)
+///         .ssl_root_cert("/etc/ssl/db-ca.pem")
+///         .application_name("billing")
+///         .connect_timeout(Duration::from_secs(5)),
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct OrmOptions {
+    pub(crate) table_prefix: String,
+    ssl_mode: Option<PgSslMode>,
+    ssl_root_cert: Option<PathBuf>,
+    ssl_client_cert: Option<(PathBuf, PathBuf)>,
+    application_name: Option<String>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) pgbouncer: bool,
+    pub(crate) after_connect: Option<AfterConnect>,
+    max_concurrent_queries: Option<usize>,
+    /// Per-table limits, by unprefixed table name.
+    table_concurrency: HashMap<String, usize>,
+    pub(crate) extensions: Vec<String>,
+}
+
+// Manual impl: the `after_connect` callback has no `Debug`.
+impl fmt::Debug for OrmOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrmOptions")
+            .field("table_prefix", &self.table_prefix)
+            .field("ssl_mode", &self.ssl_mode)
+            .field("ssl_root_cert", &self.ssl_root_cert)
+            .field("ssl_client_cert", &self.ssl_client_cert)
+            .field("application_name", &self.application_name)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("pgbouncer", &self.pgbouncer)
+            .field("after_connect", &self.after_connect.is_some())
+            .field("max_concurrent_queries", &self.max_concurrent_queries)
+            .field("table_concurrency", &self.table_concurrency)
+            .field("extensions", &self.extensions)
+            .finish()
+    }
+}
+
+impl OrmOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepends `prefix` to the name of every table the ORM manages, so
+    /// several applications can share one database. Code keeps using the
+    /// unprefixed names (`orm.insert("users", ..)` writes `app1_users`);
+    /// raw SQL can get the real name from `OrmStruct::table_name`.
+    pub fn table_prefix(mut self, prefix: &str) -> Self {
+        self.table_prefix = prefix.to_string();
+        self
+    }
+
+    /// Whether and how strictly to use TLS, like libpq's `sslmode`.
+    pub fn ssl_mode(mut self, mode: PgSslMode) -> Self {
+        self.ssl_mode = Some(mode);
+        self
+    }
+
+    /// PEM file of the certificate authorities trusted to sign the server's
+    /// certificate, checked under `VerifyCa` and `VerifyFull`.
+    pub fn ssl_root_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ssl_root_cert = Some(path.into());
+        self
+    }
+
+    /// PEM certificate and private key presented to the server, for
+    /// certificate authentication.
+    pub fn ssl_client_cert(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.ssl_client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// Name shown for the ORM's connections in `pg_stat_activity` and the
+    /// server logs.
+    pub fn application_name(mut self, name: &str) -> Self {
+        self.application_name = Some(name.to_string());
+        self
+    }
+
+    /// How long `connect` and each later connection checkout may wait
+    /// before failing with `PoolTimedOut`.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Extensions `migrate` creates (`CREATE EXTENSION IF NOT EXISTS`)
+    /// before any table, for column types, defaults and indexes that need
+    /// them. Extensions of `CITEXT`, `HSTORE` and `GEOMETRY` columns are
+    /// created without being listed.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::OrmOptions;
+    /// OrmOptions::new().require_extensions(&["uuid-ossp", "pg_trgm", "postgis"])
+    /// # ;
+    /// ```
+    pub fn require_extensions(mut self, extensions: &[&str]) -> Self {
+        for extension in extensions {
+            if !self.extensions.iter().any(|e| e == extension) {
+                self.extensions.push(extension.to_string());
+            }
+        }
+        self
+    }
+
+    /// Makes the ORM safe behind PgBouncer in transaction pooling mode,
+    /// where consecutive statements may run on different server
+    /// connections: queries aren't prepared as named statements, the
+    /// `extra_float_digits` startup parameter PgBouncer rejects isn't sent,
+    /// and `migrate` refuses `#[slint(temporary)]` tables, which only exist
+    /// on the connection that created them. Session state set with
+    /// `Session::set_local` is transaction-scoped and keeps working.
+    pub fn pgbouncer(mut self) -> Self {
+        self.pgbouncer = true;
+        self
+    }
+
+    /// Runs `f` on every connection the pool opens, before it is first
+    /// used, e.g. to `SET` the time zone or `search_path`. A connection
+    /// whose hook fails is closed and another one opened, until the
+    /// checkout times out (see [`connect_timeout`](Self::connect_timeout)).
+    ///
+    /// # Example
+    /// ```ignore
+    /// OrmOptions::new().after_connect(|conn| {
+    ///     Box::pin(async move {
+    ///         conn.execute("SET TIME ZONE 'UTC'; SET search_path TO app, public").await?;
+    ///         Ok(())
+    ///     })
+    /// })
+    /// ```
+    pub fn after_connect<F>(mut self, f: F) -> Self
+    where
+        F: for<'c> Fn(&'c mut PgConnection) -> BoxFuture<'c, sqlx::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.after_connect = Some(Arc::new(f));
+        self
+    }
+
+    /// Runs at most `n` of the ORM's table queries at once; the others
+    /// wait for a slot, in arrival order. Tables given their own limit
+    /// with [`table_concurrency`](Self::table_concurrency) don't count
+    /// against it.
+    pub fn max_concurrent_queries(mut self, n: usize) -> Self {
+        self.max_concurrent_queries = Some(n.max(1));
+        self
+    }
+
+    /// Runs at most `n` queries on `table` at once, separately from
+    /// [`max_concurrent_queries`](Self::max_concurrent_queries), so a
+    /// burst of heavy queries on one table can't hold every slot.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::OrmOptions;
+    /// OrmOptions::new()
+    ///     .max_concurrent_queries(4)
+    ///     .table_concurrency("analytics_events", 1)
+    /// # ;
+    /// ```
+    pub fn table_concurrency(mut self, table: &str, n: usize) -> Self {
+        self.table_concurrency.insert(table.to_string(), n.max(1));
+        self
+    }
+
+    /// Semaphores enforcing the concurrency limits; `table_name` maps a
+    /// configured table to the name queries report.
+    pub(crate) fn query_limits(&self, table_name: impl Fn(&str) -> String) -> QueryLimits {
+        QueryLimits {
+            global: self.max_concurrent_queries.map(Semaphore::new),
+            tables: self
+                .table_concurrency
+                .iter()
+                .map(|(table, &n)| (table_name(table), Semaphore::new(n)))
+                .collect(),
+        }
+    }
+
+    /// Applies the connection settings to options parsed from the URL.
+    pub(crate) fn apply(&self, mut connect: PgConnectOptions) -> PgConnectOptions {
+        if let Some(mode) = self.ssl_mode {
+            connect = connect.ssl_mode(mode);
+        }
+        if let Some(path) = &self.ssl_root_cert {
+            connect = connect.ssl_root_cert(path);
+        }
+        if let Some((cert, key)) = &self.ssl_client_cert {
+            connect = connect.ssl_client_cert(cert).ssl_client_key(key);
+        }
+        if let Some(name) = &self.application_name {
+            connect = connect.application_name(name);
+        }
+        if self.pgbouncer {
+            connect = connect.statement_cache_capacity(0).extra_float_digits(None);
+        }
+        connect
+    }
+}
+
+/// Concurrency limits of [`OrmOptions::max_concurrent_queries`] and
+/// [`OrmOptions::table_concurrency`], shared by every clone of an ORM.
+#[derive(Default)]
+pub(crate) struct QueryLimits {
+    global: Option<Semaphore>,
+    tables: HashMap<String, Semaphore>,
+}
+
+impl QueryLimits {
+    /// Waits for a slot to query `tables` in one statement; no permits when
+    /// none of them is limited. Tables sharing a semaphore take one slot of
+    /// it, and the semaphores are acquired in a fixed order so two such
+    /// statements can't deadlock.
+    pub(crate) async fn acquire(&self, tables: &[String]) -> Vec<SemaphorePermit<'_>> {
+        let mut semaphores: Vec<&Semaphore> = tables
+            .iter()
+            .filter_map(|table| self.tables.get(table).or(self.global.as_ref()))
+            .collect();
+        semaphores.sort_by_key(|s| *s as *const Semaphore);
+        semaphores.dedup_by_key(|s| *s as *const Semaphore);
+        let mut permits = Vec::with_capacity(semaphores.len());
+        for semaphore in semaphores {
+            // The semaphores are never closed.
+            permits.extend(semaphore.acquire().await.ok());
+        }
+        permits
+    }
+}
+
+/// Ordering and paging for `OrmStruct::get_all_with` and `find_with`.
+///
+/// # Example
+/// ```ignore
+/// let page: Vec<Post> = orm
+///     .get_all_with(
+///         "posts",
+///         &FetchOptions::new()
+///             .order_by("created_at", Direction::Desc)
+///             .limit(20)
+///             .offset(40),
+///     )
+///     .await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    order_by: Vec<(String, Direction)>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl FetchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sorts by `column`; later calls break ties of earlier ones.
+    pub fn order_by(mut self, column: &str, direction: Direction) -> Self {
+        self.order_by.push((column.to_string(), direction));
+        self
+    }
+
+    pub fn limit(mut self, n: i64) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    pub fn offset(mut self, n: i64) -> Self {
+        self.offset = Some(n);
+        self
+    }
+
+    /// ` ORDER BY ... LIMIT n OFFSET m`, each part only when set.
+    pub(crate) fn sql(&self) -> String {
+        let mut sql = String::new();
+        if !self.order_by.is_empty() {
+            let order: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|(column, direction)| format!("{} {}", column, direction.keyword()))
+                .collect();
+            sql += &format!(" ORDER BY {}", order.join(", "));
+        }
+        if let Some(limit) = self.limit {
+            sql += &format!(" LIMIT {}", limit);
+        }
+        if let Some(offset) = self.offset {
+            sql += &format!(" OFFSET {}", offset);
+        }
+        sql
+    }
+}
@@ -1,79 +1,1610 @@
-use crate::libs::schema::TableSchema;
+use crate::batch::Batch;
+use crate::breaker::CircuitBreaker;
+use crate::crypto::{FieldCipher, hash_value};
+use crate::diagram::{Format, schema_diagram};
+use crate::dump::{ConflictPolicy, RESTORE_BATCH, restore_value};
+use crate::events::{Change, EVENT_CAPACITY, OrmEvent};
+use crate::grant::{Grant, Privilege};
+use crate::hstore::map_value;
+use crate::id::{IdStrategy, UuidV4};
+#[cfg(feature = "migrate")]
+use crate::libs::schema::in_schema_of;
+use crate::libs::schema::{
+    ColumnSchema, SlintModel, TableSchema, dependency_order, mask_value, quote_ident,
+};
+#[cfg(feature = "migrate")]
+use crate::migration::{DestructiveOptions, ExecutedStatement, MigrationReport, created_index};
+use crate::naming::NamingStrategy;
+use crate::options::{FetchOptions, OrmOptions, QueryLimits};
+#[cfg(feature = "query-builder")]
 use crate::query_builder::QueryBuilder;
+use crate::row::row_to_json;
+use crate::session::{BoxFuture, RetryPolicy, Session, TxOptions, is_retryable};
+use crate::stats::{Operation, QueryStat, QueryStats, RowCount};
+use crate::value::SqlValue;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use serde_json::Value;
-use sqlx::Column;
-use sqlx::Row;
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{PgPool, query, query_as};
-use uuid::Uuid;
+use sqlx::Postgres;
+use sqlx::postgres::{PgArguments, PgConnectOptions, PgPoolOptions, PgRow};
+use sqlx::{PgPool, Row, query};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
 
+/// Cheap, clonable handle to the ORM.
+///
+/// All state lives behind a shared [`Arc`], so cloning an `OrmStruct` (or
+/// handing it to a `Table`/`Record`) never copies the pool or the schemas.
+/// Handles are `Send + Sync + 'static` and can be stored in web-framework state.
 #[derive(Clone)]
 pub struct OrmStruct {
-    pub database_url: String,
-    pub pool: Option<PgPool>,
-    pub schemas: Vec<TableSchema>,
-    limit: u32,
+    inner: Arc<OrmInner>,
+}
+
+#[derive(Clone)]
+struct OrmInner {
+    database_url: String,
+    /// Shared by every handle derived from this one (`with_actor`,
+    /// `with_options`, ...), so a later `connect` reaches all of them.
+    pool: Arc<OnceLock<PgPool>>,
+    schemas: Vec<TableSchema>,
+    /// The name of each of `schemas` without the table prefix, e.g. `users`
+    /// for `app1_users`. A schema is always named its prefix plus this.
+    base_names: Vec<&'static str>,
+    /// Strategy set by `with_naming`, applied to later registered tables.
+    naming: Option<Arc<dyn NamingStrategy>>,
+    /// Privileges added by `grant`, granted by `migrate`.
+    grants: Vec<Grant>,
+    cipher: Option<FieldCipher>,
+    id_strategies: HashMap<String, Arc<dyn IdStrategy>>,
+    actor: Option<String>,
+    events: broadcast::Sender<OrmEvent>,
+    options: OrmOptions,
+    stats: Arc<QueryStats>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    limits: Arc<QueryLimits>,
+}
+
+impl OrmInner {
+    /// Names every schema after its base name with the current table
+    /// prefix.
+    fn apply_prefix(&mut self) {
+        let prefix = &self.options.table_prefix;
+        for (schema, base) in self.schemas.iter_mut().zip(&self.base_names) {
+            let name = format!("{}{}", prefix, base);
+            if schema.name != name {
+                schema.name = Box::leak(name.into_boxed_str());
+            }
+        }
+    }
+}
+
+pub(crate) type PgQuery<'q> = sqlx::query::Query<'q, Postgres, PgArguments>;
+
+/// Binds a JSON value the same way for inserts, updates and filters:
+/// strings and numbers as text, booleans natively, anything else as NULL.
+pub(crate) fn bind_json(query: PgQuery<'_>, value: serde_json::Value) -> PgQuery<'_> {
+    match value {
+        serde_json::Value::String(s) => query.bind(s),
+        serde_json::Value::Number(n) => query.bind(n.to_string()),
+        serde_json::Value::Bool(b) => query.bind(b),
+        _ => query.bind(None::<String>),
+    }
+}
+
+/// Keeps only `columns` of each row object; all of them when empty.
+pub(crate) fn project_rows(
+    rows: Vec<serde_json::Value>,
+    columns: &[&str],
+) -> Vec<serde_json::Value> {
+    if columns.is_empty() {
+        return rows;
+    }
+    rows.into_iter()
+        .map(|mut row| {
+            if let serde_json::Value::Object(map) = &mut row {
+                map.retain(|k, _| columns.contains(&k.as_str()));
+            }
+            row
+        })
+        .collect()
+}
+
+/// Snapshot of the connection pool, suitable for readiness probes and metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Total connections currently held by the pool (idle + active).
+    pub size: u32,
+    /// Connections sitting idle in the pool.
+    pub idle: usize,
+    /// Connections checked out and running queries.
+    pub active: usize,
 }
 
 impl OrmStruct {
     pub fn new(database_url: String, schemas: Vec<TableSchema>) -> Self {
-        println!("Connecting to {}", database_url);
         Self {
-            database_url,
-            pool: None,
-            schemas,
-            limit: 0,
+            inner: Arc::new(OrmInner {
+                database_url,
+                pool: Arc::new(OnceLock::new()),
+                base_names: schemas.iter().map(|s| s.name).collect(),
+                schemas,
+                naming: None,
+                grants: Vec::new(),
+                cipher: None,
+                id_strategies: HashMap::new(),
+                actor: None,
+                events: broadcast::channel(EVENT_CAPACITY).0,
+                options: OrmOptions::default(),
+                stats: Arc::default(),
+                breaker: None,
+                limits: Arc::default(),
+            }),
         }
     }
 
-    pub async fn connect(&mut self) -> sqlx::Result<()> {
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&self.database_url)
-            .await?;
-        self.pool = Some(pool);
+    /// Sets the AES-256-GCM key used for `#[slint(encrypted)]` columns.
+    /// Configure this before cloning the handle; existing clones keep their
+    /// previous settings.
+    pub fn with_encryption_key(mut self, key: &[u8; 32]) -> Self {
+        Arc::make_mut(&mut self.inner).cipher = Some(FieldCipher::new(key));
+        self
+    }
+
+    /// Generates keys for the `#[slint(uuid)]` columns of `table_name` with
+    /// `strategy` instead of random UUIDs.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// # }
+    /// # let (url, schemas) = ("postgres://localhost/app".to_string(), vec![User::slint_schema()]);
+    /// let orm = OrmStruct::new(url, schemas)
+    ///     .with_id_strategy("orders", Snowflake::new(1))
+    ///     .with_id_strategy("events", Ulid);
+    /// ```
+    pub fn with_id_strategy(
+        mut self,
+        table_name: &str,
+        strategy: impl IdStrategy + 'static,
+    ) -> Self {
+        Arc::make_mut(&mut self.inner)
+            .id_strategies
+            .insert(table_name.to_string(), Arc::new(strategy));
+        self
+    }
+
+    /// Applies `options` (see [`OrmOptions`]). Tables are named with the
+    /// new table prefix in place of the old one, so calling it again
+    /// doesn't stack prefixes. Set options while building the ORM anyway:
+    /// prefixed table names live for the rest of the program.
+    pub fn with_options(mut self, options: OrmOptions) -> Self {
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.options = options;
+        inner.apply_prefix();
+        let limits = self
+            .inner
+            .options
+            .query_limits(|table| self.table_name(table));
+        Arc::make_mut(&mut self.inner).limits = Arc::new(limits);
+        self
+    }
+
+    /// Fails the ORM's table operations fast while the database is down:
+    /// after `failure_threshold` consecutive connection or query failures
+    /// they return an error matched by [`is_circuit_open`](crate::is_circuit_open)
+    /// for `cool_down`, then one query probes the database. See
+    /// [`CircuitBreaker`].
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// # }
+    /// # let (url, schemas) = ("postgres://localhost/app".to_string(), vec![User::slint_schema()]);
+    /// # use std::time::Duration;
+    /// let orm = OrmStruct::new(url, schemas).with_circuit_breaker(5, Duration::from_secs(30));
+    /// let mut changes = orm.circuit_breaker().unwrap().subscribe();
+    /// ```
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, cool_down: Duration) -> Self {
+        Arc::make_mut(&mut self.inner).breaker =
+            Some(Arc::new(CircuitBreaker::new(failure_threshold, cool_down)));
+        self
+    }
+
+    /// The breaker set with [`with_circuit_breaker`](Self::with_circuit_breaker),
+    /// shared by every clone of this handle.
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.inner.breaker.as_deref()
+    }
+
+    /// Renames the registered tables whose model doesn't set `table_name`
+    /// with `strategy`, and those registered later with
+    /// [`register_schema`](Self::register_schema). Call it once while
+    /// building the ORM: the new names live for the rest of the program.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # let url = "postgres://localhost/app".to_string();
+    /// #[slint]
+    /// #[derive(Serialize, Deserialize)]
+    /// struct UserProfile {
+    ///     #[slint(primary_key)]
+    ///     id: String,
+    /// }
+    ///
+    /// let orm = OrmStruct::new(url, vec![UserProfile::slint_schema()])
+    ///     .with_naming(NamingRules::new().snake_case().plural());
+    /// assert_eq!(orm.schema_of::<UserProfile>().name, "user_profiles");
+    /// ```
+    pub fn with_naming(mut self, strategy: impl NamingStrategy + 'static) -> Self {
+        let inner = Arc::make_mut(&mut self.inner);
+        for (schema, base) in inner.schemas.iter().zip(inner.base_names.iter_mut()) {
+            if !schema.explicit_name {
+                *base = Box::leak(strategy.table_name(schema.model).into_boxed_str());
+            }
+        }
+        inner.apply_prefix();
+        inner.naming = Some(Arc::new(strategy));
+        self
+    }
+
+    /// Registers a table after construction, e.g. one a plugin brings,
+    /// named with the ORM's naming strategy and table prefix like those
+    /// given to `new`. Works before and after `connect`; create the table
+    /// with [`migrate_table`](Self::migrate_table). Clones of the handle
+    /// made earlier, and the `Table`s built from them, don't see it.
+    ///
+    /// Registering a table name that is already registered fails with
+    /// `InvalidArgument`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orm.register_schema(Invoice::slint_schema())?;
+    /// orm.migrate_table("invoices").await?;
+    /// ```
+    pub fn register_schema(&mut self, schema: TableSchema) -> sqlx::Result<()> {
+        let base: &'static str = match &self.inner.naming {
+            Some(naming) if !schema.explicit_name => {
+                Box::leak(naming.table_name(schema.model).into_boxed_str())
+            }
+            _ => schema.name,
+        };
+        let name = format!("{}{}", self.inner.options.table_prefix, base);
+        if self.schemas().iter().any(|s| s.name == name) {
+            return Err(sqlx::Error::InvalidArgument(format!(
+                "table `{}` is already registered",
+                name
+            )));
+        }
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.schemas.push(schema);
+        inner.base_names.push(base);
+        inner.apply_prefix();
         Ok(())
     }
 
+    /// [`register_schema`](Self::register_schema) with the schema of
+    /// model `T`.
+    pub fn register_model<T: SlintModel>(&mut self) -> sqlx::Result<()> {
+        self.register_schema(T::slint_schema())
+    }
+
+    /// Has `migrate()` grant `privileges` on `tables` to `role`, after
+    /// creating the tables, so permission setup lives next to the schema.
+    /// Table names get the table prefix. The role must already exist;
+    /// `"public"` grants to every role.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orm.grant("readonly_role", &["users", "orders"], &[Privilege::Select])?;
+    /// orm.grant("app", &["users"], &[Privilege::Select, Privilege::Insert, Privilege::Update])?;
+    /// orm.migrate().await?;
+    /// ```
+    pub fn grant(
+        &mut self,
+        role: &str,
+        tables: &[&str],
+        privileges: &[Privilege],
+    ) -> sqlx::Result<()> {
+        if role.is_empty() || tables.is_empty() || privileges.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "a grant needs a role, at least one table and at least one privilege".into(),
+            ));
+        }
+        let grant = Grant {
+            role: role.to_string(),
+            tables: tables.iter().map(|t| self.table_name(t)).collect(),
+            privileges: privileges.to_vec(),
+        };
+        Arc::make_mut(&mut self.inner).grants.push(grant);
+        Ok(())
+    }
+
+    /// `CREATE EXTENSION` for each of [`OrmOptions::require_extensions`].
+    fn required_extension_statements(&self) -> Vec<String> {
+        self.inner
+            .options
+            .extensions
+            .iter()
+            .map(|e| format!("CREATE EXTENSION IF NOT EXISTS {}", quote_ident(e)))
+            .collect()
+    }
+
+    /// The `GRANT` statements for those granted tables `include` accepts.
+    fn grant_statements(&self, include: impl Fn(&str) -> bool) -> Vec<String> {
+        self.inner
+            .grants
+            .iter()
+            .filter_map(|g| g.statement(&include))
+            .collect()
+    }
+
+    /// The registered schema of model `T`, with the table name this ORM
+    /// uses for it, or `T`'s own schema if it isn't registered.
+    pub fn schema_of<T: SlintModel>(&self) -> TableSchema {
+        let schema = T::slint_schema();
+        self.schemas()
+            .iter()
+            .find(|s| {
+                if schema.explicit_name {
+                    s.name == self.table_name(schema.name)
+                } else {
+                    !s.explicit_name && s.model == schema.model
+                }
+            })
+            .cloned()
+            .unwrap_or(schema)
+    }
+
+    /// A handle acting on behalf of `actor` (e.g. the current user id):
+    /// inserts and updates through it fill the `created_by` / `updated_by`
+    /// columns of `#[slint(blame)]` tables. The handle shares this one's
+    /// pool once connected.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let orm = orm.with_actor(session.user_id.to_string());
+    /// Table::<Invoice>::from_schema(&orm).insert(&invoice).await?;
+    /// ```
+    pub fn with_actor(&self, actor: impl Into<String>) -> Self {
+        let mut handle = self.clone();
+        Arc::make_mut(&mut handle.inner).actor = Some(actor.into());
+        handle
+    }
+
+    /// The actor set by [`with_actor`](Self::with_actor), if any.
+    pub fn actor(&self) -> Option<&str> {
+        self.inner.actor.as_deref()
+    }
+
+    /// Opens the connection pool. The pool is shared by every clone of this
+    /// handle and every handle derived from it, whether derived before or
+    /// after connecting; connecting an already connected ORM is a no-op.
+    pub async fn connect(&self) -> sqlx::Result<()> {
+        if self.inner.pool.get().is_some() {
+            return Ok(());
+        }
+        let mut pool = PgPoolOptions::new().max_connections(5);
+        if let Some(timeout) = self.inner.options.connect_timeout {
+            pool = pool.acquire_timeout(timeout);
+        }
+        if let Some(hook) = self.inner.options.after_connect.clone() {
+            pool = pool.after_connect(move |conn, _| hook(conn));
+        }
+        let pool = pool.connect_with(self.connect_options()?).await?;
+        // Another clone may have won the race; its pool is kept and ours dropped.
+        let _ = self.inner.pool.set(pool);
+        Ok(())
+    }
+
+    /// The database URL with the connection settings of [`OrmOptions`]
+    /// (TLS, application name) applied, as used by [`connect`](Self::connect).
+    pub fn connect_options(&self) -> sqlx::Result<PgConnectOptions> {
+        let connect: PgConnectOptions = self.inner.database_url.parse()?;
+        Ok(self.inner.options.apply(connect))
+    }
+
+    pub fn database_url(&self) -> &str {
+        &self.inner.database_url
+    }
+
+    pub fn schemas(&self) -> &[TableSchema] {
+        &self.inner.schemas
+    }
+
+    /// The registered schemas with each after the tables its foreign keys
+    /// refer to, the order `migrate()` creates them in. Where foreign keys
+    /// form a cycle, the first table of it reached comes first; the keys
+    /// themselves are added once every table exists.
+    pub fn schemas_in_dependency_order(&self) -> Vec<&TableSchema> {
+        let schemas = self.schemas();
+        let names: Vec<String> = schemas.iter().map(|s| s.name.to_string()).collect();
+        let parents: Vec<Vec<usize>> = schemas
+            .iter()
+            .map(|s| {
+                s.columns
+                    .iter()
+                    .filter_map(|c| c.references())
+                    .filter_map(|(table, _)| {
+                        let table = self.table_name(table);
+                        names.iter().position(|n| *n == table)
+                    })
+                    .collect()
+            })
+            .collect();
+        dependency_order(&names, &parents)
+            .into_iter()
+            .map(|i| &schemas[i])
+            .collect()
+    }
+
+    /// Entity-relationship diagram of the registered tables, drawn from
+    /// their keys and `foreign_key` declarations.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// # }
+    /// # let (url, schemas) = ("postgres://localhost/app".to_string(), vec![User::slint_schema()]);
+    /// # let orm = OrmStruct::new(url, schemas);
+    /// let diagram = orm.schema_diagram(Format::Mermaid);
+    /// assert!(diagram.starts_with("erDiagram"));
+    /// ```
+    pub fn schema_diagram(&self, format: Format) -> String {
+        schema_diagram(self.schemas(), format)
+    }
+
+    // -------- Change events --------
+    /// Receives an [`OrmEvent`] for every row inserted, updated or deleted
+    /// through this ORM or its clones, including `Table` and `Record`
+    /// writes and batches. Raw SQL and sessions are not reported.
+    ///
+    /// While nobody is subscribed, writes run exactly as before; with
+    /// subscribers they add a `RETURNING` clause to report the rows.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut events = orm.subscribe();
+    /// tokio::spawn(async move {
+    ///     while let Ok(event) = events.recv().await {
+    ///         println!("{} changed", event.table());
+    ///     }
+    /// });
+    /// ```
+    pub fn subscribe(&self) -> broadcast::Receiver<OrmEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// ` RETURNING <select list>` for `table_name` when someone is
+    /// subscribed to events, so a write can report the rows it touched.
+    pub(crate) fn event_returning(&self, table_name: &str) -> Option<String> {
+        (self.inner.events.receiver_count() > 0)
+            .then(|| format!(" RETURNING {}", self.select_list(table_name)))
+    }
+
+    /// Sends one `change` event per row to subscribers.
+    pub(crate) fn emit_rows(&self, change: Change, table_name: &str, rows: &[serde_json::Value]) {
+        let (table, model) = self
+            .find_schema(table_name)
+            .map_or((table_name, ""), |s| (s.name, s.model));
+        for row in rows {
+            let mut row = row.clone();
+            self.strip_hashed(table_name, &mut row);
+            // Sending only fails when every receiver has been dropped.
+            let _ = self.inner.events.send(change.event(table, model, row));
+        }
+    }
+
+    /// Runs a write on `table_name` and returns the rows affected; when it
+    /// ends in an event `RETURNING` clause, reports the returned rows to
+    /// subscribers as `change`.
+    pub(crate) async fn execute_reporting(
+        &self,
+        table_name: &str,
+        query: PgQuery<'_>,
+        returning: bool,
+        change: Change,
+    ) -> sqlx::Result<u64> {
+        let operation = change.operation();
+        if !returning {
+            let result = self
+                .timed(table_name, operation, query.execute(self.pool()))
+                .await?;
+            return Ok(result.rows_affected());
+        }
+        let rows = self
+            .timed(table_name, operation, query.fetch_all(self.pool()))
+            .await?;
+        let rows = rows
+            .iter()
+            .map(|r| self.row_json(table_name, r))
+            .collect::<sqlx::Result<Vec<_>>>()?;
+        self.emit_rows(change, table_name, &rows);
+        Ok(rows.len() as u64)
+    }
+
+    /// Removes the hashed columns of `table_name` from a row object.
+    pub(crate) fn strip_hashed(&self, table_name: &str, row: &mut serde_json::Value) {
+        let schema = self.find_schema(table_name);
+        if let (Some(schema), serde_json::Value::Object(map)) = (schema, row) {
+            for c in schema.columns.iter().filter(|c| c.hashed.is_some()) {
+                map.remove(c.name);
+            }
+        }
+    }
+
+    // -------- Shutdown --------
+    /// Waits for in-flight queries to finish, then closes every pooled connection.
+    /// Calling `close` on a disconnected ORM is a no-op.
+    pub async fn close(&self) {
+        if let Some(pool) = self.inner.pool.get() {
+            pool.close().await;
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.inner.pool.get().is_some_and(|p| !p.is_closed())
+    }
+
     pub fn pool(&self) -> &PgPool {
-        self.pool.as_ref().expect("Database not connected")
+        self.inner.pool.get().expect("Database not connected")
+    }
+
+    /// A query on `sql`. In PgBouncer mode (see `OrmOptions::pgbouncer`) it
+    /// is sent as an unnamed statement, so nothing prepared outlives the
+    /// server connection it ran on.
+    pub(crate) fn statement<'q>(&self, sql: &'q str) -> PgQuery<'q> {
+        query(sql).persistent(!self.inner.options.pgbouncer)
+    }
+
+    // -------- Query statistics --------
+    /// Execution counts, latencies and rows per `(table, operation)` since
+    /// the ORM was created (or last reset), busiest first. Queries made
+    /// through `OrmStruct`, `Table`, `Record`, the query builders and
+    /// batches are counted; raw SQL, sessions and migrations are not.
+    ///
+    /// # Example
+    /// ```ignore
+    /// for stat in orm.query_stats().iter().take(5) {
+    ///     println!("{:?} {}: {} x {:?} (p95 {:?})", stat.operation, stat.table, stat.count, stat.mean, stat.p95);
+    /// }
+    /// ```
+    pub fn query_stats(&self) -> Vec<QueryStat> {
+        self.inner.stats.snapshot()
+    }
+
+    /// Clears the statistics returned by [`query_stats`](Self::query_stats).
+    pub fn reset_query_stats(&self) {
+        self.inner.stats.reset()
+    }
+
+    /// Awaits `query` and records its run time and row count under
+    /// `table_name` and `operation`. With a circuit breaker, `query` isn't
+    /// run while the circuit is open and its outcome is counted; with
+    /// concurrency limits, it waits for a slot first.
+    pub(crate) async fn timed<R: RowCount>(
+        &self,
+        table_name: &str,
+        operation: Operation,
+        query: impl Future<Output = sqlx::Result<R>>,
+    ) -> sqlx::Result<R> {
+        self.timed_tables(&[table_name], query, |result, elapsed| {
+            let rows = result.as_ref().map_or(0, RowCount::row_count);
+            self.record_stat(table_name, operation, elapsed, rows);
+        })
+        .await
+    }
+
+    /// [`timed`](Self::timed) for one statement on several tables, such as
+    /// a batch: it waits for a slot of every table and hands the outcome
+    /// and run time to `record`, which records the statistics.
+    pub(crate) async fn timed_tables<R>(
+        &self,
+        tables: &[&str],
+        query: impl Future<Output = sqlx::Result<R>>,
+        record: impl FnOnce(&sqlx::Result<R>, Duration),
+    ) -> sqlx::Result<R> {
+        if let Some(breaker) = &self.inner.breaker {
+            breaker.admit()?;
+        }
+        let tables: Vec<String> = tables.iter().map(|t| self.table_name(t)).collect();
+        let _permits = self.inner.limits.acquire(&tables).await;
+        let started = Instant::now();
+        let result = query.await;
+        if let Some(breaker) = &self.inner.breaker {
+            breaker.record(&result);
+        }
+        record(&result, started.elapsed());
+        result
+    }
+
+    /// Records a query [`timed`](Self::timed) by its caller, for results
+    /// read as a stream.
+    pub(crate) fn record_stat(
+        &self,
+        table_name: &str,
+        operation: Operation,
+        elapsed: Duration,
+        rows: u64,
+    ) {
+        self.inner
+            .stats
+            .record(&self.table_name(table_name), operation, elapsed, rows);
+    }
+
+    // -------- Health check --------
+    /// Runs `SELECT 1`, for readiness probes. Fails with
+    /// `sqlx::Error::PoolClosed` before [`connect`](Self::connect) and after
+    /// [`close`](Self::close) instead of panicking.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let ready = orm.ping().await.is_ok();
+    /// ```
+    pub async fn ping(&self) -> sqlx::Result<()> {
+        let pool = self.inner.pool.get().ok_or(sqlx::Error::PoolClosed)?;
+        self.statement("SELECT 1").execute(pool).await?;
+        Ok(())
+    }
+
+    /// Connection counts of the pool, for metrics and autoscaling
+    /// signals; `None` before [`connect`](Self::connect).
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// let orm = OrmStruct::new("postgres://localhost/app".into(), Vec::new());
+    /// assert_eq!(orm.pool_status(), None);
+    /// ```
+    pub fn pool_status(&self) -> Option<PoolStatus> {
+        let pool = self.inner.pool.get()?;
+        let size = pool.size();
+        let idle = pool.num_idle();
+        Some(PoolStatus {
+            size,
+            idle,
+            active: (size as usize).saturating_sub(idle),
+        })
+    }
+
+    // -------- Create tables --------
+    /// Creates the registered tables that don't exist yet and adds the
+    /// columns an existing table lacks (`ALTER TABLE ... ADD COLUMN`), so
+    /// new struct fields reach the database, then the `foreign_key`
+    /// constraints missing from them. Existing columns are never changed
+    /// or dropped. The returned report lists what changed.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let report = orm.migrate().await?;
+    /// log::info!("migration:\n{}", report);
+    /// ```
+    #[cfg(feature = "migrate")]
+    pub async fn migrate(&self) -> sqlx::Result<MigrationReport> {
+        let (mut report, statements) = self.plan_migration().await?;
+        self.run_migration(&mut report, statements).await?;
+        Ok(report)
+    }
+
+    /// Like [`migrate`](Self::migrate), then also drops the columns of
+    /// registered tables that their structs no longer have, and the tables
+    /// no struct is registered for. Only tables of the current schema named
+    /// with the ORM's table prefix are considered, so no table is dropped
+    /// without a prefix; history, job and extension tables are kept.
+    ///
+    /// Nothing is dropped without [`DestructiveOptions::allow_data_loss`];
+    /// preview the changes with [`DestructiveOptions::dry_run`] first.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let preview = orm.migrate_destructive(DestructiveOptions::new().dry_run()).await?;
+    /// println!("{}", preview);
+    /// orm.migrate_destructive(DestructiveOptions::new().allow_data_loss()).await?;
+    /// ```
+    #[cfg(feature = "migrate")]
+    pub async fn migrate_destructive(
+        &self,
+        options: DestructiveOptions,
+    ) -> sqlx::Result<MigrationReport> {
+        let (mut report, mut statements) = self.plan_migration().await?;
+
+        for schema in self.schemas().iter().filter(|s| !s.temporary) {
+            for column in self.live_columns(schema.name).await? {
+                if schema.column(&column).is_none() {
+                    statements.push(format!(
+                        "ALTER TABLE {} DROP COLUMN {}",
+                        schema.name, column
+                    ));
+                    report
+                        .columns_dropped
+                        .push((schema.name.to_string(), column));
+                }
+            }
+        }
+        for table in self.stale_tables().await? {
+            // CASCADE drops the foreign keys of tables in a cycle with it.
+            statements.push(format!("DROP TABLE {} CASCADE", quote_ident(&table)));
+            report.tables_dropped.push(table);
+        }
+
+        if options.dry_run {
+            report.dry_run = true;
+            report.statements = statements
+                .into_iter()
+                .map(|sql| ExecutedStatement {
+                    sql,
+                    duration: Duration::ZERO,
+                })
+                .collect();
+            return Ok(report);
+        }
+        let drops = report.tables_dropped.len() + report.columns_dropped.len();
+        if drops > 0 && !options.allow_data_loss {
+            return Err(sqlx::Error::InvalidArgument(format!(
+                "migration would drop {} tables or columns; preview it with dry_run \
+                 and confirm with allow_data_loss",
+                drops
+            )));
+        }
+        self.run_migration(&mut report, statements).await?;
+        Ok(report)
+    }
+
+    /// [`migrate`](Self::migrate) for the single registered table
+    /// `table_name`, typically one added with
+    /// [`register_schema`](Self::register_schema) after the others were
+    /// migrated.
+    #[cfg(feature = "migrate")]
+    pub async fn migrate_table(&self, table_name: &str) -> sqlx::Result<MigrationReport> {
+        let schema = self.find_schema(table_name).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("table `{}` is not registered", table_name))
+        })?;
+        let mut report = MigrationReport::default();
+        let mut statements = self.required_extension_statements();
+        self.plan_table(schema, &mut report, &mut statements)
+            .await?;
+        self.plan_foreign_keys(schema, &mut report, &mut statements)
+            .await?;
+        statements.extend(self.grant_statements(|table| table == schema.name));
+        self.run_migration(&mut report, statements).await?;
+        Ok(report)
+    }
+
+    /// The statements `migrate` runs and a report of the changes they make.
+    #[cfg(feature = "migrate")]
+    async fn plan_migration(&self) -> sqlx::Result<(MigrationReport, Vec<String>)> {
+        let mut report = MigrationReport::default();
+        let mut statements = self.required_extension_statements();
+        for schema in self.schemas_in_dependency_order() {
+            self.plan_table(schema, &mut report, &mut statements)
+                .await?;
+        }
+        for schema in self.schemas() {
+            self.plan_foreign_keys(schema, &mut report, &mut statements)
+                .await?;
+        }
+        statements.extend(self.grant_statements(|_| true));
+        Ok((report, statements))
+    }
+
+    /// Adds the statements migrating `schema`'s table to `statements`, and
+    /// the changes they make to `report`.
+    #[cfg(feature = "migrate")]
+    async fn plan_table(
+        &self,
+        schema: &TableSchema,
+        report: &mut MigrationReport,
+        statements: &mut Vec<String>,
+    ) -> sqlx::Result<()> {
+        if schema.temporary && self.inner.options.pgbouncer {
+            return Err(sqlx::Error::InvalidArgument(format!(
+                "temporary table `{}` can't be used in PgBouncer mode",
+                schema.name
+            )));
+        }
+        let live = self.live_columns(schema.name).await?;
+        let live_history = if schema.versioned {
+            self.live_columns(&schema.history_table()).await?
+        } else {
+            Vec::new()
+        };
+
+        if live.is_empty() {
+            report.tables_created.push(schema.name.to_string());
+        } else {
+            report.columns_added.extend(
+                schema
+                    .missing_columns(&live)
+                    .map(|c| (schema.name.to_string(), c.name.to_string())),
+            );
+        }
+        if schema.versioned && live_history.is_empty() {
+            report
+                .tables_created
+                .push(format!("{}_history", schema.name));
+        }
+
+        for statement in schema.ddl_statements(&live, &live_history)? {
+            if let Some(index) = created_index(&statement)
+                && !self
+                    .relation_exists(&in_schema_of(schema.name, index))
+                    .await?
+            {
+                report
+                    .indexes_created
+                    .push(index.trim_matches('"').to_string());
+            }
+            statements.push(statement);
+        }
+        Ok(())
+    }
+
+    /// Adds the foreign keys of `schema`'s table that the database lacks to
+    /// `statements`, and to `report`. They come after every table is
+    /// created, so tables can refer to each other in a cycle.
+    #[cfg(feature = "migrate")]
+    async fn plan_foreign_keys(
+        &self,
+        schema: &TableSchema,
+        report: &mut MigrationReport,
+        statements: &mut Vec<String>,
+    ) -> sqlx::Result<()> {
+        for column in schema.columns {
+            let Some((parent, _)) = column.references() else {
+                continue;
+            };
+            let exists: bool = self
+                .statement(
+                    "SELECT EXISTS (SELECT 1 FROM pg_constraint \
+                     WHERE conrelid = to_regclass($1) AND conname = $2)",
+                )
+                .bind(schema.name)
+                .bind(schema.foreign_key_name(column.name))
+                .fetch_one(self.pool())
+                .await?
+                .try_get(0)?;
+            if !exists {
+                statements.push(schema.foreign_key_statement(column, &self.table_name(parent)));
+                report
+                    .foreign_keys_added
+                    .push((schema.name.to_string(), column.name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "migrate")]
+    async fn run_migration(
+        &self,
+        report: &mut MigrationReport,
+        statements: Vec<String>,
+    ) -> sqlx::Result<()> {
+        for statement in statements {
+            let started = Instant::now();
+            self.statement(&statement).execute(self.pool()).await?;
+            report.statements.push(ExecutedStatement {
+                sql: statement,
+                duration: started.elapsed(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Tables of the current schema named with the table prefix that no
+    /// registered struct maps to, other than history and job tables and
+    /// tables owned by an extension, in an order they can be dropped in.
+    /// Without a table prefix there are none: the schema may hold tables
+    /// the ORM doesn't manage.
+    #[cfg(feature = "migrate")]
+    async fn stale_tables(&self) -> sqlx::Result<Vec<String>> {
+        let prefix = &self.inner.options.table_prefix;
+        if prefix.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = self
+            .statement(
+                "SELECT c.relname::TEXT FROM pg_class c \
+                 WHERE c.relnamespace = current_schema()::regnamespace \
+                 AND c.relkind IN ('r', 'p') AND NOT c.relispartition \
+                 AND starts_with(c.relname, $1) \
+                 AND NOT EXISTS (SELECT 1 FROM pg_depend d \
+                 WHERE d.classid = 'pg_class'::regclass AND d.objid = c.oid AND d.deptype = 'e') \
+                 ORDER BY c.relname",
+            )
+            .bind(prefix)
+            .fetch_all(self.pool())
+            .await?;
+        // Registered tables may be schema-qualified; the rows are not.
+        let mut keep: Vec<String> = vec![self.table_name(crate::jobs::JOBS_TABLE)];
+        for schema in self.schemas() {
+            keep.push(schema.unqualified_name().to_string());
+            keep.push(format!("{}_history", schema.unqualified_name()));
+        }
+        let mut stale = Vec::new();
+        for row in rows {
+            let table: String = row.try_get(0)?;
+            if !keep.contains(&table) {
+                stale.push(table);
+            }
+        }
+
+        // Drop children before the tables their foreign keys refer to.
+        let mut parents = vec![Vec::new(); stale.len()];
+        let references = self
+            .statement(
+                "SELECT child.relname::TEXT, parent.relname::TEXT FROM pg_constraint c \
+                 JOIN pg_class child ON child.oid = c.conrelid \
+                 JOIN pg_class parent ON parent.oid = c.confrelid \
+                 WHERE c.contype = 'f' AND child.relnamespace = current_schema()::regnamespace \
+                 AND parent.relnamespace = child.relnamespace",
+            )
+            .fetch_all(self.pool())
+            .await?;
+        for row in references {
+            let (child, parent): (String, String) = (row.try_get(0)?, row.try_get(1)?);
+            let position = |table: &str| stale.iter().position(|t| t == table);
+            if let (Some(child), Some(parent)) = (position(&child), position(&parent)) {
+                parents[child].push(parent);
+            }
+        }
+        let order = dependency_order(&stale, &parents);
+        Ok(order.into_iter().rev().map(|i| stale[i].clone()).collect())
+    }
+
+    #[cfg(feature = "migrate")]
+    async fn relation_exists(&self, name: &str) -> sqlx::Result<bool> {
+        self.statement("SELECT to_regclass($1) IS NOT NULL")
+            .bind(name)
+            .fetch_one(self.pool())
+            .await?
+            .try_get(0)
+    }
+
+    /// Columns of `table` as it exists in the database; empty if it doesn't.
+    #[cfg(feature = "migrate")]
+    async fn live_columns(&self, table: &str) -> sqlx::Result<Vec<String>> {
+        let rows = self
+            .statement(
+                "SELECT attname::TEXT FROM pg_attribute \
+                 WHERE attrelid = to_regclass($1) AND attnum > 0 AND NOT attisdropped",
+            )
+            .bind(table)
+            .fetch_all(self.pool())
+            .await?;
+        rows.iter().map(|r| r.try_get(0)).collect()
+    }
+
+    // -------- Schema export --------
+    /// Writes the statements `migrate()` runs on an empty database to
+    /// `path`, for review and for diffing in CI: the extensions, then each
+    /// registered table after the tables it refers to, then the foreign
+    /// keys, then the grants.
+    /// Every statement ends in `;` and a newline.
+    pub async fn dump_schema_sql(&self, path: impl AsRef<std::path::Path>) -> sqlx::Result<()> {
+        let mut sql = String::new();
+        let extensions = self.required_extension_statements();
+        for statement in &extensions {
+            sql.push_str(statement);
+            sql.push_str(";\n");
+        }
+        if !extensions.is_empty() {
+            sql.push('\n');
+        }
+        for schema in self.schemas_in_dependency_order() {
+            for statement in schema.ddl_statements(&[], &[])? {
+                sql.push_str(&statement);
+                sql.push_str(";\n");
+            }
+            sql.push('\n');
+        }
+        let mut foreign_keys = false;
+        for schema in self.schemas() {
+            for column in schema.columns {
+                if let Some((parent, _)) = column.references() {
+                    let statement = schema.foreign_key_statement(column, &self.table_name(parent));
+                    sql.push_str(&statement);
+                    sql.push_str(";\n");
+                    foreign_keys = true;
+                }
+            }
+        }
+        if foreign_keys {
+            sql.push('\n');
+        }
+        for statement in self.grant_statements(|_| true) {
+            sql.push_str(&statement);
+            sql.push_str(";\n");
+        }
+        tokio::fs::write(path, sql).await?;
+        Ok(())
+    }
+
+    /// Writes every registered `TableSchema`, with its columns and their
+    /// constraints, to `path` as pretty-printed JSON for non-Rust services.
+    pub async fn dump_schema_json(&self, path: impl AsRef<std::path::Path>) -> sqlx::Result<()> {
+        let json = serde_json::to_string_pretty(self.schemas())
+            .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+
+    // -------- Table dump --------
+    /// Streams every row of `table_name` to `writer` as NDJSON, one JSON
+    /// object per line, ordered by the key column. Values are written as
+    /// stored: hashed and encrypted columns stay hashed and encrypted, and
+    /// masked columns are not redacted. Returns the number of rows written.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut file = tokio::fs::File::create("users.ndjson").await?;
+    /// orm.dump_table_json("users", &mut file).await?;
+    /// ```
+    pub async fn dump_table_json<W>(&self, table_name: &str, writer: &mut W) -> sqlx::Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let table = self.table_name(table_name);
+        let order = self
+            .find_schema(&table)
+            .and_then(TableSchema::key_column)
+            .map_or_else(String::new, |key| format!(" ORDER BY {}", key));
+        let sql = format!("SELECT * FROM {}{}", table, order);
+        let started = Instant::now();
+        let mut rows = self.statement(&sql).fetch(self.pool());
+        let mut written = 0;
+        while let Some(row) = rows.try_next().await? {
+            let mut line = row_to_json(&row).to_string();
+            line.push('\n');
+            writer.write_all(line.as_bytes()).await?;
+            written += 1;
+        }
+        writer.flush().await?;
+        self.record_stat(&table, Operation::Select, started.elapsed(), written);
+        Ok(written)
+    }
+
+    /// Inserts the rows of an NDJSON dump of `table_name` (see
+    /// [`dump_table_json`](Self::dump_table_json)) in batched statements,
+    /// with `policy` deciding what happens to rows that already exist.
+    /// Values are inserted as dumped, without hashing or encrypting them
+    /// again, and `serial` keys are kept, with their sequences moved past
+    /// the restored rows. No change events are sent. Returns the number of
+    /// rows inserted or overwritten.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let file = tokio::io::BufReader::new(tokio::fs::File::open("users.ndjson").await?);
+    /// orm.restore_table_json("users", file, ConflictPolicy::Skip).await?;
+    /// ```
+    pub async fn restore_table_json<R>(
+        &self,
+        table_name: &str,
+        reader: R,
+        policy: ConflictPolicy,
+    ) -> sqlx::Result<u64>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let schema = self.schema_for(table_name);
+        let suffix = match policy {
+            ConflictPolicy::Error => String::new(),
+            ConflictPolicy::Skip => " ON CONFLICT DO NOTHING".to_string(),
+            ConflictPolicy::Overwrite => {
+                let key = schema.key_column().ok_or_else(|| {
+                    sqlx::Error::InvalidArgument(format!(
+                        "overwriting rows of `{}` needs a key column",
+                        schema.name
+                    ))
+                })?;
+                let updates: Vec<String> = schema
+                    .columns
+                    .iter()
+                    .filter(|c| c.name != key && c.generated.is_none())
+                    .map(|c| format!("{} = EXCLUDED.{}", c.name, c.name))
+                    .collect();
+                if updates.is_empty() {
+                    format!(" ON CONFLICT ({}) DO NOTHING", key)
+                } else {
+                    format!(
+                        " ON CONFLICT ({}) DO UPDATE SET {}",
+                        key,
+                        updates.join(", ")
+                    )
+                }
+            }
+        };
+        let batch_size = RESTORE_BATCH
+            .min(65_535 / schema.columns.len().max(1))
+            .max(1);
+        let mut lines = reader.lines();
+        let mut line_number = 0;
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut restored = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = match serde_json::from_str(&line) {
+                Ok(serde_json::Value::Object(row)) => row,
+                Ok(_) => {
+                    return Err(sqlx::Error::Decode(
+                        format!("line {}: expected a JSON object", line_number).into(),
+                    ));
+                }
+                Err(e) => {
+                    return Err(sqlx::Error::Decode(
+                        format!("line {}: {}", line_number, e).into(),
+                    ));
+                }
+            };
+            if let Some(unknown) = row.keys().find(|k| schema.column(k).is_none()) {
+                return Err(sqlx::Error::InvalidArgument(format!(
+                    "line {}: `{}` has no column `{}`",
+                    line_number, schema.name, unknown
+                )));
+            }
+            batch.push(row);
+            if batch.len() == batch_size {
+                restored += self.restore_rows(schema, &batch, &suffix).await?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            restored += self.restore_rows(schema, &batch, &suffix).await?;
+        }
+        for c in schema.columns.iter().filter(|c| c.serial) {
+            let sql = format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', '{column}'), max({column})) \
+                 FROM {table} HAVING max({column}) IS NOT NULL",
+                table = schema.name,
+                column = c.name
+            );
+            self.statement(&sql).execute(self.pool()).await?;
+        }
+        Ok(restored)
+    }
+
+    /// One multi-row `INSERT` of dumped `rows`; columns a row leaves out
+    /// take their `DEFAULT`, and generated columns are recomputed.
+    async fn restore_rows(
+        &self,
+        schema: &TableSchema,
+        rows: &[serde_json::Map<String, serde_json::Value>],
+        suffix: &str,
+    ) -> sqlx::Result<u64> {
+        let columns: Vec<&ColumnSchema> = schema
+            .columns
+            .iter()
+            .filter(|c| c.generated.is_none())
+            .collect();
+        let cols: Vec<&str> = columns.iter().map(|c| c.name).collect();
+        let mut values = Vec::new();
+        let tuples: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                let placeholders: Vec<String> = columns
+                    .iter()
+                    .map(|c| match row.get(c.name) {
+                        Some(v) => {
+                            values.push(restore_value(c, v.clone()));
+                            c.placeholder(values.len())
+                        }
+                        None => "DEFAULT".to_string(),
+                    })
+                    .collect();
+                format!("({})", placeholders.join(","))
+            })
+            .collect();
+        // Keeps dumped `serial` keys instead of generating new ones.
+        let overriding = if schema.columns.iter().any(|c| c.serial) {
+            " OVERRIDING SYSTEM VALUE"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "INSERT INTO {} ({}){} VALUES {}{}",
+            schema.name,
+            cols.join(","),
+            overriding,
+            tuples.join(","),
+            suffix
+        );
+        let mut query = self.statement(&sql);
+        for v in values {
+            query = bind_json(query, v);
+        }
+        Ok(self
+            .timed(schema.name, Operation::Insert, query.execute(self.pool()))
+            .await?
+            .rows_affected())
+    }
+
+    /// Prepares a value for writing into `column`: checks its declared
+    /// length, converts `geo_types` values of geometry columns to EWKT and
+    /// maps to hstore or JSON text, and encrypts it if the column is
+    /// `#[slint(encrypted)]`.
+    pub(crate) fn encode_value(
+        &self,
+        column: &ColumnSchema,
+        value: serde_json::Value,
+    ) -> sqlx::Result<serde_json::Value> {
+        column.validate(&value)?;
+        if column.hashed.is_some() {
+            return hash_value(&value);
+        }
+        #[cfg(feature = "geo")]
+        if crate::geo::is_geometry(column) && value.is_object() {
+            return crate::geo::geometry_ewkt(&value)
+                .map(serde_json::Value::String)
+                .ok_or_else(|| {
+                    sqlx::Error::InvalidArgument(format!(
+                        "column `{}` takes a point or polygon",
+                        column.name
+                    ))
+                });
+        }
+        let value = map_value(column, value);
+        if !column.encrypted {
+            return Ok(value);
+        }
+        match &self.inner.cipher {
+            Some(cipher) => cipher.encrypt(&value),
+            None => Err(sqlx::Error::Configuration(
+                format!(
+                    "column `{}` is encrypted but no encryption key is configured",
+                    column.name
+                )
+                .into(),
+            )),
+        }
+    }
+
+    /// [`encode_value`](Self::encode_value) by table and column name; values
+    /// for unknown tables or columns pass through unchanged.
+    pub(crate) fn encode_column(
+        &self,
+        table_name: &str,
+        column: &str,
+        value: serde_json::Value,
+    ) -> sqlx::Result<serde_json::Value> {
+        match self.find_schema(table_name).and_then(|s| s.column(column)) {
+            Some(c) => self.encode_value(c, value),
+            None => Ok(value),
+        }
+    }
+
+    /// Converts a row of `table_name` to JSON, decrypting encrypted columns
+    /// and redacting `#[slint(masked)]` ones.
+    pub(crate) fn row_json(
+        &self,
+        table_name: &str,
+        row: &PgRow,
+    ) -> sqlx::Result<serde_json::Value> {
+        self.row_json_with(table_name, row, false)
+    }
+
+    /// [`row_json`](Self::row_json), optionally returning masked columns raw.
+    pub(crate) fn row_json_with(
+        &self,
+        table_name: &str,
+        row: &PgRow,
+        unmasked: bool,
+    ) -> sqlx::Result<serde_json::Value> {
+        let mut value = row_to_json(row);
+        self.reveal(table_name, &mut value, unmasked)?;
+        Ok(value)
+    }
+
+    /// Decrypts the encrypted columns of a `table_name` row in place and
+    /// redacts masked ones unless `unmasked`.
+    pub(crate) fn reveal(
+        &self,
+        table_name: &str,
+        value: &mut serde_json::Value,
+        unmasked: bool,
+    ) -> sqlx::Result<()> {
+        let schema = self.find_schema(table_name);
+        if let (Some(schema), serde_json::Value::Object(map)) = (schema, value) {
+            for c in schema.columns.iter() {
+                let Some(v) = map.get_mut(c.name) else {
+                    continue;
+                };
+                if c.encrypted {
+                    let cipher = self.inner.cipher.as_ref().ok_or_else(|| {
+                        sqlx::Error::Configuration(
+                            format!(
+                                "column `{}` is encrypted but no encryption key is configured",
+                                c.name
+                            )
+                            .into(),
+                        )
+                    })?;
+                    *v = cipher.decrypt(v)?;
+                }
+                if c.masked && !unmasked {
+                    *v = mask_value(v);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserializes a row of `table_name` into `T`, decrypting encrypted
+    /// columns and redacting masked ones.
+    pub(crate) fn decode<T>(&self, table_name: &str, row: &PgRow) -> sqlx::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.decode_with(table_name, row, false)
+    }
+
+    /// [`decode`](Self::decode), optionally returning masked columns raw.
+    pub(crate) fn decode_with<T>(
+        &self,
+        table_name: &str,
+        row: &PgRow,
+        unmasked: bool,
+    ) -> sqlx::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_value::<T>(self.row_json_with(table_name, row, unmasked)?).map_err(|e| {
+            sqlx::Error::ColumnDecode {
+                index: "serde_json".into(),
+                source: Box::new(e),
+            }
+        })
+    }
+
+    /// Default select list for `table_name` (see [`TableSchema::select_list`]).
+    pub(crate) fn select_list(&self, table_name: &str) -> String {
+        self.find_schema(table_name)
+            .map_or_else(|| "*".to_string(), TableSchema::select_list)
+    }
+
+    /// ` RETURNING a, b` for the given columns of `table_name`, or its
+    /// [`select_list`](Self::select_list) when empty, so hashed columns
+    /// never come back. Columns the table doesn't have are an
+    /// `InvalidArgument` error.
+    pub(crate) fn returning_clause(
+        &self,
+        table_name: &str,
+        columns: &[&str],
+    ) -> sqlx::Result<String> {
+        if let Some(schema) = self.find_schema(table_name) {
+            schema.check_columns(columns)?;
+        }
+        if columns.is_empty() {
+            Ok(format!(" RETURNING {}", self.select_list(table_name)))
+        } else {
+            Ok(format!(" RETURNING {}", columns.join(", ")))
+        }
+    }
+
+    /// Placeholder `$index` for `column` of `table_name`, cast to the
+    /// column's declared type (see [`TableSchema::placeholder`]).
+    pub(crate) fn placeholder(&self, table_name: &str, column: &str, index: usize) -> String {
+        self.find_schema(table_name)
+            .map_or_else(|| format!("${}", index), |s| s.placeholder(column, index))
+    }
+
+    /// Sets `columns` of `map` to the actor when `table_name` is a
+    /// `#[slint(blame)]` table and the handle has an actor.
+    fn stamp_actor(
+        &self,
+        table_name: &str,
+        map: &mut serde_json::Map<String, serde_json::Value>,
+        columns: &[&str],
+    ) {
+        let blame = self.find_schema(table_name).is_some_and(|s| s.blame);
+        if let (true, Some(actor)) = (blame, self.actor()) {
+            for column in columns {
+                map.insert(column.to_string(), actor.into());
+            }
+        }
+    }
+
+    /// Fills `updated_by` in a set of changes (see [`with_actor`](Self::with_actor)).
+    pub(crate) fn stamp_update(
+        &self,
+        table_name: &str,
+        changes: &mut serde_json::Map<String, serde_json::Value>,
+    ) {
+        self.stamp_actor(table_name, changes, &["updated_by"]);
+    }
+
+    pub(crate) fn schema_for(&self, table_name: &str) -> &TableSchema {
+        self.find_schema(table_name)
+            .expect("Table schema not found")
+    }
+
+    /// The registered schema of `table_name`, given with or without the
+    /// table prefix (see [`OrmOptions::table_prefix`]).
+    pub(crate) fn find_schema(&self, table_name: &str) -> Option<&TableSchema> {
+        let prefixed = self.table_name(table_name);
+        self.schemas().iter().find(|s| s.name == prefixed)
+    }
+
+    /// `name` with the configured table prefix, for tables the ORM
+    /// manages. The name of a registered table as the ORM uses it, i.e.
+    /// already prefixed, is returned unchanged. Use it to refer to ORM
+    /// tables from raw SQL.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # #[slint(table_name = "users")]
+    /// # #[derive(Serialize, Deserialize)]
+    /// # struct User {
+    /// #     #[slint(primary_key)]
+    /// #     id: String,
+    /// # }
+    /// # let (url, schemas) = ("postgres://localhost/app".to_string(), vec![User::slint_schema()]);
+    /// let orm = OrmStruct::new(url, schemas).with_options(OrmOptions::new().table_prefix("app1_"));
+    /// let sql = format!("SELECT count(*) FROM {}", orm.table_name("users"));
+    /// assert_eq!(sql, "SELECT count(*) FROM app1_users");
+    /// ```
+    pub fn table_name(&self, name: &str) -> String {
+        if self.schemas().iter().any(|s| s.name == name) {
+            return name.to_string();
+        }
+        format!("{}{}", self.inner.options.table_prefix, name)
+    }
+
+    /// The id strategy registered for `table_name`, matched with or without
+    /// the table prefix.
+    fn id_strategy(&self, table_name: &str) -> Option<&Arc<dyn IdStrategy>> {
+        self.inner
+            .id_strategies
+            .iter()
+            .find(|(table, _)| self.table_name(table) == table_name)
+            .map(|(_, strategy)| strategy)
+    }
+
+    /// Bind values for one row, one per insert column. Keys of `uuid`
+    /// columns the item leaves unset are generated (see
+    /// [`with_id_strategy`](Self::with_id_strategy)); other columns it
+    /// leaves out are `None` and take the column's `DEFAULT`.
+    fn row_values<T>(
+        &self,
+        schema: &TableSchema,
+        item: &T,
+    ) -> sqlx::Result<Vec<Option<serde_json::Value>>>
+    where
+        T: Serialize,
+    {
+        let mut map = serde_json::to_value(item).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        if let serde_json::Value::Object(map) = &mut map {
+            self.stamp_actor(schema.name, map, &["created_by", "updated_by"]);
+        }
+        schema
+            .insert_columns()
+            .map(|c| {
+                let value = match map.get(c.name) {
+                    Some(v) => v.clone(),
+                    None if c.uuid => match self.id_strategy(schema.name) {
+                        Some(strategy) => strategy.generate(),
+                        None => UuidV4.generate(),
+                    },
+                    None => return Ok(None),
+                };
+                self.encode_value(c, value).map(Some)
+            })
+            .collect()
     }
 
-    fn placeholders(count: usize) -> Vec<String> {
-        (1..=count).map(|i| format!("${}", i)).collect()
+    /// Multi-row `INSERT ... VALUES (...), (...) <suffix>`, split into
+    /// statements that stay under Postgres' bind-parameter limit.
+    /// Returns the number of rows actually inserted.
+    pub(crate) async fn insert_rows<T>(
+        &self,
+        table_name: &str,
+        items: &[T],
+        suffix: &str,
+    ) -> sqlx::Result<u64>
+    where
+        T: Serialize,
+    {
+        const MAX_BIND_PARAMS: usize = 65_535;
+
+        let schema = self.schema_for(table_name);
+        let col_count = schema.insert_columns().count();
+        if items.is_empty() || col_count == 0 {
+            return Ok(0);
+        }
+
+        let returning = self.event_returning(table_name);
+        let suffix = format!("{}{}", suffix, returning.as_deref().unwrap_or(""));
+        let rows_per_statement = (MAX_BIND_PARAMS / col_count).max(1);
+        let mut inserted = 0;
+        for chunk in items.chunks(rows_per_statement) {
+            let (sql, values) = self.insert_statement(schema, chunk, &suffix, 0)?;
+            let mut query = self.statement(&sql);
+            for v in values {
+                query = bind_json(query, v);
+            }
+            if returning.is_some() {
+                let rows = self
+                    .timed(table_name, Operation::Insert, query.fetch_all(self.pool()))
+                    .await?;
+                let rows = rows
+                    .iter()
+                    .map(|r| self.row_json(table_name, r))
+                    .collect::<sqlx::Result<Vec<_>>>()?;
+                self.emit_rows(Change::Insert, table_name, &rows);
+                inserted += rows.len() as u64;
+            } else {
+                inserted += self
+                    .timed(table_name, Operation::Insert, query.execute(self.pool()))
+                    .await?
+                    .rows_affected();
+            }
+        }
+        Ok(inserted)
     }
 
-    // -------- Create tables --------
-    pub async fn migrate(&self) -> sqlx::Result<()> {
-        for schema in &self.schemas {
-            let mut sql = format!("CREATE TABLE IF NOT EXISTS {} (", schema.name);
-            let cols: Vec<String> = schema
-                .columns
-                .iter()
-                .map(|c| {
-                    let mut col_def = format!("{} {}", c.name, c.sql_type);
-                    if c.primary {
-                        col_def.push_str(" PRIMARY KEY")
-                    }
-                    if c.unique {
-                        col_def.push_str(" UNIQUE")
-                    }
-                    if c.not_null {
-                        col_def.push_str(" NOT NULL")
+    /// One `INSERT ... VALUES (...), (...) <suffix>` statement for `items`
+    /// and its bind values, numbered from `offset + 1`. Serial columns are
+    /// left to the database.
+    pub(crate) fn insert_statement<T>(
+        &self,
+        schema: &TableSchema,
+        items: &[T],
+        suffix: &str,
+        offset: usize,
+    ) -> sqlx::Result<(String, Vec<serde_json::Value>)>
+    where
+        T: Serialize,
+    {
+        let cols: Vec<&str> = schema.insert_columns().map(|c| c.name).collect();
+        let mut tuples = Vec::with_capacity(items.len());
+        let mut values = Vec::with_capacity(items.len() * cols.len());
+        for item in items {
+            let row = self.row_values(schema, item)?;
+            let placeholders: Vec<String> = schema
+                .insert_columns()
+                .zip(row)
+                .map(|(c, v)| match v {
+                    Some(v) => {
+                        values.push(v);
+                        c.placeholder(offset + values.len())
                     }
-                    // if let Some(fk) = &c.foreign_key {
-                    //     col_def.push_str(&format!(" REFERENCES {}", fk));
-                    // }
-                    col_def
+                    None => "DEFAULT".to_string(),
                 })
                 .collect();
-            sql.push_str(&cols.join(", "));
-            sql.push(')');
-            query(&sql).execute(self.pool()).await?;
+            tuples.push(format!("({})", placeholders.join(",")));
         }
-        Ok(())
+
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES {}{}",
+            schema.name,
+            cols.join(","),
+            tuples.join(","),
+            suffix
+        );
+        Ok((sql, values))
     }
 
     // -------- Insert a record --------
@@ -81,46 +1612,153 @@ impl OrmStruct {
     where
         T: Serialize,
     {
-        let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
-            .expect("Table schema not found");
+        self.insert_rows(table_name, std::slice::from_ref(item), "")
+            .await?;
+        Ok(())
+    }
 
-        let map = serde_json::to_value(item).unwrap();
-        let mut cols = Vec::new();
-        let mut values: Vec<serde_json::Value> = Vec::new();
+    // -------- Insert, returning the generated key --------
+    /// Inserts `item` and returns the integer the database assigned to the
+    /// table's `#[slint(serial)]` column.
+    pub async fn insert_returning<T>(&self, table_name: &str, item: &T) -> sqlx::Result<i64>
+    where
+        T: Serialize,
+    {
+        let schema = self.schema_for(table_name);
+        let serial = schema.columns.iter().find(|c| c.serial).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("table `{}` has no serial column", table_name))
+        })?;
 
-        for c in schema.columns.iter() {
-            let val = if c.uuid && map.get(&c.name).is_none() {
-                serde_json::Value::String(Uuid::new_v4().to_string())
-            } else {
-                map.get(&c.name).cloned().unwrap_or(serde_json::Value::Null)
-            };
-            cols.push(c.name);
-            values.push(val);
+        // The key comes last, after the event columns if there are any.
+        let suffix = match self.event_returning(table_name) {
+            Some(returning) => format!("{}, {}::BIGINT AS {}", returning, serial.name, serial.name),
+            None => format!(" RETURNING {}::BIGINT", serial.name),
+        };
+        let (sql, values) =
+            self.insert_statement(schema, std::slice::from_ref(item), &suffix, 0)?;
+        let mut query = self.statement(&sql);
+        for v in values {
+            query = bind_json(query, v);
+        }
+        let row = self
+            .timed(table_name, Operation::Insert, query.fetch_one(self.pool()))
+            .await?;
+        if row.len() > 1 {
+            self.emit_rows(
+                Change::Insert,
+                table_name,
+                &[self.row_json(table_name, &row)?],
+            );
         }
+        row.try_get::<i64, _>(row.len() - 1)
+    }
 
-        let placeholders = Self::placeholders(cols.len());
-        let sql = format!(
-            "INSERT INTO {} ({}) VALUES ({})",
-            schema.name,
-            cols.join(","),
-            placeholders.join(",")
-        );
+    // -------- Insert, skipping conflicting rows --------
+    /// Inserts `item` with `ON CONFLICT DO NOTHING`; returns whether a row
+    /// was actually written.
+    pub async fn insert_ignore<T>(&self, table_name: &str, item: &T) -> sqlx::Result<bool>
+    where
+        T: Serialize,
+    {
+        let inserted = self
+            .insert_rows(
+                table_name,
+                std::slice::from_ref(item),
+                " ON CONFLICT DO NOTHING",
+            )
+            .await?;
+        Ok(inserted > 0)
+    }
 
-        let mut query = query(&sql);
-        for v in values {
-            query = match v {
-                serde_json::Value::String(s) => query.bind(s),
-                serde_json::Value::Number(n) => query.bind(n.to_string()),
-                serde_json::Value::Bool(b) => query.bind(b),
-                _ => query.bind(None::<String>),
+    /// Batch form of [`insert_ignore`](Self::insert_ignore); returns how many
+    /// of `items` were inserted.
+    pub async fn insert_many_ignore<T>(&self, table_name: &str, items: &[T]) -> sqlx::Result<u64>
+    where
+        T: Serialize,
+    {
+        self.insert_rows(table_name, items, " ON CONFLICT DO NOTHING")
+            .await
+    }
+
+    // -------- Batch upsert --------
+    /// Inserts `items` in batched multi-row statements; rows that conflict on
+    /// `conflict_columns` have the columns the new row supplies overwritten
+    /// (`DO UPDATE SET col = EXCLUDED.col`). Keys, `uuid` columns and the
+    /// `created_at` / `created_by` stamps keep their stored value, and so do
+    /// columns the item leaves out; items supplying different columns are
+    /// written by separate statements. Returns rows inserted or updated.
+    ///
+    /// Postgres rejects a statement that touches the same row twice, so
+    /// `items` must not contain duplicate conflict keys.
+    pub async fn upsert_many<T>(
+        &self,
+        table_name: &str,
+        items: &[T],
+        conflict_columns: &[&str],
+    ) -> sqlx::Result<u64>
+    where
+        T: Serialize,
+    {
+        if conflict_columns.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "upsert requires at least one conflict column".into(),
+            ));
+        }
+
+        let schema = self.find_schema(table_name).ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!("table `{}` is not registered", table_name))
+        })?;
+        let mut groups: Vec<(Vec<&str>, Vec<&T>)> = Vec::new();
+        for item in items {
+            let supplied = self.supplied_columns(schema, item)?;
+            match groups.iter_mut().find(|(columns, _)| *columns == supplied) {
+                Some((_, group)) => group.push(item),
+                None => groups.push((supplied, vec![item])),
+            }
+        }
+
+        let key = schema.key_column();
+        let mut written = 0;
+        for (supplied, group) in groups {
+            let updates: Vec<String> = schema
+                .insert_columns()
+                .filter(|c| supplied.contains(&c.name) && !conflict_columns.contains(&c.name))
+                .filter(|c| !c.primary && !c.uuid && Some(c.name) != key)
+                .filter(|c| !matches!(c.name, "created_at" | "created_by"))
+                .map(|c| format!("{} = EXCLUDED.{}", c.name, c.name))
+                .collect();
+            let suffix = if updates.is_empty() {
+                format!(" ON CONFLICT ({}) DO NOTHING", conflict_columns.join(", "))
+            } else {
+                format!(
+                    " ON CONFLICT ({}) DO UPDATE SET {}",
+                    conflict_columns.join(", "),
+                    updates.join(", ")
+                )
             };
+            written += self.insert_rows(table_name, &group, &suffix).await?;
         }
+        Ok(written)
+    }
 
-        query.execute(self.pool()).await?;
-        Ok(())
+    /// Insert columns `item` has a value for, `created_by` and `updated_by`
+    /// included when they are stamped.
+    fn supplied_columns<T>(&self, schema: &TableSchema, item: &T) -> sqlx::Result<Vec<&'static str>>
+    where
+        T: Serialize,
+    {
+        let map = match serde_json::to_value(item).map_err(|e| sqlx::Error::Encode(Box::new(e)))? {
+            serde_json::Value::Object(mut map) => {
+                self.stamp_actor(schema.name, &mut map, &["created_by", "updated_by"]);
+                map
+            }
+            _ => serde_json::Map::new(),
+        };
+        Ok(schema
+            .insert_columns()
+            .filter(|c| map.contains_key(c.name))
+            .map(|c| c.name)
+            .collect())
     }
 
     // -------- Get first record by column --------
@@ -134,52 +1772,28 @@ impl OrmStruct {
         T: DeserializeOwned,
     {
         let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
+            .find_schema(table_name)
             .expect("Table schema not found");
 
         let sql = format!(
-            "SELECT * FROM {} WHERE {} = $1 LIMIT 1",
-            schema.name, column
+            "SELECT {} FROM {} WHERE {} = {} LIMIT 1",
+            schema.select_list(),
+            schema.name,
+            column,
+            schema.placeholder(column, 1)
         );
 
-        let row = sqlx::query(&sql)
-            .bind(value)
-            .fetch_optional(self.pool())
+        let query = self.statement(&sql).bind(value);
+        let row = self
+            .timed(
+                table_name,
+                Operation::Select,
+                query.fetch_optional(self.pool()),
+            )
             .await?;
 
         if let Some(r) = row {
-            let mut map = serde_json::Map::new();
-
-            for col in r.columns() {
-                let col_name = col.name();
-                let value = match r.try_get::<Option<i64>, _>(col_name) {
-                    Ok(Some(v)) => Value::from(v),
-                    Ok(None) => Value::Null,
-                    Err(_) => match r.try_get::<Option<f64>, _>(col_name) {
-                        Ok(Some(v)) => Value::from(v),
-                        Ok(None) => Value::Null,
-                        Err(_) => match r.try_get::<Option<bool>, _>(col_name) {
-                            Ok(Some(v)) => Value::from(v),
-                            Ok(None) => Value::Null,
-                            Err(_) => match r.try_get::<Option<String>, _>(col_name) {
-                                Ok(Some(v)) => Value::from(v),
-                                Ok(None) => Value::Null,
-                                Err(_) => Value::Null, // fallback
-                            },
-                        },
-                    },
-                };
-                map.insert(col_name.to_string(), value);
-            }
-
-            let obj = serde_json::from_value::<T>(Value::Object(map)).map_err(|e| {
-                sqlx::Error::ColumnDecode {
-                    index: "serde_json".into(),
-                    source: Box::new(e),
-                }
-            })?;
+            let obj = self.decode::<T>(table_name, &r)?;
 
             Ok(Some(obj))
         } else {
@@ -187,6 +1801,80 @@ impl OrmStruct {
         }
     }
 
+    // -------- Get first record matching every column in a filter --------
+    /// Returns the first row whose columns equal every entry of `filter`
+    /// (conditions are combined with `AND`). Every key must be a column of
+    /// the table.
+    pub async fn first_by<T>(
+        &self,
+        table_name: &str,
+        filter: &serde_json::Map<String, serde_json::Value>,
+    ) -> sqlx::Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        if filter.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "filter must contain at least one column".into(),
+            ));
+        }
+
+        let schema = self
+            .find_schema(table_name)
+            .expect("Table schema not found");
+        schema.check_columns(filter.keys())?;
+
+        let conds: Vec<String> = filter
+            .keys()
+            .enumerate()
+            .map(|(i, column)| format!("{} = {}", column, schema.placeholder(column, i + 1)))
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} LIMIT 1",
+            schema.select_list(),
+            schema.name,
+            conds.join(" AND ")
+        );
+
+        let mut query = self.statement(&sql);
+        for value in filter.values() {
+            query = bind_json(query, value.clone());
+        }
+
+        let row = self
+            .timed(
+                table_name,
+                Operation::Select,
+                query.fetch_optional(self.pool()),
+            )
+            .await?;
+        row.map(|r| self.decode::<T>(table_name, &r)).transpose()
+    }
+
+    // -------- Get first record matching typed conditions --------
+    /// Returns the first row matching every `(column, op, value)` condition,
+    /// with values bound as their own types.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let user: Option<User> = orm
+    ///     .first_where("users", &[("age", ">=", 18.into()), ("active", "=", true.into())])
+    ///     .await?;
+    /// ```
+    #[cfg(feature = "query-builder")]
+    pub async fn first_where<T>(
+        &self,
+        table_name: &str,
+        conditions: &[(&str, &str, SqlValue)],
+    ) -> sqlx::Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.conditions_query(table_name, conditions)?
+            .fetch_optional()
+            .await
+    }
+
     // -------- Fetch multiple records --------
 
     pub async fn find<T>(
@@ -195,105 +1883,123 @@ impl OrmStruct {
         column: &str,
         filter: &str,
     ) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.find_with(table_name, column, filter, &FetchOptions::default())
+            .await
+    }
+
+    /// [`find`](Self::find), ordered and paged by `options`.
+    pub async fn find_with<T>(
+        &self,
+        table_name: &str,
+        column: &str,
+        filter: &str,
+        options: &FetchOptions,
+    ) -> sqlx::Result<Vec<T>>
     where
         T: DeserializeOwned,
     {
         let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
+            .find_schema(table_name)
             .expect("Table schema not found");
 
-        let sql = format!("SELECT * FROM {} WHERE {} = $1", schema.name, column);
-        let rows = sqlx::query(&sql)
-            .bind(filter)
-            .fetch_all(self.pool())
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} = {}{}",
+            schema.select_list(),
+            schema.name,
+            column,
+            schema.placeholder(column, 1),
+            options.sql()
+        );
+        let query = self.statement(&sql).bind(filter);
+        let rows = self
+            .timed(table_name, Operation::Select, query.fetch_all(self.pool()))
             .await?;
 
         let mut result = Vec::with_capacity(rows.len());
 
         for row in rows {
-            let mut map = serde_json::Map::new();
-            for col in row.columns() {
-                let col_name = col.name();
-                let value = match row.try_get::<Option<i64>, _>(col_name) {
-                    Ok(Some(v)) => Value::from(v),
-                    Ok(None) => Value::Null,
-                    Err(_) => match row.try_get::<Option<f64>, _>(col_name) {
-                        Ok(Some(v)) => Value::from(v),
-                        Ok(None) => Value::Null,
-                        Err(_) => match row.try_get::<Option<bool>, _>(col_name) {
-                            Ok(Some(v)) => Value::from(v),
-                            Ok(None) => Value::Null,
-                            Err(_) => match row.try_get::<Option<String>, _>(col_name) {
-                                Ok(Some(v)) => Value::from(v),
-                                Ok(None) => Value::Null,
-                                Err(_) => Value::Null,
-                            },
-                        },
-                    },
-                };
-                map.insert(col_name.to_string(), value);
-            }
-
-            let obj = serde_json::from_value::<T>(Value::Object(map)).map_err(|e| {
-                sqlx::Error::ColumnDecode {
-                    index: "serde_json".into(),
-                    source: Box::new(e),
-                }
-            })?;
+            let obj = self.decode::<T>(table_name, &row)?;
             result.push(obj);
         }
 
         Ok(result)
     }
 
+    // -------- Fetch records matching typed conditions --------
+    /// Returns every row matching all `(column, op, value)` conditions; the
+    /// multi-condition form of [`find`](Self::find).
+    #[cfg(feature = "query-builder")]
+    pub async fn find_where<T>(
+        &self,
+        table_name: &str,
+        conditions: &[(&str, &str, SqlValue)],
+    ) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.conditions_query(table_name, conditions)?
+            .fetch_all()
+            .await
+    }
+
+    #[cfg(feature = "query-builder")]
+    fn conditions_query(
+        &self,
+        table_name: &str,
+        conditions: &[(&str, &str, SqlValue)],
+    ) -> sqlx::Result<QueryBuilder<'_>> {
+        if conditions.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "conditions must contain at least one column".into(),
+            ));
+        }
+        Ok(conditions
+            .iter()
+            .fold(self.query(table_name), |query, (column, op, value)| {
+                query.r#where(column, *op, value.clone())
+            }))
+    }
+
     // -------- Get all records --------
     pub async fn get_all<T>(&self, table_name: &str) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.get_all_with(table_name, &FetchOptions::default())
+            .await
+    }
+
+    /// [`get_all`](Self::get_all), ordered and paged by `options`.
+    pub async fn get_all_with<T>(
+        &self,
+        table_name: &str,
+        options: &FetchOptions,
+    ) -> sqlx::Result<Vec<T>>
     where
         T: DeserializeOwned,
     {
         let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
+            .find_schema(table_name)
             .expect("Table schema not found");
 
-        let sql = format!("SELECT * FROM {}", schema.name);
-        let rows = sqlx::query(&sql).fetch_all(self.pool()).await?;
+        let sql = format!(
+            "SELECT {} FROM {}{}",
+            schema.select_list(),
+            schema.name,
+            options.sql()
+        );
+        let query = self.statement(&sql);
+        let rows = self
+            .timed(table_name, Operation::Select, query.fetch_all(self.pool()))
+            .await?;
 
         let mut results = Vec::with_capacity(rows.len());
 
         for r in rows {
-            let mut map = serde_json::Map::new();
-            for col in r.columns() {
-                let col_name = col.name();
-                let value = match r.try_get::<Option<i64>, _>(col_name) {
-                    Ok(Some(v)) => Value::from(v),
-                    Ok(None) => Value::Null,
-                    Err(_) => match r.try_get::<Option<f64>, _>(col_name) {
-                        Ok(Some(v)) => Value::from(v),
-                        Ok(None) => Value::Null,
-                        Err(_) => match r.try_get::<Option<bool>, _>(col_name) {
-                            Ok(Some(v)) => Value::from(v),
-                            Ok(None) => Value::Null,
-                            Err(_) => match r.try_get::<Option<String>, _>(col_name) {
-                                Ok(Some(v)) => Value::from(v),
-                                Ok(None) => Value::Null,
-                                Err(_) => Value::Null,
-                            },
-                        },
-                    },
-                };
-                map.insert(col_name.to_string(), value);
-            }
-
-            let obj = serde_json::from_value::<T>(Value::Object(map)).map_err(|e| {
-                sqlx::Error::ColumnDecode {
-                    index: "serde_json".into(),
-                    source: Box::new(e),
-                }
-            })?;
+            let obj = self.decode::<T>(table_name, &r)?;
             results.push(obj);
         }
 
@@ -311,83 +2017,429 @@ impl OrmStruct {
     where
         T: Serialize,
     {
-        let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
-            .expect("Table schema not found");
+        let schema = self.schema_for(table_name);
+        let (mut sql, bind_values) = self.update_statement(schema, column, value, item, 0)?;
+        let returning = self.event_returning(table_name);
+        if let Some(returning) = &returning {
+            sql.push_str(returning);
+        }
+
+        let mut query = self.statement(&sql);
+        for v in bind_values {
+            query = bind_json(query, v);
+        }
+
+        if returning.is_some() {
+            let rows = self
+                .timed(table_name, Operation::Update, query.fetch_all(self.pool()))
+                .await?;
+            let rows = rows
+                .iter()
+                .map(|r| self.row_json(table_name, r))
+                .collect::<sqlx::Result<Vec<_>>>()?;
+            self.emit_rows(Change::Update, table_name, &rows);
+        } else {
+            self.timed(table_name, Operation::Update, query.execute(self.pool()))
+                .await?;
+        }
+        Ok(())
+    }
 
-        let map = serde_json::to_value(item).unwrap();
+    /// `UPDATE ... SET <item's columns> WHERE column = value` and its bind
+    /// values, numbered from `offset + 1`.
+    pub(crate) fn update_statement<T>(
+        &self,
+        schema: &TableSchema,
+        column: &str,
+        value: &str,
+        item: &T,
+        offset: usize,
+    ) -> sqlx::Result<(String, Vec<serde_json::Value>)>
+    where
+        T: Serialize,
+    {
+        let mut map = serde_json::to_value(item).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        if let serde_json::Value::Object(map) = &mut map {
+            self.stamp_update(schema.name, map);
+        }
         let mut sets = Vec::new();
         let mut bind_values = Vec::new();
 
-        for c in schema.columns.iter() {
-            if let Some(v) = map.get(&c.name) {
-                sets.push(format!("{} = ${}", c.name, bind_values.len() + 1));
-                bind_values.push(v.clone());
+        for c in schema.columns.iter().filter(|c| c.generated.is_none()) {
+            if let Some(v) = map.get(c.name) {
+                // Fetched records never carry the hash, so don't clear it.
+                if c.hashed.is_some() && v.is_null() {
+                    continue;
+                }
+                sets.push(format!(
+                    "{} = {}",
+                    c.name,
+                    c.placeholder(offset + bind_values.len() + 1)
+                ));
+                bind_values.push(self.encode_value(c, v.clone())?);
             }
         }
 
         let sql = format!(
-            "UPDATE {} SET {} WHERE {} = ${}",
+            "UPDATE {} SET {} WHERE {} = {}",
             schema.name,
             sets.join(", "),
             column,
-            bind_values.len() + 1
+            schema.placeholder(column, offset + bind_values.len() + 1)
         );
+        bind_values.push(serde_json::Value::String(value.to_string()));
+        Ok((sql, bind_values))
+    }
 
-        let mut query = query(&sql);
-        for v in bind_values {
-            query = match v {
-                serde_json::Value::String(s) => query.bind(s),
-                serde_json::Value::Number(n) => query.bind(n.to_string()),
-                serde_json::Value::Bool(b) => query.bind(b),
-                _ => query.bind(None::<String>),
-            };
+    // -------- Update some columns of a record --------
+    /// Sets only the columns present in `changes` (a JSON object) on the row
+    /// whose `key_column` equals `key`, leaving every other column as it is.
+    /// Returns the number of rows updated.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orm.update_partial("users", "id", &user_id, json!({ "email": new_email }))
+    ///     .await?;
+    /// ```
+    pub async fn update_partial(
+        &self,
+        table_name: &str,
+        key_column: &str,
+        key: &str,
+        changes: serde_json::Value,
+    ) -> sqlx::Result<u64> {
+        let serde_json::Value::Object(mut changes) = changes else {
+            return Err(sqlx::Error::InvalidArgument(
+                "update_partial expects a JSON object of column values".into(),
+            ));
+        };
+        if changes.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "changes must contain at least one column".into(),
+            ));
+        }
+        let schema = self
+            .find_schema(table_name)
+            .expect("Table schema not found");
+        schema.check_columns(changes.keys())?;
+
+        self.stamp_update(table_name, &mut changes);
+        let sets: Vec<String> = changes
+            .keys()
+            .enumerate()
+            .map(|(i, column)| format!("{} = {}", column, schema.placeholder(column, i + 1)))
+            .collect();
+        let mut sql = format!(
+            "UPDATE {} SET {} WHERE {} = {}",
+            schema.name,
+            sets.join(", "),
+            key_column,
+            schema.placeholder(key_column, changes.len() + 1)
+        );
+        let returning = self.event_returning(table_name);
+        if let Some(returning) = &returning {
+            sql.push_str(returning);
         }
-        query = query.bind(value);
 
-        query.execute(self.pool()).await?;
-        Ok(())
+        let mut query = self.statement(&sql);
+        for (column, v) in changes {
+            query = bind_json(query, self.encode_column(table_name, &column, v)?);
+        }
+        query = query.bind(key);
+        self.execute_reporting(table_name, query, returning.is_some(), Change::Update)
+            .await
+    }
+
+    // -------- Update matching records, returning columns --------
+    /// Applies `changes` to every row matching all columns of `filter` and
+    /// returns the requested columns of each updated row (all but hashed
+    /// columns when `returning` is empty). `filter`, `changes` and
+    /// `returning` must name columns of the table, and `filter` at least
+    /// one.
+    pub async fn update_by_returning(
+        &self,
+        table_name: &str,
+        filter: &serde_json::Map<String, serde_json::Value>,
+        changes: &serde_json::Map<String, serde_json::Value>,
+        returning: &[&str],
+    ) -> sqlx::Result<Vec<serde_json::Value>> {
+        if changes.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "changes must contain at least one column".into(),
+            ));
+        }
+        // An empty filter would update every row of the table.
+        if filter.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "filter must contain at least one column".into(),
+            ));
+        }
+
+        let schema = self
+            .find_schema(table_name)
+            .expect("Table schema not found");
+        schema.check_columns(changes.keys().chain(filter.keys()))?;
+        let returning_sql = self.returning_clause(table_name, returning)?;
+
+        let mut changes = changes.clone();
+        self.stamp_update(table_name, &mut changes);
+        let sets: Vec<String> = changes
+            .keys()
+            .enumerate()
+            .map(|(i, column)| format!("{} = {}", column, schema.placeholder(column, i + 1)))
+            .collect();
+        let conds: Vec<String> = filter
+            .keys()
+            .enumerate()
+            .map(|(i, column)| {
+                let placeholder = schema.placeholder(column, changes.len() + i + 1);
+                format!("{} = {}", column, placeholder)
+            })
+            .collect();
+        let mut sql = format!(
+            "UPDATE {} SET {} WHERE {}",
+            schema.name,
+            sets.join(", "),
+            conds.join(" AND ")
+        );
+        // Subscribers get whole rows; the caller's columns are picked out after.
+        let event_returning = self.event_returning(table_name);
+        sql.push_str(event_returning.as_deref().unwrap_or(&returning_sql));
+
+        let mut query = self.statement(&sql);
+        for (column, v) in &changes {
+            query = bind_json(query, self.encode_column(table_name, column, v.clone())?);
+        }
+        for v in filter.values() {
+            query = bind_json(query, v.clone());
+        }
+
+        let rows = self
+            .timed(table_name, Operation::Update, query.fetch_all(self.pool()))
+            .await?;
+        let rows = rows
+            .iter()
+            .map(|r| self.row_json(table_name, r))
+            .collect::<sqlx::Result<Vec<_>>>()?;
+        if event_returning.is_none() {
+            return Ok(rows);
+        }
+        self.emit_rows(Change::Update, table_name, &rows);
+        Ok(project_rows(rows, returning))
     }
 
     // -------- Delete record --------
     pub async fn delete(&self, table_name: &str, column: &str, value: &str) -> sqlx::Result<()> {
         let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
+            .find_schema(table_name)
             .expect("Table schema not found");
 
-        let sql = format!("DELETE FROM {} WHERE {} = $1", schema.name, column);
-        query(&sql).bind(value).execute(self.pool()).await?;
+        let mut sql = format!(
+            "DELETE FROM {} WHERE {} = {}",
+            schema.name,
+            column,
+            schema.placeholder(column, 1)
+        );
+        let Some(returning) = self.event_returning(table_name) else {
+            let query = self.statement(&sql).bind(value);
+            self.timed(table_name, Operation::Delete, query.execute(self.pool()))
+                .await?;
+            return Ok(());
+        };
+        sql.push_str(&returning);
+        let query = self.statement(&sql).bind(value);
+        let rows = self
+            .timed(table_name, Operation::Delete, query.fetch_all(self.pool()))
+            .await?;
+        let rows = rows
+            .iter()
+            .map(|r| self.row_json(table_name, r))
+            .collect::<sqlx::Result<Vec<_>>>()?;
+        self.emit_rows(Change::Delete, table_name, &rows);
         Ok(())
     }
 
     // -------- Check if record exists --------
     pub async fn exists(&self, table_name: &str, column: &str, value: &str) -> sqlx::Result<bool> {
         let schema = self
-            .schemas
-            .iter()
-            .find(|s| s.name == table_name)
+            .find_schema(table_name)
             .expect("Table schema not found");
 
         let sql = format!(
-            "SELECT EXISTS(SELECT 1 FROM {} WHERE {} = $1)",
-            schema.name, column
+            "SELECT EXISTS(SELECT 1 FROM {} WHERE {} = {})",
+            schema.name,
+            column,
+            schema.placeholder(column, 1)
         );
 
-        let row: (bool,) = query_as(&sql).bind(value).fetch_one(self.pool()).await?;
-        Ok(row.0)
+        let query = self.statement(&sql).bind(value);
+        self.timed(table_name, Operation::Select, query.fetch_one(self.pool()))
+            .await?
+            .try_get(0)
     }
 
     // -------- Execute raw SQL --------
     pub async fn raw(&self, sql: &str) -> sqlx::Result<sqlx::postgres::PgQueryResult> {
-        query(sql).execute(self.pool()).await
+        self.statement(sql).execute(self.pool()).await
+    }
+
+    /// Runs an arbitrary select with positional `binds` and returns each row
+    /// as a JSON object keyed by column name.
+    ///
+    /// ```ignore
+    /// let rows = orm
+    ///     .raw_fetch_json("SELECT id, name FROM users WHERE age > $1", &[SqlValue::from(18)])
+    ///     .await?;
+    /// ```
+    pub async fn raw_fetch_json(
+        &self,
+        sql: &str,
+        binds: &[SqlValue],
+    ) -> sqlx::Result<Vec<serde_json::Value>> {
+        let mut query = self.statement(sql);
+        for v in binds {
+            query = v.clone().bind(query);
+        }
+        let rows = query.fetch_all(self.pool()).await?;
+        Ok(rows.iter().map(row_to_json).collect())
+    }
+
+    // -------- Date series --------
+    /// The timestamps from `from` to `to` inclusive, `interval` apart
+    /// (e.g. `"1 day"` or `"15 minutes"`), from Postgres'
+    /// `generate_series`. To bucket a query's rows by them, including
+    /// empty buckets, see `Query::get_series`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let days = orm.series_dates(week_start, week_start + Duration::days(6), "1 day").await?;
+    /// ```
+    pub async fn series_dates(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: &str,
+    ) -> sqlx::Result<Vec<DateTime<Utc>>> {
+        self.statement("SELECT generate_series($1::TIMESTAMPTZ, $2::TIMESTAMPTZ, $3::INTERVAL)")
+            .bind(from)
+            .bind(to)
+            .bind(interval)
+            .fetch_all(self.pool())
+            .await?
+            .iter()
+            .map(|r| r.try_get(0))
+            .collect()
+    }
+
+    // -------- Session on a single connection --------
+    /// Checks out one connection, runs `f` on it inside a transaction and
+    /// releases it. Use [`Session::set_local`] for per-request settings such
+    /// as the tenant id read by row-level security policies; they last until
+    /// the session ends. The transaction commits when `f` succeeds and rolls
+    /// back when it fails.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let invoices = orm
+    ///     .session(|s| {
+    ///         Box::pin(async move {
+    ///             s.set_local("app.tenant_id", "42").await?;
+    ///             s.fetch_json("SELECT * FROM invoices", &[]).await
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn session<F, R>(&self, f: F) -> sqlx::Result<R>
+    where
+        F: for<'s> FnOnce(&'s mut Session) -> BoxFuture<'s, sqlx::Result<R>>,
+    {
+        let tx = self.pool().begin().await?;
+        let mut session = Session::new(self.clone(), tx);
+        let result = f(&mut session).await?;
+        session.commit().await?;
+        Ok(result)
+    }
+
+    // -------- Batched writes --------
+    /// Starts a [`Batch`] of inserts, updates and deletes that are sent in
+    /// one round trip by [`Batch::execute`].
+    pub fn batch(&self) -> Batch<'_> {
+        Batch::new(self)
+    }
+
+    // -------- Explicit transactions --------
+    /// Starts a transaction with the given isolation level and access mode.
+    /// Commit it with [`Session::commit`]; dropping it rolls back.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut tx = orm
+    ///     .begin(TxOptions {
+    ///         isolation_level: Some(IsolationLevel::RepeatableRead),
+    ///         read_only: true,
+    ///     })
+    ///     .await?;
+    /// let totals = tx.fetch_json("SELECT sum(amount) FROM invoices", &[]).await?;
+    /// let open = tx.fetch_json("SELECT count(*) FROM invoices WHERE paid = false", &[]).await?;
+    /// tx.commit().await?;
+    /// ```
+    pub async fn begin(&self, options: TxOptions) -> sqlx::Result<Session> {
+        let mut tx = self.pool().begin().await?;
+        if let Some(sql) = options.to_sql() {
+            self.statement(&sql).execute(&mut *tx).await?;
+        }
+        Ok(Session::new(self.clone(), tx))
+    }
+
+    /// Runs `f` in a transaction at the policy's isolation level, starting
+    /// over when it fails with a serialization failure or deadlock
+    /// (SQLSTATE `40001` / `40P01`). `f` may run several times, so it should
+    /// have no side effects outside the transaction. The last error is
+    /// returned once the retries are used up.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orm.transaction_with_retry(RetryPolicy::default(), |tx| {
+    ///     Box::pin(async move {
+    ///         tx.execute("UPDATE accounts SET balance = balance - 10 WHERE id = $1", &[from.into()]).await?;
+    ///         tx.execute("UPDATE accounts SET balance = balance + 10 WHERE id = $1", &[to.into()]).await?;
+    ///         Ok(())
+    ///     })
+    /// })
+    /// .await?;
+    /// ```
+    pub async fn transaction_with_retry<F, R>(
+        &self,
+        policy: RetryPolicy,
+        mut f: F,
+    ) -> sqlx::Result<R>
+    where
+        F: for<'s> FnMut(&'s mut Session) -> BoxFuture<'s, sqlx::Result<R>>,
+    {
+        let options = TxOptions {
+            isolation_level: Some(policy.isolation_level),
+            read_only: false,
+        };
+        let mut retry = 0;
+        loop {
+            let attempt = async {
+                let mut tx = self.begin(options).await?;
+                let result = f(&mut tx).await?;
+                tx.commit().await?;
+                Ok(result)
+            };
+            match attempt.await {
+                Err(e) if retry < policy.max_retries && is_retryable(&e) => {
+                    tokio::time::sleep(policy.delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
     }
 
+    #[cfg(feature = "query-builder")]
     pub fn query<'a>(&'a self, table: &str) -> QueryBuilder<'a> {
-        let pool = self.pool.as_ref().expect("DB pool not initialized");
-        QueryBuilder::new(table, pool)
+        QueryBuilder::new(table, self)
     }
 }
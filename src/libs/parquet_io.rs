@@ -0,0 +1,179 @@
+use crate::TableSchema;
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use parquet::arrow::AsyncArrowWriter;
+use parquet::arrow::async_writer::AsyncFileWriter;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Rows `Query::export_parquet` converts and writes at a time.
+pub const PARQUET_BATCH: usize = 8192;
+
+/// Arrow type a column declared as `sql_type` is exported as. Numbers,
+/// booleans, timestamps and dates keep their type (`NUMERIC` becomes a
+/// float, as it does when decoded); everything else, including UUIDs, JSON
+/// and arrays, is exported as text.
+pub fn arrow_type(sql_type: &str) -> DataType {
+    // `VARCHAR(255)` and `TIMESTAMP(3) WITH TIME ZONE` without their
+    // modifiers, in one spacing and case.
+    let mut base = String::new();
+    let mut depth = 0;
+    for c in sql_type.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if depth == 0 => base.push(c.to_ascii_uppercase()),
+            _ => {}
+        }
+    }
+    let base = base.split_whitespace().collect::<Vec<_>>().join(" ");
+    match base.as_str() {
+        "BOOL" | "BOOLEAN" => DataType::Boolean,
+        "SMALLINT" | "INT2" | "SMALLSERIAL" => DataType::Int16,
+        "INT" | "INTEGER" | "INT4" | "SERIAL" => DataType::Int32,
+        "BIGINT" | "INT8" | "BIGSERIAL" => DataType::Int64,
+        "REAL" | "FLOAT4" => DataType::Float32,
+        "DOUBLE PRECISION" | "FLOAT" | "FLOAT8" | "NUMERIC" | "DECIMAL" => DataType::Float64,
+        "TIMESTAMPTZ" | "TIMESTAMP WITH TIME ZONE" => {
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        }
+        "TIMESTAMP" | "TIMESTAMP WITHOUT TIME ZONE" => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        "DATE" => DataType::Date32,
+        _ => DataType::Utf8,
+    }
+}
+
+/// Arrow schema of the rows a query on `schema`'s table returns: every
+/// column but the `hashed` ones, in declaration order. Fields are nullable,
+/// since values that can't be converted are written as nulls.
+pub fn arrow_schema(schema: &TableSchema) -> Schema {
+    Schema::new(
+        schema
+            .columns
+            .iter()
+            .filter(|c| c.hashed.is_none())
+            .map(|c| Field::new(c.name, arrow_type(c.sql_type), true))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Record batch of row objects, one array per field of `schema`.
+fn record_batch(schema: &SchemaRef, rows: &[Value]) -> sqlx::Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let values = rows
+                .iter()
+                .map(|row| row.get(field.name()).unwrap_or(&Value::Null));
+            column_array(field.data_type(), values)
+        })
+        .collect();
+    RecordBatch::try_new(schema.clone(), columns).map_err(parquet_error)
+}
+
+/// Writes `rows` as one record batch and empties it. Returns the number
+/// of rows written.
+pub(crate) async fn write_batch<W: AsyncFileWriter>(
+    writer: &mut AsyncArrowWriter<W>,
+    schema: &SchemaRef,
+    rows: &mut Vec<Value>,
+) -> sqlx::Result<u64> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+    let batch = record_batch(schema, rows)?;
+    writer.write(&batch).await.map_err(parquet_error)?;
+    let written = rows.len() as u64;
+    rows.clear();
+    Ok(written)
+}
+
+fn column_array<'v>(data_type: &DataType, values: impl Iterator<Item = &'v Value>) -> ArrayRef {
+    match data_type {
+        DataType::Boolean => Arc::new(values.map(Value::as_bool).collect::<BooleanArray>()),
+        DataType::Int16 => Arc::new(
+            values
+                .map(|v| int(v).and_then(|n| i16::try_from(n).ok()))
+                .collect::<Int16Array>(),
+        ),
+        DataType::Int32 => Arc::new(
+            values
+                .map(|v| int(v).and_then(|n| i32::try_from(n).ok()))
+                .collect::<Int32Array>(),
+        ),
+        DataType::Int64 => Arc::new(values.map(int).collect::<Int64Array>()),
+        DataType::Float32 => Arc::new(
+            values
+                .map(|v| float(v).map(|f| f as f32))
+                .collect::<Float32Array>(),
+        ),
+        DataType::Float64 => Arc::new(values.map(float).collect::<Float64Array>()),
+        DataType::Timestamp(_, zone) => {
+            let micros: TimestampMicrosecondArray = values
+                .map(|v| timestamp_micros(v, zone.is_some()))
+                .collect();
+            Arc::new(micros.with_timezone_opt(zone.clone()))
+        }
+        DataType::Date32 => Arc::new(values.map(days).collect::<Date32Array>()),
+        _ => Arc::new(values.map(text).collect::<StringArray>()),
+    }
+}
+
+/// Numbers may arrive as text, e.g. from encrypted columns.
+fn int(value: &Value) -> Option<i64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        v => v.as_i64(),
+    }
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        v => v.as_f64(),
+    }
+}
+
+/// Microseconds since the epoch of an RFC 3339 timestamp, or of a naive
+/// one read as UTC.
+fn timestamp_micros(value: &Value, zoned: bool) -> Option<i64> {
+    let s = value.as_str()?;
+    if zoned {
+        DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.timestamp_micros())
+    } else {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .map(|t| t.and_utc().timestamp_micros())
+    }
+}
+
+/// Days since the epoch of a `YYYY-MM-DD` date.
+fn days(value: &Value) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(value.as_str()?, "%Y-%m-%d").ok()?;
+    i32::try_from(
+        date.signed_duration_since(DateTime::UNIX_EPOCH.date_naive())
+            .num_days(),
+    )
+    .ok()
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+pub(crate) fn parquet_error(e: impl std::error::Error + Send + Sync + 'static) -> sqlx::Error {
+    sqlx::Error::Encode(Box::new(e))
+}
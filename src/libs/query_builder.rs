@@ -1,12 +1,16 @@
+use crate::events::Change;
+use crate::orm::{PgQuery, bind_json};
+use crate::range::element_placeholder;
+use crate::stats::Operation;
+use crate::{Op, OrmStruct, SqlValue, quote_ident};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use sqlx::Column;
 use sqlx::Row;
-use sqlx::postgres::PgPool;
+use sqlx::postgres::PgRow;
 
 pub struct QueryBuilder<'a> {
     table: String,
-    pool: &'a PgPool,
+    orm: &'a OrmStruct,
     selects: Vec<String>,
     wheres: Vec<String>,
     joins: Vec<String>,
@@ -15,14 +19,14 @@ pub struct QueryBuilder<'a> {
     limit_clause: Option<String>,
     offset_clause: Option<String>,
     order_clause: Option<String>,
-    params: Vec<String>,
+    params: Vec<SqlValue>,
 }
 
 impl<'a> QueryBuilder<'a> {
-    pub fn new(table: &str, pool: &'a PgPool) -> Self {
+    pub fn new(table: &str, orm: &'a OrmStruct) -> Self {
         Self {
-            table: table.to_string(),
-            pool,
+            table: orm.table_name(table),
+            orm,
             selects: vec!["*".to_string()],
             wheres: vec![],
             joins: vec![],
@@ -40,10 +44,197 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
-    pub fn r#where(mut self, column: &str, op: &str, value: &str) -> Self {
+    /// Adds `expression AS alias` to the select list, for computed columns:
+    /// `select_expr("count(*)", "total")`. The alias is quoted, so it keeps
+    /// its case and matches the struct field of the same name.
+    ///
+    /// The default `*` stays in the list; call [`select`](Self::select)
+    /// first to replace it.
+    pub fn select_expr(mut self, expression: &str, alias: &str) -> Self {
+        self.selects
+            .push(format!("{} AS {}", expression, quote_ident(alias)));
+        self
+    }
+
+    /// Adds a column under another name, e.g. a joined table's column that
+    /// would clash with the base table's: `select_as("u.name", "user_name")`.
+    pub fn select_as(self, column: &str, alias: &str) -> Self {
+        self.select_expr(column, alias)
+    }
+
+    /// A `column op $n` condition, ANDed with the others. The value is bound
+    /// with its own type, so `r#where("age", ">", 18)` compares integers and
+    /// `r#where("id", "=", uuid)` compares UUIDs. See [`Op`] for the
+    /// operators.
+    pub fn r#where(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        let (condition, value) = op
+            .into()
+            .render(column, self.params.len() + 1, value.into());
+        self.wheres.push(condition);
+        self.params.extend(value);
+        self
+    }
+
+    /// A `column <> $n` condition.
+    pub fn where_not(self, column: &str, value: impl Into<SqlValue>) -> Self {
+        self.r#where(column, "<>", value)
+    }
+
+    /// A `column IS DISTINCT FROM $n` condition: unlike
+    /// [`where_not`](Self::where_not) it treats NULL as a comparable value,
+    /// so rows whose column is NULL match any non-NULL `value`, and
+    /// `SqlValue::Null` matches every non-NULL row.
+    pub fn is_distinct_from(mut self, column: &str, value: impl Into<SqlValue>) -> Self {
+        let placeholder = self
+            .orm
+            .placeholder(&self.table, column, self.params.len() + 1);
+        self.wheres
+            .push(format!("{} IS DISTINCT FROM {}", column, placeholder));
+        self.params.push(value.into());
+        self
+    }
+
+    /// A `column = ANY($n)` condition matching any of `values`, bound as
+    /// one array parameter. No values match no rows.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let open: Vec<Ticket> = orm.query("tickets").where_in("status", &["new", "open"]).fetch_all().await?;
+    /// ```
+    pub fn where_in<V>(mut self, column: &str, values: &[V]) -> Self
+    where
+        V: Clone + Into<SqlValue>,
+    {
+        if values.is_empty() {
+            self.wheres.push("FALSE".to_string());
+            return self;
+        }
+        self.wheres
+            .push(format!("{} = ANY(${})", column, self.params.len() + 1));
+        self.params.push(SqlValue::List(
+            values.iter().cloned().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// A `column <> ALL($n)` condition matching none of `values`, bound as
+    /// one array parameter. No values filter nothing out; as with SQL's
+    /// `NOT IN`, rows whose column is NULL never match.
+    pub fn not_in<V>(mut self, column: &str, values: &[V]) -> Self
+    where
+        V: Clone + Into<SqlValue>,
+    {
+        if values.is_empty() {
+            return self;
+        }
         self.wheres
-            .push(format!("{} {} ${}", column, op, self.params.len() + 1));
-        self.params.push(value.to_string());
+            .push(format!("{} <> ALL(${})", column, self.params.len() + 1));
+        self.params.push(SqlValue::List(
+            values.iter().cloned().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// A `column <<= $n::INET` condition matching addresses inside the
+    /// network `cidr`, e.g. `"10.0.0.0/8"`.
+    pub fn where_ip_contained_in(mut self, column: &str, cidr: &str) -> Self {
+        self.wheres
+            .push(format!("{} <<= ${}::INET", column, self.params.len() + 1));
+        self.params.push(cidr.into());
+        self
+    }
+
+    /// A `column ? $n` condition matching rows whose `HSTORE` or `JSONB`
+    /// column has the key `key`.
+    pub fn where_has_key(mut self, column: &str, key: &str) -> Self {
+        self.wheres
+            .push(format!("{} ? ${}", column, self.params.len() + 1));
+        self.params.push(key.into());
+        self
+    }
+
+    /// A `column @> $n` condition matching rows whose range column
+    /// contains `value`, e.g. a booking period containing a timestamp.
+    pub fn where_range_contains(mut self, column: &str, value: impl Into<SqlValue>) -> Self {
+        let placeholder = element_placeholder(self.orm, &self.table, column, self.params.len() + 1);
+        self.wheres.push(format!("{} @> {}", column, placeholder));
+        self.params.push(value.into());
+        self
+    }
+
+    /// A `column && $n` condition matching rows whose range column
+    /// overlaps `range`.
+    pub fn where_range_overlaps(mut self, column: &str, range: impl Into<SqlValue>) -> Self {
+        let placeholder = self
+            .orm
+            .placeholder(&self.table, column, self.params.len() + 1);
+        self.wheres.push(format!("{} && {}", column, placeholder));
+        self.params.push(range.into());
+        self
+    }
+
+    /// An `ST_DWithin` condition matching rows whose geometry column lies
+    /// within `meters` of `point` (longitude `x`, latitude `y`).
+    ///
+    /// # Example
+    /// ```ignore
+    /// let nearby: Vec<Shop> = orm.query("shops")
+    ///     .where_within_distance("location", Point::new(13.40, 52.52), 500.0)
+    ///     .fetch_all()
+    ///     .await?;
+    /// ```
+    #[cfg(feature = "geo")]
+    pub fn where_within_distance(
+        mut self,
+        column: &str,
+        point: geo_types::Point<f64>,
+        meters: f64,
+    ) -> Self {
+        self.wheres.push(crate::geo::within_distance_sql(
+            column,
+            self.params.len() + 1,
+            meters,
+        ));
+        self.params.push(point.into());
+        self
+    }
+
+    /// ORs a condition with the one added just before it, so
+    /// `.r#where("status", "=", "open").or_where("status", "=", "new").r#where("age", ">", 18)`
+    /// filters `(status = 'open' OR status = 'new') AND age > 18`.
+    pub fn or_where(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        let (condition, value) = op
+            .into()
+            .render(column, self.params.len() + 1, value.into());
+        self.params.extend(value);
+        match self.wheres.pop() {
+            Some(previous) => self.wheres.push(format!("({} OR {})", previous, condition)),
+            None => self.wheres.push(condition),
+        }
+        self
+    }
+
+    /// Adds a group of conditions of which any may match, ANDed with the
+    /// rest of the filter.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // WHERE tenant_id = $1 AND (name ILIKE $2 OR email ILIKE $3)
+    /// orm.query("users")
+    ///     .r#where("tenant_id", "=", tenant)
+    ///     .where_any(|g| g.ilike("name", term).ilike("email", term));
+    /// ```
+    pub fn where_any(mut self, build: impl FnOnce(WhereGroup) -> WhereGroup) -> Self {
+        let group = build(WhereGroup {
+            conditions: Vec::new(),
+            first_param: self.params.len() + 1,
+            params: Vec::new(),
+        });
+        if !group.conditions.is_empty() {
+            self.wheres
+                .push(format!("({})", group.conditions.join(" OR ")));
+            self.params.extend(group.params);
+        }
         self
     }
 
@@ -51,17 +242,25 @@ impl<'a> QueryBuilder<'a> {
     let pattern = format!("%{}%", pattern); // wrap automatically
     self.wheres
         .push(format!("{} LIKE ${}", column, self.params.len() + 1));
-    self.params.push(pattern);
+    self.params.push(pattern.into());
     self
 }
     pub fn ilike(mut self, column: &str, pattern: &str) -> Self {
     let pattern = format!("%{}%", pattern); // wrap automatically
     self.wheres
         .push(format!("{} ILIKE ${}", column, self.params.len() + 1));
-    self.params.push(pattern);
+    self.params.push(pattern.into());
     self
 }
 
+    /// `column NOT LIKE %pattern%`, the negation of [`like`](Self::like).
+    pub fn not_like(mut self, column: &str, pattern: &str) -> Self {
+        self.wheres
+            .push(format!("{} NOT LIKE ${}", column, self.params.len() + 1));
+        self.params.push(format!("%{}%", pattern).into());
+        self
+    }
+
 
     pub fn join(mut self, table: &str, left: &str, right: &str) -> Self {
         self.joins
@@ -75,6 +274,53 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
+    pub fn right_join(mut self, table: &str, left: &str, right: &str) -> Self {
+        self.joins
+            .push(format!("RIGHT JOIN {} ON {} = {}", table, left, right));
+        self
+    }
+
+    pub fn full_join(mut self, table: &str, left: &str, right: &str) -> Self {
+        self.joins
+            .push(format!("FULL JOIN {} ON {} = {}", table, left, right));
+        self
+    }
+
+    /// Every row paired with every row of `table`; takes no `ON` conditions.
+    pub fn cross_join(mut self, table: &str) -> Self {
+        self.joins.push(format!("CROSS JOIN {}", table));
+        self
+    }
+
+    /// Adds `AND left = right` to the `ON` clause of the most recent join.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orm.query("users")
+    ///     .left_join("orders", "orders.user_id", "users.id")
+    ///     .on("orders.tenant_id", "users.tenant_id")
+    ///     .on_value("orders.status", "=", "open");
+    /// ```
+    pub fn on(mut self, left: &str, right: &str) -> Self {
+        if let Some(join) = self.joins.last_mut() {
+            join.push_str(&format!(" AND {} = {}", left, right));
+        }
+        self
+    }
+
+    /// Adds `AND column op $n` to the `ON` clause of the most recent join,
+    /// comparing against a bound value rather than another column.
+    pub fn on_value(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        if let Some(join) = self.joins.last_mut() {
+            let (condition, value) = op
+                .into()
+                .render(column, self.params.len() + 1, value.into());
+            join.push_str(&format!(" AND {}", condition));
+            self.params.extend(value);
+        }
+        self
+    }
+
     pub fn limit(mut self, n: i64) -> Self {
         self.limit_clause = Some(format!("LIMIT {}", n));
         self
@@ -85,8 +331,32 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
-    fn build_sql(&self) -> String {
-        let mut sql = format!("SELECT {} FROM {}", self.selects.join(","), self.table);
+    pub fn offset(mut self, n: i64) -> Self {
+        self.offset_clause = Some(format!("OFFSET {}", n));
+        self
+    }
+
+    /// Adds `columns` to the `GROUP BY` list; pair it with a `select` of the
+    /// grouped columns and aggregates.
+    pub fn group_by(mut self, columns: &[&str]) -> Self {
+        self.groups.extend(columns.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// A `HAVING` condition on a grouped column or aggregate, e.g.
+    /// `having("count(*)", ">", 5)`. Several are joined with `AND`.
+    pub fn having(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        let (condition, value) = op
+            .into()
+            .render(column, self.params.len() + 1, value.into());
+        self.havings.push(condition);
+        self.params.extend(value);
+        self
+    }
+
+    /// `FROM` through `HAVING`: everything that decides which rows match.
+    fn body_sql(&self) -> String {
+        let mut sql = format!("FROM {}", self.table);
         if !self.joins.is_empty() {
             sql += &format!(" {}", self.joins.join(" "));
         }
@@ -99,10 +369,47 @@ impl<'a> QueryBuilder<'a> {
         if !self.havings.is_empty() {
             sql += &format!(" HAVING {}", self.havings.join(" AND "));
         }
+        sql
+    }
+
+    /// `sql` with the builder's parameters bound.
+    fn bound<'q>(&'q self, sql: &'q str) -> PgQuery<'q> {
+        let mut query = self.orm.statement(sql);
+        for param in &self.params {
+            query = param.clone().bind(query);
+        }
+        query
+    }
+
+    /// Runs the select `sql` with the builder's parameters, counting it in
+    /// `OrmStruct::query_stats`.
+    async fn fetch_rows(&self, sql: &str) -> sqlx::Result<Vec<PgRow>> {
+        let query = self.bound(sql).fetch_all(self.orm.pool());
+        self.orm.timed(&self.table, Operation::Select, query).await
+    }
+
+    fn build_sql(&self) -> String {
+        self.build_sql_limited(self.limit_clause.as_deref())
+    }
+
+    fn build_sql_limited(&self, limit: Option<&str>) -> String {
+        // A bare `*` on the base table leaves out write-only hashed columns.
+        let selects: Vec<String> = self
+            .selects
+            .iter()
+            .map(|s| {
+                if s == "*" && self.joins.is_empty() {
+                    self.orm.select_list(&self.table)
+                } else {
+                    s.clone()
+                }
+            })
+            .collect();
+        let mut sql = format!("SELECT {} {}", selects.join(","), self.body_sql());
         if let Some(order) = &self.order_clause {
             sql += &format!(" {}", order);
         }
-        if let Some(limit) = &self.limit_clause {
+        if let Some(limit) = limit {
             sql += &format!(" {}", limit);
         }
         if let Some(offset) = &self.offset_clause {
@@ -111,54 +418,168 @@ impl<'a> QueryBuilder<'a> {
         sql
     }
 
-   
+    /// The SQL this builder runs and its bind values in placeholder order,
+    /// without executing it.
+    ///
+    /// # Example
+    /// ```
+    /// # use slintrust::*;
+    /// # let orm = OrmStruct::new("postgres://localhost/app".into(), Vec::new());
+    /// let (sql, params) = orm.query("users").r#where("age", ">", 18).to_sql();
+    /// assert_eq!(sql, "SELECT * FROM users WHERE age > $1");
+    /// assert_eq!(params, vec![SqlValue::from(18)]);
+    /// ```
+    pub fn to_sql(&self) -> (String, Vec<SqlValue>) {
+        (self.build_sql(), self.params.clone())
+    }
+
     pub async fn fetch_all<T>(&self) -> Result<Vec<T>, sqlx::Error>
     where
         T: DeserializeOwned,
     {
         let sql = self.build_sql();
-        let mut query = sqlx::query(&sql);
-        for param in &self.params {
-            query = query.bind(param);
-        }
-
-        let rows = query.fetch_all(self.pool).await?;
+        let rows = self.fetch_rows(&sql).await?;
         let mut results = Vec::with_capacity(rows.len());
 
         for r in rows {
-            let mut map = serde_json::Map::new();
-            for col in r.columns() {
-                let col_name = col.name();
-                let value = match r.try_get::<Option<i64>, _>(col_name) {
-                    Ok(Some(v)) => Value::from(v),
-                    Ok(None) => Value::Null,
-                    Err(_) => match r.try_get::<Option<f64>, _>(col_name) {
-                        Ok(Some(v)) => Value::from(v),
-                        Ok(None) => Value::Null,
-                        Err(_) => match r.try_get::<Option<bool>, _>(col_name) {
-                            Ok(Some(v)) => Value::from(v),
-                            Ok(None) => Value::Null,
-                            Err(_) => match r.try_get::<Option<String>, _>(col_name) {
-                                Ok(Some(v)) => Value::from(v),
-                                Ok(None) => Value::Null,
-                                Err(_) => Value::Null,
-                            },
-                        },
-                    },
-                };
-                map.insert(col_name.to_string(), value);
-            }
-            let obj = serde_json::from_value::<T>(Value::Object(map)).map_err(|e| {
-                sqlx::Error::ColumnDecode {
-                    index: "serde_json".into(),
-                    source: Box::new(e),
-                }
-            })?;
+            let obj = self.orm.decode::<T>(&self.table, &r)?;
             results.push(obj);
         }
         Ok(results)
     }
 
+    /// Like `fetch_all`, but returns each row as a JSON object keyed by
+    /// column name, for result shapes without a matching struct.
+    pub async fn fetch_json(&self) -> Result<Vec<Value>, sqlx::Error> {
+        let sql = self.build_sql();
+        let rows = self.fetch_rows(&sql).await?;
+        rows.iter()
+            .map(|r| self.orm.row_json(&self.table, r))
+            .collect()
+    }
+
+    /// Number of matching rows — or of groups, with `group_by` — ignoring
+    /// order, limit and offset.
+    pub async fn count(&self) -> Result<i64, sqlx::Error> {
+        let sql = if self.groups.is_empty() {
+            format!("SELECT count(*) {}", self.body_sql())
+        } else {
+            format!(
+                "SELECT count(*) FROM (SELECT 1 {}) AS counted",
+                self.body_sql()
+            )
+        };
+        let query = self.bound(&sql).fetch_one(self.orm.pool());
+        self.orm
+            .timed(&self.table, Operation::Select, query)
+            .await?
+            .try_get(0)
+    }
+
+    /// Whether any row matches, stopping at the first one found.
+    pub async fn exists(&self) -> Result<bool, sqlx::Error> {
+        let sql = format!("SELECT EXISTS (SELECT 1 {})", self.body_sql());
+        let query = self.bound(&sql).fetch_one(self.orm.pool());
+        self.orm
+            .timed(&self.table, Operation::Select, query)
+            .await?
+            .try_get(0)
+    }
+
+    /// Sets the columns of `changes` (a JSON object) on every matching row
+    /// and returns how many rows were updated. Values are encrypted and
+    /// hashed like other ORM writes.
+    ///
+    /// # Example
+    /// ```ignore
+    /// orm.query("orders")
+    ///     .r#where("status", "=", "pending")
+    ///     .r#where("created_at", "<", cutoff)
+    ///     .update(json!({ "status": "expired" }))
+    ///     .await?;
+    /// ```
+    pub async fn update(&self, changes: Value) -> Result<u64, sqlx::Error> {
+        let Value::Object(mut changes) = changes else {
+            return Err(sqlx::Error::InvalidArgument(
+                "update expects a JSON object of column values".into(),
+            ));
+        };
+        if changes.is_empty() {
+            return Err(sqlx::Error::InvalidArgument(
+                "changes must contain at least one column".into(),
+            ));
+        }
+        self.check_writable("update")?;
+        self.orm.stamp_update(&self.table, &mut changes);
+
+        let first = self.params.len() + 1;
+        let sets: Vec<String> = changes
+            .keys()
+            .enumerate()
+            .map(|(i, column)| {
+                let placeholder = self.orm.placeholder(&self.table, column, first + i);
+                format!("{} = {}", column, placeholder)
+            })
+            .collect();
+        let mut sql = format!("UPDATE {} SET {}", self.table, sets.join(", "));
+        if !self.wheres.is_empty() {
+            sql += &format!(" {}", self.where_clause());
+        }
+        let returning = self.orm.event_returning(&self.table);
+        if let Some(returning) = &returning {
+            sql.push_str(returning);
+        }
+
+        let mut query = self.bound(&sql);
+        for (column, v) in changes {
+            query = bind_json(query, self.orm.encode_column(&self.table, &column, v)?);
+        }
+        self.orm
+            .execute_reporting(&self.table, query, returning.is_some(), Change::Update)
+            .await
+    }
+
+    /// Deletes every matching row and returns how many were deleted. With
+    /// no `where` conditions that is the whole table.
+    pub async fn delete(&self) -> Result<u64, sqlx::Error> {
+        self.check_writable("delete")?;
+        let mut sql = format!("DELETE FROM {}", self.table);
+        if !self.wheres.is_empty() {
+            sql += &format!(" {}", self.where_clause());
+        }
+        let returning = self.orm.event_returning(&self.table);
+        if let Some(returning) = &returning {
+            sql.push_str(returning);
+        }
+        let query = self.bound(&sql);
+        self.orm
+            .execute_reporting(&self.table, query, returning.is_some(), Change::Delete)
+            .await
+    }
+
+    /// Writes filter rows with `WHERE` alone; joins, grouping and paging
+    /// would change which rows match.
+    fn check_writable(&self, operation: &str) -> Result<(), sqlx::Error> {
+        let unsupported = [
+            (!self.joins.is_empty(), "joins"),
+            (
+                !self.groups.is_empty() || !self.havings.is_empty(),
+                "grouping",
+            ),
+            (
+                self.limit_clause.is_some() || self.offset_clause.is_some(),
+                "limit or offset",
+            ),
+        ];
+        match unsupported.iter().find(|(used, _)| *used) {
+            Some((_, what)) => Err(sqlx::Error::InvalidArgument(format!(
+                "{} can't be combined with {}",
+                operation, what
+            ))),
+            None => Ok(()),
+        }
+    }
+
     pub fn where_clause(&self) -> String {
         if self.wheres.is_empty() {
             "".to_string()
@@ -167,11 +588,66 @@ impl<'a> QueryBuilder<'a> {
         }
     }
 
+    /// The first matching row, or `RowNotFound` when there is none.
     pub async fn fetch_one<T>(&self) -> Result<T, sqlx::Error>
     where
         T: DeserializeOwned,
     {
-        let mut all = self.fetch_all::<T>().await?;
-        all.pop().ok_or(sqlx::Error::RowNotFound)
+        self.fetch_optional::<T>()
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// The first matching row, if any. Only that row is fetched: the query
+    /// runs with `LIMIT 1`.
+    pub async fn fetch_optional<T>(&self) -> Result<Option<T>, sqlx::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let sql = self.build_sql_limited(Some("LIMIT 1"));
+        let query = self.bound(&sql).fetch_optional(self.orm.pool());
+        let row = self
+            .orm
+            .timed(&self.table, Operation::Select, query)
+            .await?;
+        row.map(|r| self.orm.decode::<T>(&self.table, &r))
+            .transpose()
+    }
+}
+
+/// Conditions of a [`QueryBuilder::where_any`] group, joined with `OR`.
+pub struct WhereGroup {
+    conditions: Vec<String>,
+    first_param: usize,
+    params: Vec<SqlValue>,
+}
+
+impl WhereGroup {
+    fn push(&mut self, condition: String, value: SqlValue) {
+        self.conditions.push(condition);
+        self.params.push(value);
+    }
+
+    fn next_param(&self) -> usize {
+        self.first_param + self.params.len()
+    }
+
+    pub fn r#where(mut self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        let (condition, value) = op.into().render(column, self.next_param(), value.into());
+        self.conditions.push(condition);
+        self.params.extend(value);
+        self
+    }
+
+    pub fn like(mut self, column: &str, pattern: &str) -> Self {
+        let condition = format!("{} LIKE ${}", column, self.next_param());
+        self.push(condition, format!("%{}%", pattern).into());
+        self
+    }
+
+    pub fn ilike(mut self, column: &str, pattern: &str) -> Self {
+        let condition = format!("{} ILIKE ${}", column, self.next_param());
+        self.push(condition, format!("%{}%", pattern).into());
+        self
     }
 }
@@ -0,0 +1,318 @@
+use crate::{OrmStruct, SqlValue};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::ops::{Bound, Range, RangeFrom, RangeInclusive, RangeTo};
+use std::str::FromStr;
+
+/// A value of a Postgres range column (`INT4RANGE`, `INT8RANGE`,
+/// `DATERANGE`, `TSRANGE`, `TSTZRANGE`), stored and read as the range's
+/// text form, e.g. `[2024-06-01,2024-06-08)`, or `empty` for the empty
+/// range ([`SqlRange::empty`]).
+///
+/// `#[slint]` infers the column type from the bound type: `i32`, `i64`,
+/// `NaiveDate`, `NaiveDateTime` and `DateTime<Utc>` give the ranges above.
+///
+/// # Example
+/// ```
+/// # use slintrust::*;
+/// # use chrono::NaiveDate;
+/// # let id = "b1".to_string();
+/// # let check_in = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+/// # let check_out = NaiveDate::from_ymd_opt(2024, 6, 8).unwrap();
+/// #[slint(table_name = "bookings")]
+/// #[derive(Serialize, Deserialize)]
+/// struct Booking {
+///     #[slint(primary)]
+///     id: String,
+///     stay: SqlRange<NaiveDate>, // DATERANGE
+/// }
+///
+/// let booking = Booking { id, stay: (check_in..check_out).into() };
+/// assert_eq!(booking.stay.to_string(), "[2024-06-01,2024-06-08)");
+/// assert!("empty".parse::<SqlRange<NaiveDate>>().unwrap().is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlRange<T> {
+    pub start: Bound<T>,
+    pub end: Bound<T>,
+    /// The range contains no values, e.g. `[5,5)`. Postgres stores every
+    /// such range as `empty`, without bounds; the bounds are then
+    /// `Unbounded` and ignored.
+    pub empty: bool,
+}
+
+impl<T> SqlRange<T> {
+    pub fn new(start: Bound<T>, end: Bound<T>) -> Self {
+        Self {
+            start,
+            end,
+            empty: false,
+        }
+    }
+
+    /// The empty range, which contains no values and overlaps nothing.
+    pub fn empty() -> Self {
+        Self {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+            empty: true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.empty
+    }
+}
+
+impl<T> From<Range<T>> for SqlRange<T> {
+    fn from(range: Range<T>) -> Self {
+        Self::new(Bound::Included(range.start), Bound::Excluded(range.end))
+    }
+}
+
+impl<T> From<RangeInclusive<T>> for SqlRange<T> {
+    fn from(range: RangeInclusive<T>) -> Self {
+        let (start, end) = range.into_inner();
+        Self::new(Bound::Included(start), Bound::Included(end))
+    }
+}
+
+impl<T> From<RangeFrom<T>> for SqlRange<T> {
+    fn from(range: RangeFrom<T>) -> Self {
+        Self::new(Bound::Included(range.start), Bound::Unbounded)
+    }
+}
+
+impl<T> From<RangeTo<T>> for SqlRange<T> {
+    fn from(range: RangeTo<T>) -> Self {
+        Self::new(Bound::Unbounded, Bound::Excluded(range.end))
+    }
+}
+
+/// The range's text form. Bounds are written as they serialize to JSON,
+/// so timestamps are RFC 3339.
+impl<T: Serialize> fmt::Display for SqlRange<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.empty {
+            return f.write_str("empty");
+        }
+        let bound = |bound: &Bound<T>| match bound {
+            Bound::Included(v) | Bound::Excluded(v) => match serde_json::to_value(v) {
+                Ok(Value::String(s)) => quote_bound(&s),
+                Ok(other) => other.to_string(),
+                Err(_) => String::new(),
+            },
+            Bound::Unbounded => String::new(),
+        };
+        let open = if matches!(self.start, Bound::Included(_)) {
+            '['
+        } else {
+            '('
+        };
+        let close = if matches!(self.end, Bound::Included(_)) {
+            ']'
+        } else {
+            ')'
+        };
+        write!(
+            f,
+            "{}{},{}{}",
+            open,
+            bound(&self.start),
+            bound(&self.end),
+            close
+        )
+    }
+}
+
+impl<T: DeserializeOwned> FromStr for SqlRange<T> {
+    type Err = String;
+
+    /// Parses a range's text form, including `empty`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("empty") {
+            return Ok(Self::empty());
+        }
+        let invalid = || format!("invalid range `{}`", s);
+        let mut chars = s.chars();
+        let (Some(open), Some(close)) = (chars.next(), chars.next_back()) else {
+            return Err(invalid());
+        };
+        let (lower, upper) = split_bounds(chars.as_str()).ok_or_else(invalid)?;
+        let bound = |text: Option<String>, included: bool| -> Result<Bound<T>, String> {
+            let Some(text) = text else {
+                return Ok(Bound::Unbounded);
+            };
+            // Strings such as dates deserialize from JSON strings, numbers
+            // from their JSON text.
+            let value = serde_json::from_value(Value::String(text.clone()))
+                .or_else(|_| serde_json::from_str(&text))
+                .map_err(|e| format!("invalid range bound `{}`: {}", text, e))?;
+            Ok(if included {
+                Bound::Included(value)
+            } else {
+                Bound::Excluded(value)
+            })
+        };
+        let start = match open {
+            '[' => bound(lower, true)?,
+            '(' => bound(lower, false)?,
+            _ => return Err(invalid()),
+        };
+        let end = match close {
+            ']' => bound(upper, true)?,
+            ')' => bound(upper, false)?,
+            _ => return Err(invalid()),
+        };
+        Ok(Self::new(start, end))
+    }
+}
+
+impl<T: Serialize> Serialize for SqlRange<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for SqlRange<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Bound as the range's text, which the column's placeholder cast parses.
+impl<T: Serialize> From<SqlRange<T>> for SqlValue {
+    fn from(v: SqlRange<T>) -> Self {
+        SqlValue::Text(v.to_string())
+    }
+}
+
+/// The two bounds between a range's brackets, unquoted; `None` for a
+/// missing (unbounded) one.
+fn split_bounds(inner: &str) -> Option<(Option<String>, Option<String>)> {
+    let mut bounds = vec![String::new()];
+    let mut quoted = vec![false];
+    let mut in_quotes = false;
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                *quoted.last_mut()? = true;
+            }
+            '\\' => bounds.last_mut()?.push(chars.next()?),
+            ',' if !in_quotes => {
+                bounds.push(String::new());
+                quoted.push(false);
+            }
+            c => bounds.last_mut()?.push(c),
+        }
+    }
+    let [lower, upper] = <[String; 2]>::try_from(bounds).ok()?;
+    let present = |text: String, quoted: bool| (quoted || !text.is_empty()).then_some(text);
+    Some((present(lower, quoted[0]), present(upper, quoted[1])))
+}
+
+/// `s` as a range bound, quoted only when it is empty or holds characters
+/// the range syntax uses, as Postgres writes it.
+fn quote_bound(s: &str) -> String {
+    let special = |c: char| "()[],\"\\".contains(c) || c.is_whitespace();
+    if !s.is_empty() && !s.contains(special) {
+        return s.to_string();
+    }
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Element type of the range type `sql_type`, e.g. `INTEGER` for
+/// `INT4RANGE`.
+pub fn range_element_type(sql_type: &str) -> Option<&'static str> {
+    match sql_type.to_ascii_uppercase().as_str() {
+        "INT4RANGE" => Some("INTEGER"),
+        "INT8RANGE" => Some("BIGINT"),
+        "NUMRANGE" => Some("NUMERIC"),
+        "DATERANGE" => Some("DATE"),
+        "TSRANGE" => Some("TIMESTAMP"),
+        "TSTZRANGE" => Some("TIMESTAMPTZ"),
+        _ => None,
+    }
+}
+
+/// Placeholder `$index` for a value compared with the elements of range
+/// `column`, cast to the element type when the column is a known range.
+/// Values bind with their own type, and `int4range @> bigint` doesn't
+/// resolve without it.
+pub(crate) fn element_placeholder(
+    orm: &OrmStruct,
+    table_name: &str,
+    column: &str,
+    index: usize,
+) -> String {
+    let element = orm
+        .find_schema(table_name)
+        .and_then(|s| s.column(column))
+        .and_then(|c| range_element_type(c.sql_type));
+    match element {
+        Some(element) => format!("${}::{}", index, element),
+        None => format!("${}", index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parses_bounds_and_inclusivity() {
+        let range: SqlRange<i32> = "[1,10)".parse().unwrap();
+        assert_eq!(range, SqlRange::from(1..10));
+        let range: SqlRange<i64> = "(,5]".parse().unwrap();
+        assert_eq!(range, SqlRange::new(Bound::Unbounded, Bound::Included(5)));
+        let range: SqlRange<i32> = "(3,)".parse().unwrap();
+        assert_eq!(range, SqlRange::new(Bound::Excluded(3), Bound::Unbounded));
+    }
+
+    #[test]
+    fn parses_quoted_bounds() {
+        let range: SqlRange<String> = r#"["a,b","c\"d")"#.parse().unwrap();
+        assert_eq!(range, SqlRange::from("a,b".to_string().."c\"d".to_string()));
+        let range: SqlRange<String> = r#"["",z)"#.parse().unwrap();
+        assert_eq!(range.start, Bound::Included(String::new()));
+    }
+
+    #[test]
+    fn parses_the_empty_range() {
+        for text in ["empty", "EMPTY", " empty "] {
+            let range: SqlRange<i32> = text.parse().unwrap();
+            assert!(range.is_empty());
+            assert_eq!(range, SqlRange::empty());
+        }
+        assert_eq!(SqlRange::<i32>::empty().to_string(), "empty");
+    }
+
+    #[test]
+    fn rejects_malformed_ranges() {
+        for text in ["", "[1,2", "1,2)", "{1,2}", "[1,2,3)", "[x,2)"] {
+            assert!(text.parse::<SqlRange<i32>>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn displays_as_postgres_writes_it() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        assert_eq!(
+            SqlRange::from(day(1)..day(8)).to_string(),
+            "[2024-06-01,2024-06-08)"
+        );
+        assert_eq!(SqlRange::from(1..=9).to_string(), "[1,9]");
+        assert_eq!(SqlRange::from(..4).to_string(), "(,4)");
+        let text = SqlRange::from("a b".to_string().."".to_string()).to_string();
+        assert_eq!(text, r#"["a b","")"#);
+        assert_eq!(text.parse::<SqlRange<String>>().unwrap().to_string(), text);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{Direction, Op, Query, SqlValue, Table};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// A [`Table`] handle that can only read, from [`Table::read_only`].
+///
+/// It has no insert, update or delete methods, and returns plain values
+/// rather than [`Record`](crate::Record)s, which could update or delete
+/// their row. Hand it to code that must not write, e.g. the query side of
+/// a command/query split.
+///
+/// # Example
+/// ```ignore
+/// let users: ReadOnlyTable<User> = user_table.read_only();
+/// let admins = users.query().where_clause("role", Op::Eq, "admin").get().await?;
+/// ```
+pub struct ReadOnlyTable<T> {
+    table: Table<T>,
+}
+
+impl<T> Clone for ReadOnlyTable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<T> ReadOnlyTable<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    pub(crate) fn new(table: Table<T>) -> Self {
+        Self { table }
+    }
+
+    /// The first row matching every column of `filter`, see [`Table::get`].
+    pub async fn get(&self, filter: Value) -> sqlx::Result<Option<T>> {
+        Ok(self.table.get(filter).await?.map(|record| record.value))
+    }
+
+    /// Every row of the table.
+    pub async fn get_all(&self) -> sqlx::Result<Vec<T>> {
+        self.query().get().await
+    }
+
+    /// The row whose key column equals `key`.
+    pub async fn find(&self, key: impl Into<SqlValue>) -> sqlx::Result<Option<T>> {
+        Ok(self.table.find(key).await?.map(|record| record.value))
+    }
+
+    /// See [`Table::descendants_of`].
+    pub async fn descendants_of(
+        &self,
+        id: impl Into<SqlValue>,
+        parent_column: &str,
+    ) -> sqlx::Result<Vec<T>> {
+        let records = self.table.descendants_of(id, parent_column).await?;
+        Ok(records.into_iter().map(|record| record.value).collect())
+    }
+
+    /// See [`Table::ancestors_of`].
+    pub async fn ancestors_of(
+        &self,
+        id: impl Into<SqlValue>,
+        parent_column: &str,
+    ) -> sqlx::Result<Vec<T>> {
+        let records = self.table.ancestors_of(id, parent_column).await?;
+        Ok(records.into_iter().map(|record| record.value).collect())
+    }
+
+    /// A query over the table's rows.
+    pub fn query(&self) -> ReadQuery<'_, T> {
+        ReadQuery {
+            query: self.table.query(),
+        }
+    }
+
+    /// A query over the table as it was at `at`, see [`Table::as_of`].
+    pub fn as_of(&self, at: DateTime<Utc>) -> ReadQuery<'_, T> {
+        ReadQuery {
+            query: self.table.as_of(at),
+        }
+    }
+}
+
+/// A [`Query`] of a [`ReadOnlyTable`], without `update_returning` and
+/// returning plain values. The common conditions have methods of their
+/// own; [`refine`](Self::refine) gives access to the rest of the builder.
+pub struct ReadQuery<'a, T> {
+    query: Query<'a, T>,
+}
+
+impl<T> Clone for ReadQuery<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            query: self.query.clone(),
+        }
+    }
+}
+
+impl<'a, T> ReadQuery<'a, T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    /// See [`Query::where_clause`].
+    pub fn where_clause(self, column: &str, op: impl Into<Op>, value: impl Into<SqlValue>) -> Self {
+        self.refine(|q| q.where_clause(column, op, value))
+    }
+
+    /// See [`Query::where_key`].
+    pub fn where_key(self, key: impl Into<SqlValue>) -> Self {
+        self.refine(|q| q.where_key(key))
+    }
+
+    /// See [`Query::where_in`].
+    pub fn where_in<V>(self, column: &str, values: &[V]) -> Self
+    where
+        V: Clone + Into<SqlValue>,
+    {
+        self.refine(|q| q.where_in(column, values))
+    }
+
+    pub fn order_by(self, column: &str, direction: Direction) -> Self {
+        self.refine(|q| q.order_by(column, direction))
+    }
+
+    pub fn limit(self, n: u32) -> Self {
+        self.refine(|q| q.limit(n))
+    }
+
+    pub fn offset(self, n: u32) -> Self {
+        self.refine(|q| q.offset(n))
+    }
+
+    /// Applies any other [`Query`] builder method.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let page = users.query()
+    ///     .refine(|q| q.ilike("name", "ada").distinct())
+    ///     .get()
+    ///     .await?;
+    /// ```
+    pub fn refine(self, build: impl FnOnce(Query<'a, T>) -> Query<'a, T>) -> Self {
+        Self {
+            query: build(self.query),
+        }
+    }
+
+    /// See [`Query::to_sql`].
+    pub fn to_sql(&self) -> (String, Vec<SqlValue>) {
+        self.query.to_sql()
+    }
+
+    pub async fn get(self) -> sqlx::Result<Vec<T>> {
+        let records = self.query.get().await?;
+        Ok(records.into_iter().map(|record| record.value).collect())
+    }
+
+    pub async fn first(self) -> sqlx::Result<Option<T>> {
+        self.query.first_value_opt().await
+    }
+
+    /// See [`Query::count`].
+    pub async fn count(self) -> sqlx::Result<i64> {
+        self.query.count().await
+    }
+
+    /// See [`Query::get_as`].
+    pub async fn get_as<R>(self) -> sqlx::Result<Vec<R>>
+    where
+        R: DeserializeOwned,
+    {
+        self.query.get_as().await
+    }
+}
@@ -0,0 +1,327 @@
+use crate::SqlRange;
+use crate::hstore::{hstore_binary_json, hstore_text_json};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::postgres::types::PgRange;
+use sqlx::postgres::{PgHasArrayType, PgRow, PgTypeInfo, PgTypeKind, PgValueFormat};
+use sqlx::{Column, Decode, Postgres, Row, Type, TypeInfo, ValueRef};
+use std::net::IpAddr;
+use std::ops::Range;
+use uuid::Uuid;
+
+/// Converts a row into a JSON object keyed by column name.
+///
+/// Integers, floats and booleans become JSON numbers and booleans. Text,
+/// UUIDs, network addresses, dates and times (RFC 3339, as chrono
+/// serializes them) and ranges (`[1,10)`, see [`SqlRange`]) become
+/// strings. `JSON` / `JSONB` columns (with the `json` feature) are
+/// embedded as-is, `HSTORE` columns become objects of strings and arrays
+/// become JSON arrays. `NUMERIC` is converted to a float, like `f64`
+/// fields expect. NULLs and columns of any other type are `null`.
+pub fn row_to_json(row: &PgRow) -> Value {
+    columns_to_json(row, 0..row.len())
+}
+
+/// [`row_to_json`] of the columns in `columns` only.
+pub(crate) fn columns_to_json(row: &PgRow, columns: Range<usize>) -> Value {
+    let mut map = serde_json::Map::new();
+    for index in columns {
+        let col = &row.columns()[index];
+        map.insert(
+            col.name().to_string(),
+            column_value(row, index, col.type_info()),
+        );
+    }
+    Value::Object(map)
+}
+
+/// JSON value of column `index` of `row`, whose type is `ty`.
+fn column_value(row: &PgRow, index: usize, ty: &PgTypeInfo) -> Value {
+    if let PgTypeKind::Array(element) = ty.kind() {
+        return array_value(row, index, element.name());
+    }
+    match ty.name() {
+        "BOOL" => get(row, index, Value::Bool),
+        "INT2" => get(row, index, |v: i16| Value::from(v)),
+        "INT4" => get(row, index, |v: i32| Value::from(v)),
+        "INT8" => get(row, index, |v: i64| Value::from(v)),
+        "FLOAT4" => get(row, index, |v: f32| float(v as f64)),
+        "FLOAT8" => get(row, index, float),
+        "NUMERIC" => numeric_value(row, index),
+        "INET" | "CIDR" | "MACADDR" | "MACADDR8" => network_value(row, index, ty.name()),
+        "INT4RANGE" => range_value::<i32>(row, index),
+        "INT8RANGE" => range_value::<i64>(row, index),
+        "DATERANGE" => range_value::<NaiveDate>(row, index),
+        "TSRANGE" => range_value::<NaiveDateTime>(row, index),
+        "TSTZRANGE" => range_value::<DateTime<Utc>>(row, index),
+        // `CHAR` is sqlx's name for `BPCHAR`.
+        "TEXT" | "VARCHAR" | "CHAR" | "NAME" => get(row, index, Value::String),
+        "UUID" => get(row, index, |v: Uuid| Value::String(v.to_string())),
+        #[cfg(feature = "json")]
+        "JSON" | "JSONB" => get(row, index, |v: Value| v),
+        "TIMESTAMPTZ" => get(row, index, |v: DateTime<Utc>| json(v)),
+        "TIMESTAMP" => get(row, index, |v: NaiveDateTime| json(v)),
+        "DATE" => get(row, index, |v: NaiveDate| json(v)),
+        "TIME" => get(row, index, |v: NaiveTime| json(v)),
+        // Extension text types and enum labels are sent as their text.
+        "citext" => text(row, index),
+        "hstore" => hstore_value(row, index),
+        #[cfg(feature = "geo")]
+        "geometry" => geometry_value(row, index),
+        _ if matches!(ty.kind(), PgTypeKind::Enum(_)) => text(row, index),
+        _ => Value::Null,
+    }
+}
+
+/// Column `index` read as text whatever its declared type.
+fn text(row: &PgRow, index: usize) -> Value {
+    match row.try_get_unchecked(index) {
+        Ok(Some(v)) => Value::String(v),
+        _ => Value::Null,
+    }
+}
+
+/// JSON array for an array column whose elements are of type `element`.
+fn array_value(row: &PgRow, index: usize, element: &str) -> Value {
+    match element {
+        "BOOL" => get_array(row, index, Value::Bool),
+        "INT2" => get_array(row, index, |v: i16| Value::from(v)),
+        "INT4" => get_array(row, index, |v: i32| Value::from(v)),
+        "INT8" => get_array(row, index, |v: i64| Value::from(v)),
+        "FLOAT4" => get_array(row, index, |v: f32| float(v as f64)),
+        "FLOAT8" => get_array(row, index, float),
+        "TEXT" | "VARCHAR" | "CHAR" | "NAME" => get_array(row, index, Value::String),
+        "UUID" => get_array(row, index, |v: Uuid| Value::String(v.to_string())),
+        #[cfg(feature = "json")]
+        "JSON" | "JSONB" => get_array(row, index, |v: Value| v),
+        "TIMESTAMPTZ" => get_array(row, index, |v: DateTime<Utc>| json(v)),
+        "TIMESTAMP" => get_array(row, index, |v: NaiveDateTime| json(v)),
+        "DATE" => get_array(row, index, |v: NaiveDate| json(v)),
+        "TIME" => get_array(row, index, |v: NaiveTime| json(v)),
+        _ => Value::Null,
+    }
+}
+
+/// Column `index` decoded as `T` and mapped to JSON; `null` when NULL.
+fn get<'r, T>(row: &'r PgRow, index: usize, to_json: impl FnOnce(T) -> Value) -> Value
+where
+    T: Decode<'r, Postgres> + Type<Postgres>,
+{
+    match row.try_get::<Option<T>, _>(index) {
+        Ok(Some(v)) => to_json(v),
+        _ => Value::Null,
+    }
+}
+
+/// Array column `index` decoded as `T` elements; NULL elements stay `null`.
+fn get_array<T>(row: &PgRow, index: usize, to_json: impl Fn(T) -> Value) -> Value
+where
+    T: for<'r> Decode<'r, Postgres> + Type<Postgres> + PgHasArrayType,
+{
+    match row.try_get::<Option<Vec<Option<T>>>, _>(index) {
+        Ok(Some(items)) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| item.map_or(Value::Null, &to_json))
+                .collect(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+/// A float as a JSON number; NaN and infinities have none and become `null`.
+fn float(v: f64) -> Value {
+    serde_json::Number::from_f64(v).map_or(Value::Null, Value::Number)
+}
+
+fn json<T: serde::Serialize>(v: T) -> Value {
+    serde_json::to_value(v).unwrap_or(Value::Null)
+}
+
+/// Range column `index` as its text form, which [`SqlRange`] fields
+/// deserialize from. `sqlx` decodes the empty range as unbounded on both
+/// sides, so it is told apart by the flags byte of the binary format (or
+/// the text `empty`) first.
+fn range_value<T>(row: &PgRow, index: usize) -> Value
+where
+    T: serde::Serialize,
+    PgRange<T>: for<'r> Decode<'r, Postgres> + Type<Postgres>,
+{
+    /// `RANGE_EMPTY` of Postgres' `rangetypes.h`.
+    const RANGE_EMPTY: u8 = 0x01;
+    let Ok(raw) = row.try_get_raw(index) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let empty = match raw.format() {
+        PgValueFormat::Text => raw.as_str().is_ok_and(|t| t.eq_ignore_ascii_case("empty")),
+        PgValueFormat::Binary => raw
+            .as_bytes()
+            .is_ok_and(|b| b.first().is_some_and(|f| f & RANGE_EMPTY != 0)),
+    };
+    if empty {
+        return Value::String(SqlRange::<T>::empty().to_string());
+    }
+    get(row, index, |v: PgRange<T>| {
+        Value::String(SqlRange::new(v.start, v.end).to_string())
+    })
+}
+
+/// `NUMERIC` column `index` as a float. No decimal type is enabled in
+/// `sqlx`, so the value is read from its wire format.
+fn numeric_value(row: &PgRow, index: usize) -> Value {
+    let Ok(raw) = row.try_get_raw(index) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let text = match raw.format() {
+        PgValueFormat::Text => raw.as_str().ok().map(str::to_string),
+        PgValueFormat::Binary => raw.as_bytes().ok().and_then(numeric_text),
+    };
+    text.and_then(|t| t.parse::<f64>().ok())
+        .map_or(Value::Null, float)
+}
+
+/// Network address column `index` of type `ty` as the text Postgres
+/// prints for it, e.g. `10.0.0.1`, `10.0.0.0/8` or `08:00:2b:01:02:03`.
+/// No network types are enabled in `sqlx`, so the value is read from its
+/// wire format.
+fn network_value(row: &PgRow, index: usize, ty: &str) -> Value {
+    let Ok(raw) = row.try_get_raw(index) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let text = match raw.format() {
+        PgValueFormat::Text => raw.as_str().ok().map(str::to_string),
+        PgValueFormat::Binary => raw.as_bytes().ok().and_then(|bytes| match ty {
+            "INET" | "CIDR" => inet_text(bytes),
+            _ => Some(mac_text(bytes)),
+        }),
+    };
+    text.map_or(Value::Null, Value::String)
+}
+
+/// hstore column `index` as a JSON object of strings (and nulls).
+fn hstore_value(row: &PgRow, index: usize) -> Value {
+    let Ok(raw) = row.try_get_raw(index) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let map = match raw.format() {
+        PgValueFormat::Text => raw.as_str().ok().and_then(hstore_text_json),
+        PgValueFormat::Binary => raw.as_bytes().ok().and_then(hstore_binary_json),
+    };
+    map.unwrap_or(Value::Null)
+}
+
+/// PostGIS geometry column `index` in the JSON form of its `geo_types`
+/// type.
+#[cfg(feature = "geo")]
+fn geometry_value(row: &PgRow, index: usize) -> Value {
+    let Ok(raw) = row.try_get_raw(index) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let bytes = match raw.format() {
+        PgValueFormat::Text => raw.as_str().ok().and_then(crate::geo::unhex),
+        PgValueFormat::Binary => raw.as_bytes().ok().map(<[u8]>::to_vec),
+    };
+    bytes
+        .as_deref()
+        .and_then(crate::geo::geometry_json)
+        .unwrap_or(Value::Null)
+}
+
+/// Text of a binary `INET` / `CIDR`: family, prefix length, CIDR flag and
+/// address length, then the address. The prefix is left out for a single
+/// `INET` host, as Postgres does.
+fn inet_text(bytes: &[u8]) -> Option<String> {
+    let [family, bits, is_cidr, _, address @ ..] = bytes else {
+        return None;
+    };
+    let (ip, max_bits): (IpAddr, u8) = match family {
+        2 => (<[u8; 4]>::try_from(address).ok()?.into(), 32),
+        3 => (<[u8; 16]>::try_from(address).ok()?.into(), 128),
+        _ => return None,
+    };
+    if *is_cidr == 0 && *bits == max_bits {
+        Some(ip.to_string())
+    } else {
+        Some(format!("{}/{}", ip, bits))
+    }
+}
+
+/// `MACADDR` / `MACADDR8` bytes as colon-separated lowercase hex.
+fn mac_text(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Decimal text of a binary `NUMERIC`: a header of digit count, weight
+/// (exponent of the first digit) and sign, then base-10000 digits.
+/// `None` for NaN and infinities.
+fn numeric_text(bytes: &[u8]) -> Option<String> {
+    let word = |i: usize| {
+        bytes
+            .get(i * 2..i * 2 + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    let ndigits = word(0)? as i32;
+    let weight = word(1)? as i16 as i32;
+    let sign = word(2)?;
+    let digits: Vec<u16> = (0..ndigits as usize)
+        .map(|i| word(4 + i))
+        .collect::<Option<_>>()?;
+    // Digit `i` is worth 10000^(weight - i); positions past either end are 0.
+    let digit = |i: i32| {
+        usize::try_from(i)
+            .ok()
+            .and_then(|i| digits.get(i).copied())
+            .unwrap_or(0)
+    };
+    let mut text = match sign {
+        0x0000 => String::new(),
+        0x4000 => "-".to_string(),
+        _ => return None,
+    };
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        for i in 1..=weight {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    if ndigits > weight + 1 {
+        text.push('.');
+        for i in weight + 1..ndigits {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    Some(text)
+}
+
+/// Deserializes a row into `T` through its JSON representation.
+pub fn decode_row<T>(row: &PgRow) -> sqlx::Result<T>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_value::<T>(row_to_json(row)).map_err(|e| sqlx::Error::ColumnDecode {
+        index: "serde_json".into(),
+        source: Box::new(e),
+    })
+}
@@ -1,21 +1,862 @@
-///
-
-
 // schema.rs
-#[derive(Debug)]
+use std::fmt;
+
+#[derive(Debug, serde::Serialize)]
 pub struct ColumnSchema {
     pub name: &'static str,
     pub sql_type: &'static str,
     pub primary: bool,
     pub unique: bool,
+    /// `#[slint(unique_ci)]`: unique regardless of case, enforced by a
+    /// `UNIQUE (lower(column))` index. `#[slint(unique_ci = "citext")]`
+    /// declares a `CITEXT UNIQUE` column instead.
+    pub unique_ci: bool,
+    /// `#[slint(deferrable)]`: the column's `UNIQUE` / `PRIMARY KEY` and
+    /// foreign key constraints are `DEFERRABLE`, so
+    /// `Session::set_constraints_deferred` can postpone their check to
+    /// commit.
+    pub deferrable: bool,
+    /// `#[slint(deferred)]`: deferrable and `INITIALLY DEFERRED`, checked
+    /// at commit unless a transaction sets it back to immediate.
+    pub initially_deferred: bool,
     pub not_null: bool,
     pub uuid: bool,
-    
+    /// Maximum character length for `#[slint(length = n)]` (`VARCHAR(n)`) columns.
+    pub length: Option<u32>,
+    /// Documentation emitted as `COMMENT ON COLUMN` by `migrate()`.
+    pub comment: Option<&'static str>,
+    /// `#[slint(collate = "und-x-icu")]`: collation of a text column,
+    /// emitted as `COLLATE` when the column is created.
+    pub collation: Option<&'static str>,
+    /// `#[slint(encrypted)]`: stored encrypted with the ORM's field cipher.
+    pub encrypted: bool,
+    /// `#[slint(hashed = "argon2")]`: write-only column holding a password
+    /// hash. Hashed on insert/update and left out of default selects.
+    pub hashed: Option<&'static str>,
+    /// `#[slint(masked)]`: redacted on fetch unless read through
+    /// `Table::with_unmasked`.
+    pub masked: bool,
+    /// `#[slint(default = "...")]`: SQL expression emitted as the column's
+    /// `DEFAULT`. Inserts that leave the column out get this value.
+    pub default: Option<&'static str>,
+    /// `#[slint(generated = "lower(email)")]`: `GENERATED ALWAYS AS (...)
+    /// STORED` column computed by the database and never written by
+    /// inserts or updates.
+    pub generated: Option<&'static str>,
+    /// `#[slint(serial)]`: `GENERATED ALWAYS AS IDENTITY` key, assigned by
+    /// the database and never written by inserts.
+    pub serial: bool,
+    /// `#[slint(foreign_key = "users.id")]`: the parent table (and column)
+    /// this column refers to, used to preload related records.
+    pub foreign_key: Option<&'static str>,
+    /// `#[slint(trigram)]`: indexed with a `pg_trgm` GIN index, so
+    /// `Query::fuzzy` on the column can use it.
+    pub trigram: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct TableSchema {
     pub name: &'static str,
+    /// Name of the Rust struct the schema was derived from.
+    pub model: &'static str,
+    /// Whether `name` was given with `table_name = "..."` rather than
+    /// derived from the model, in which case `OrmStruct::with_naming`
+    /// leaves it alone.
+    pub explicit_name: bool,
     pub columns: &'static [ColumnSchema],
+    /// Documentation emitted as `COMMENT ON TABLE` by `migrate()`.
+    pub comment: Option<&'static str>,
+    /// `#[slint(temporary)]`: created as a session-local `TEMPORARY` table.
+    pub temporary: bool,
+    /// `#[slint(unlogged)]`: created `UNLOGGED`, skipping the WAL for faster
+    /// writes at the cost of being emptied after a crash.
+    pub unlogged: bool,
+    /// `#[slint(blame)]`: has `created_by` / `updated_by` columns filled
+    /// from `OrmStruct::with_actor`.
+    pub blame: bool,
+    /// `#[slint(versioned)]`: every row version is kept in a
+    /// `<table>_history` table, readable with `Table::as_of`.
+    pub versioned: bool,
+    /// Rows of a lookup table declared with `#[slint]` on an enum: the
+    /// variants, which `migrate()` inserts into the key column.
+    pub lookup_values: &'static [&'static str],
+}
+
+/// SQL dialect `TableSchema::to_create_sql` renders for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// The dialect `migrate()` runs, and the only one the ORM connects to.
+    #[default]
+    Postgres,
+}
+
+/// Implemented by `#[slint]` for every model, giving generic code access to
+/// the model's schema.
+pub trait SlintModel {
+    fn slint_schema() -> TableSchema;
+}
+
+/// Implemented by the `New<Model>` insert builders `#[slint]` generates,
+/// tying each builder to the model it inserts.
+pub trait Insertable: serde::Serialize {
+    type Model;
+}
+
+impl ColumnSchema {
+    /// Column definition as used in `CREATE TABLE`.
+    pub(crate) fn definition(&self) -> String {
+        let mut col_def = format!("{} {}", self.name, self.sql_type);
+        if let Some(collation) = self.collation {
+            col_def.push_str(&format!(" COLLATE {}", quote_ident(collation)))
+        }
+        let timing = self.timing();
+        if self.primary {
+            col_def.push_str(" PRIMARY KEY");
+            col_def.push_str(timing)
+        }
+        if self.unique {
+            col_def.push_str(" UNIQUE");
+            col_def.push_str(timing)
+        }
+        if self.not_null {
+            col_def.push_str(" NOT NULL")
+        }
+        if self.serial {
+            col_def.push_str(" GENERATED ALWAYS AS IDENTITY")
+        }
+        if let Some(generated) = self.generated {
+            col_def.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", generated))
+        } else if let Some(default) = self.default {
+            col_def.push_str(&format!(" DEFAULT {}", default))
+        }
+        col_def
+    }
+
+    /// ` DEFERRABLE [INITIALLY DEFERRED]` for the column's key, unique and
+    /// foreign key constraints, or nothing.
+    fn timing(&self) -> &'static str {
+        if self.initially_deferred {
+            " DEFERRABLE INITIALLY DEFERRED"
+        } else if self.deferrable {
+            " DEFERRABLE"
+        } else {
+            ""
+        }
+    }
+
+    /// Placeholder `$index` for binding into this column. JSON values are
+    /// bound as text (or booleans), so non-text columns get an explicit cast
+    /// (`$index::INTEGER`) to reach their declared type.
+    pub fn placeholder(&self, index: usize) -> String {
+        match self.sql_type {
+            "TEXT" => format!("${}", index),
+            // Text binds straight into VARCHAR; a cast would silently truncate.
+            _ if self.length.is_some() => format!("${}", index),
+            sql_type => format!("${}::{}", index, sql_type),
+        }
+    }
+
+    /// The table and column named by `foreign_key`, accepting both
+    /// `table.column` and `table(column)`. The column is `None` when only the
+    /// table is given.
+    pub fn references(&self) -> Option<(&'static str, Option<&'static str>)> {
+        let fk = self.foreign_key?;
+        match fk.split_once(['.', '(']) {
+            Some((table, column)) => {
+                Some((table.trim(), Some(column.trim_end_matches(')').trim())))
+            }
+            None => Some((fk.trim(), None)),
+        }
+    }
+
+    /// Checks a value against the column's declared length, so oversized
+    /// strings are rejected instead of failing (or truncating) in the database.
+    pub fn validate(&self, value: &serde_json::Value) -> sqlx::Result<()> {
+        if let (Some(max), serde_json::Value::String(s)) = (self.length, value) {
+            let len = s.chars().count();
+            if len > max as usize {
+                return Err(sqlx::Error::InvalidArgument(format!(
+                    "value for column `{}` is {} characters long, exceeding its length of {}",
+                    self.name, len, max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TableSchema {
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Column list for default selects: `*`, or every column except
+    /// write-only hashed ones when the table has any.
+    pub fn select_list(&self) -> String {
+        if self.columns.iter().all(|c| c.hashed.is_none()) {
+            return "*".to_string();
+        }
+        self.columns
+            .iter()
+            .filter(|c| c.hashed.is_none())
+            .map(|c| c.name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// `CREATE TABLE IF NOT EXISTS` statement for the table.
+    pub(crate) fn create_statement(&self) -> String {
+        let kind = if self.temporary {
+            "TEMPORARY TABLE"
+        } else if self.unlogged {
+            "UNLOGGED TABLE"
+        } else {
+            "TABLE"
+        };
+        let cols: Vec<String> = self.columns.iter().map(ColumnSchema::definition).collect();
+        format!(
+            "CREATE {} IF NOT EXISTS {} ({})",
+            kind,
+            self.name,
+            cols.join(", ")
+        )
+    }
+
+    /// The statements `migrate()` runs for the table on an empty database:
+    /// the extensions it needs, `CREATE TABLE`, its indexes, its comments,
+    /// the rows of a lookup table and its foreign keys, each ending in `;`
+    /// and a newline. The foreign keys need the tables they refer to.
+    /// Needs no connection, so the DDL can be inspected or snapshot-tested.
+    /// The history of a `versioned` table is left out; see
+    /// `OrmStruct::dump_schema_sql`.
+    ///
+    /// ```
+    /// use slintrust::*;
+    ///
+    /// #[slint(table_name = "users")]
+    /// #[derive(Serialize, Deserialize)]
+    /// struct User {
+    ///     #[slint(primary_key)]
+    ///     id: String,
+    ///     email: String,
+    /// }
+    ///
+    /// assert_eq!(
+    ///     User::slint_schema().to_create_sql(Dialect::Postgres),
+    ///     "CREATE TABLE IF NOT EXISTS users (id TEXT PRIMARY KEY NOT NULL, email TEXT NOT NULL);\n",
+    /// );
+    /// ```
+    pub fn to_create_sql(&self, dialect: Dialect) -> String {
+        match dialect {
+            Dialect::Postgres => {
+                let mut sql = String::new();
+                let statements = self
+                    .extension_statements()
+                    .into_iter()
+                    .chain(std::iter::once(self.create_statement()))
+                    .chain(self.index_statements())
+                    .chain(self.comment_statements())
+                    .chain(self.seed_statements())
+                    .chain(self.foreign_key_statements());
+                for statement in statements {
+                    sql.push_str(&statement);
+                    sql.push_str(";\n");
+                }
+                sql
+            }
+        }
+    }
+
+    /// Every statement `migrate()` runs for the table: the extensions its
+    /// column types need, the table itself, the columns missing from it,
+    /// its indexes, its comments, the rows of lookup tables and, for
+    /// versioned tables, the history. `live` and `live_history` are the
+    /// columns the table and its history table already have, empty for
+    /// tables that don't exist yet.
+    pub(crate) fn ddl_statements(
+        &self,
+        live: &[String],
+        live_history: &[String],
+    ) -> sqlx::Result<Vec<String>> {
+        let mut statements = self.extension_statements();
+        statements.push(self.create_statement());
+        statements.extend(self.add_column_statements(live));
+        if !live_history.is_empty() {
+            for c in self.missing_columns(live_history) {
+                statements.push(format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+                    self.history_table(),
+                    c.name,
+                    c.sql_type
+                ));
+            }
+        }
+        statements.extend(self.index_statements());
+        statements.extend(self.comment_statements());
+        statements.extend(self.seed_statements());
+        statements.extend(self.history_statements()?);
+        Ok(statements)
+    }
+
+    /// Columns of the schema that aren't among `live`.
+    pub(crate) fn missing_columns<'a>(
+        &self,
+        live: &'a [String],
+    ) -> impl Iterator<Item = &'static ColumnSchema> + 'a {
+        self.columns
+            .iter()
+            .filter(move |c| !live.iter().any(|l| l == c.name))
+    }
+
+    /// `ALTER TABLE ... ADD COLUMN` for each column of the schema missing
+    /// from an existing table with columns `live`; nothing when the table
+    /// doesn't exist yet (`live` is empty).
+    pub fn add_column_statements(&self, live: &[String]) -> Vec<String> {
+        if live.is_empty() {
+            return Vec::new();
+        }
+        self.missing_columns(live)
+            .map(|c| {
+                format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {}",
+                    self.name,
+                    c.definition()
+                )
+            })
+            .collect()
+    }
+
+    /// `CREATE EXTENSION` for the extension types among the columns:
+    /// `citext`, `hstore` and PostGIS' `GEOMETRY`.
+    fn extension_statements(&self) -> Vec<String> {
+        let uses = |prefix: &str| {
+            self.columns.iter().any(|c| {
+                c.sql_type
+                    .get(..prefix.len())
+                    .is_some_and(|t| t.eq_ignore_ascii_case(prefix))
+            })
+        };
+        let mut statements = Vec::new();
+        if uses("CITEXT") {
+            statements.push("CREATE EXTENSION IF NOT EXISTS citext".to_string());
+        }
+        if uses("HSTORE") {
+            statements.push("CREATE EXTENSION IF NOT EXISTS hstore".to_string());
+        }
+        if uses("GEOMETRY") {
+            statements.push("CREATE EXTENSION IF NOT EXISTS postgis".to_string());
+        }
+        if self.columns.iter().any(|c| c.trigram) {
+            statements.push("CREATE EXTENSION IF NOT EXISTS pg_trgm".to_string());
+        }
+        statements
+    }
+
+    /// `CREATE UNIQUE INDEX` on `lower(column)` for each `unique_ci`
+    /// column, named `<table>_<column>_ci_key`, and a `gin_trgm_ops` GIN
+    /// index for each `trigram` column, named `<table>_<column>_trgm_idx`.
+    /// Indexes live in their table's schema, so `<table>` is the name
+    /// without it.
+    pub fn index_statements(&self) -> Vec<String> {
+        let index = |column: &str, suffix: &str| {
+            quote_ident(&format!(
+                "{}_{}_{}",
+                self.unqualified_name(),
+                column,
+                suffix
+            ))
+        };
+        let unique_ci = self.columns.iter().filter(|c| c.unique_ci).map(|c| {
+            format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS {index} ON {table} (lower({column}))",
+                index = index(c.name, "ci_key"),
+                table = self.name,
+                column = c.name
+            )
+        });
+        let trigram = self.columns.iter().filter(|c| c.trigram).map(|c| {
+            format!(
+                "CREATE INDEX IF NOT EXISTS {index} \
+                 ON {table} USING GIN ({column} gin_trgm_ops)",
+                index = index(c.name, "trgm_idx"),
+                table = self.name,
+                column = c.name
+            )
+        });
+        unique_ci.chain(trigram).collect()
+    }
+
+    /// `INSERT` of the [`lookup_values`](Self::lookup_values) missing from
+    /// the table. Rows of removed variants are kept, as other tables may
+    /// still refer to them.
+    pub fn seed_statements(&self) -> Vec<String> {
+        let Some(key) = self.columns.iter().find(|c| c.primary) else {
+            return Vec::new();
+        };
+        if self.lookup_values.is_empty() {
+            return Vec::new();
+        }
+        let rows: Vec<String> = self
+            .lookup_values
+            .iter()
+            .map(|v| format!("({})", quote_literal(v)))
+            .collect();
+        vec![format!(
+            "INSERT INTO {} ({}) VALUES {} ON CONFLICT DO NOTHING",
+            self.name,
+            key.name,
+            rows.join(", ")
+        )]
+    }
+
+    /// `COMMENT ON TABLE/COLUMN` statements for every documented part of
+    /// the table.
+    pub fn comment_statements(&self) -> Vec<String> {
+        let mut statements = Vec::new();
+        if let Some(comment) = self.comment {
+            statements.push(format!(
+                "COMMENT ON TABLE {} IS {}",
+                self.name,
+                quote_literal(comment)
+            ));
+        }
+        for c in self.columns {
+            if let Some(comment) = c.comment {
+                statements.push(format!(
+                    "COMMENT ON COLUMN {}.{} IS {}",
+                    self.name,
+                    c.name,
+                    quote_literal(comment)
+                ));
+            }
+        }
+        statements
+    }
+
+    /// `ALTER TABLE ... ADD CONSTRAINT ... FOREIGN KEY` for each
+    /// `foreign_key` column, referring to the tables as declared. They run
+    /// once every table exists, so tables may refer to each other in a
+    /// cycle.
+    pub fn foreign_key_statements(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter_map(|c| {
+                let (parent, _) = c.references()?;
+                Some(self.foreign_key_statement(c, parent))
+            })
+            .collect()
+    }
+
+    /// Name of the foreign key constraint of `column`,
+    /// `<table>_<column>_fkey` as Postgres would name it, unquoted.
+    pub(crate) fn foreign_key_name(&self, column: &str) -> String {
+        format!("{}_{}_fkey", self.unqualified_name(), column)
+    }
+
+    /// The foreign key constraint of `column`, referring to the table
+    /// `parent` and, when `foreign_key` names one, its column.
+    pub(crate) fn foreign_key_statement(&self, column: &ColumnSchema, parent: &str) -> String {
+        let parent_column = column
+            .references()
+            .and_then(|(_, c)| c)
+            .map_or_else(String::new, |c| format!(" ({})", c));
+        format!(
+            "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {}{}{}",
+            self.name,
+            quote_ident(&self.foreign_key_name(column.name)),
+            column.name,
+            parent,
+            parent_column,
+            column.timing()
+        )
+    }
+
+    /// The table's name without its schema: `users` for `app.users`.
+    pub fn unqualified_name(&self) -> &'static str {
+        self.name.rsplit('.').next().unwrap_or(self.name)
+    }
+
+    /// The history table of a `versioned` table as SQL refers to it: in
+    /// the table's schema and quoted, e.g. `app."users_history"`.
+    pub fn history_table(&self) -> String {
+        history_table(self.name)
+    }
+
+    /// Statements creating the history table of a `versioned` table and
+    /// the trigger that fills it: each row version is stored with the
+    /// period it was current, `slint_valid_from` to `slint_valid_to`
+    /// (`NULL` while it still is). Rows already in the table are copied
+    /// in when the history is first created.
+    pub fn history_statements(&self) -> sqlx::Result<Vec<String>> {
+        if !self.versioned {
+            return Ok(Vec::new());
+        }
+        let key = self.key_column().ok_or_else(|| {
+            sqlx::Error::InvalidArgument(format!(
+                "versioned table `{}` needs a primary key",
+                self.name
+            ))
+        })?;
+        let history = self.history_table();
+        // The trigger is named without a schema, and its function lives in
+        // the table's.
+        let trigger = quote_ident(&format!("{}_history", self.unqualified_name()));
+        let function = in_schema_of(
+            self.name,
+            &quote_ident(&format!("{}_history_fn", self.unqualified_name())),
+        );
+        // Columns are named rather than copied with `*`, so the history stays
+        // correct after `migrate()` adds columns to both tables.
+        let columns: Vec<&str> = self.columns.iter().map(|c| c.name).collect();
+        let new_values: Vec<String> = columns.iter().map(|c| format!("NEW.{}", c)).collect();
+        let columns = columns.join(", ");
+        Ok(vec![
+            format!(
+                "CREATE TABLE IF NOT EXISTS {history} (LIKE {table}, \
+                 slint_valid_from TIMESTAMPTZ NOT NULL, slint_valid_to TIMESTAMPTZ)",
+                history = history,
+                table = self.name
+            ),
+            format!(
+                "INSERT INTO {history} ({columns}, slint_valid_from, slint_valid_to) \
+                 SELECT {columns}, now(), NULL FROM {table} \
+                 WHERE NOT EXISTS (SELECT 1 FROM {history})",
+                history = history,
+                columns = columns,
+                table = self.name
+            ),
+            format!(
+                "CREATE OR REPLACE FUNCTION {function}() RETURNS trigger AS $$ \
+                 BEGIN \
+                 IF TG_OP IN ('UPDATE', 'DELETE') THEN \
+                 UPDATE {history} SET slint_valid_to = now() \
+                 WHERE {key} = OLD.{key} AND slint_valid_to IS NULL; \
+                 END IF; \
+                 IF TG_OP IN ('INSERT', 'UPDATE') THEN \
+                 INSERT INTO {history} ({columns}, slint_valid_from, slint_valid_to) \
+                 VALUES ({new_values}, now(), NULL); \
+                 END IF; \
+                 RETURN NULL; \
+                 END $$ LANGUAGE plpgsql",
+                function = function,
+                history = history,
+                key = key,
+                columns = columns,
+                new_values = new_values.join(", ")
+            ),
+            format!(
+                "DROP TRIGGER IF EXISTS {trigger} ON {table}",
+                trigger = trigger,
+                table = self.name
+            ),
+            format!(
+                "CREATE TRIGGER {trigger} AFTER INSERT OR UPDATE OR DELETE ON {table} \
+                 FOR EACH ROW EXECUTE FUNCTION {function}()",
+                trigger = trigger,
+                table = self.name,
+                function = function
+            ),
+        ])
+    }
+
+    /// Validates `value` for `column` (see [`ColumnSchema::validate`]);
+    /// unknown columns are left to the database.
+    pub fn validate(&self, column: &str, value: &serde_json::Value) -> sqlx::Result<()> {
+        self.column(column).map_or(Ok(()), |c| c.validate(value))
+    }
+
+    /// `InvalidArgument` for the first of `columns` the table doesn't
+    /// have, so names from a JSON object never reach the SQL unchecked.
+    pub(crate) fn check_columns(
+        &self,
+        columns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> sqlx::Result<()> {
+        match columns
+            .into_iter()
+            .find(|c| self.column(c.as_ref()).is_none())
+        {
+            Some(column) => Err(sqlx::Error::InvalidArgument(format!(
+                "table `{}` has no column `{}`",
+                self.name,
+                column.as_ref()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Placeholder `$index` for `column`, cast to its declared type when
+    /// the column is known (see [`ColumnSchema::placeholder`]).
+    pub fn placeholder(&self, column: &str, index: usize) -> String {
+        self.column(column)
+            .map_or_else(|| format!("${}", index), |c| c.placeholder(index))
+    }
+
+    /// Columns written by inserts, i.e. all but serial and generated
+    /// columns.
+    pub fn insert_columns(&self) -> impl Iterator<Item = &'static ColumnSchema> {
+        self.columns
+            .iter()
+            .filter(|c| !c.serial && c.generated.is_none())
+    }
+
+    /// The column used to identify rows: the primary key if one is declared,
+    /// otherwise a column named `id`.
+    pub fn key_column(&self) -> Option<&'static str> {
+        self.columns
+            .iter()
+            .find(|c| c.primary)
+            .or_else(|| self.columns.iter().find(|c| c.name == "id"))
+            .map(|c| c.name)
+    }
+}
+
+/// The table's `to_create_sql(Dialect::Postgres)`.
+impl fmt::Display for TableSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_create_sql(Dialect::Postgres))
+    }
+}
+
+/// Indexes of `tables` ordered so every table comes after its parents,
+/// `parents[i]` being the indexes of the tables `tables[i]` refers to.
+/// Tables keep their given order where no reference says otherwise.
+/// References of a table to itself are ignored, and so is the reference
+/// closing a cycle: the first table of the cycle reached comes first.
+pub(crate) fn dependency_order(tables: &[String], parents: &[Vec<usize>]) -> Vec<usize> {
+    fn visit(i: usize, parents: &[Vec<usize>], path: &mut Vec<usize>, order: &mut Vec<usize>) {
+        if order.contains(&i) || path.contains(&i) {
+            return;
+        }
+        path.push(i);
+        for &parent in &parents[i] {
+            visit(parent, parents, path, order);
+        }
+        path.pop();
+        order.push(i);
+    }
+
+    let mut order = Vec::with_capacity(tables.len());
+    for i in 0..tables.len() {
+        visit(i, parents, &mut Vec::new(), &mut order);
+    }
+    order
+}
+
+/// The history table of `table` (see [`TableSchema::history_table`]).
+pub(crate) fn history_table(table: &str) -> String {
+    let name = table.rsplit('.').next().unwrap_or(table);
+    in_schema_of(table, &quote_ident(&format!("{}_history", name)))
+}
+
+/// The identifier `name` in the schema `table` is qualified with, if any.
+pub(crate) fn in_schema_of(table: &str, name: &str) -> String {
+    match table.rsplit_once('.') {
+        Some((schema, _)) => format!("{}.{}", schema, name),
+        None => name.to_string(),
+    }
+}
+
+/// Quotes `value` as a SQL string literal, doubling embedded quotes.
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes `name` as a SQL identifier, keeping its case and doubling
+/// embedded quotes.
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Redacts a masked column value: strings keep only their last four
+/// characters (`"****6789"`), anything else becomes `null`.
+pub fn mask_value(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            if chars.len() <= 4 {
+                serde_json::Value::String("****".to_string())
+            } else {
+                let tail: String = chars[chars.len() - 4..].iter().collect();
+                serde_json::Value::String(format!("****{}", tail))
+            }
+        }
+        _ => serde_json::Value::Null,
+    }
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A `TEXT NOT NULL` column, to adjust with struct update syntax.
+    pub(crate) const fn column(name: &'static str) -> ColumnSchema {
+        ColumnSchema {
+            name,
+            sql_type: "TEXT",
+            primary: false,
+            unique: false,
+            unique_ci: false,
+            deferrable: false,
+            initially_deferred: false,
+            not_null: true,
+            uuid: false,
+            length: None,
+            comment: None,
+            collation: None,
+            encrypted: false,
+            hashed: None,
+            masked: false,
+            default: None,
+            generated: None,
+            serial: false,
+            foreign_key: None,
+            trigram: false,
+        }
+    }
+
+    pub(crate) fn table(name: &'static str, columns: &'static [ColumnSchema]) -> TableSchema {
+        TableSchema {
+            name,
+            model: name,
+            explicit_name: true,
+            columns,
+            comment: None,
+            temporary: false,
+            unlogged: false,
+            blame: false,
+            versioned: false,
+            lookup_values: &[],
+        }
+    }
+
+    static USERS: [ColumnSchema; 3] = [
+        ColumnSchema {
+            sql_type: "BIGINT",
+            primary: true,
+            serial: true,
+            ..column("id")
+        },
+        ColumnSchema {
+            unique_ci: true,
+            comment: Some("Login, compared ignoring case"),
+            ..column("email")
+        },
+        ColumnSchema {
+            trigram: true,
+            default: Some("''"),
+            ..column("bio")
+        },
+    ];
+
+    #[test]
+    fn create_sql_lists_every_statement_in_migration_order() {
+        let users = TableSchema {
+            comment: Some("People who can sign in"),
+            ..table("users", &USERS)
+        };
+        assert_eq!(
+            users.to_create_sql(Dialect::Postgres),
+            "CREATE EXTENSION IF NOT EXISTS pg_trgm;\n\
+             CREATE TABLE IF NOT EXISTS users (\
+             id BIGINT PRIMARY KEY NOT NULL GENERATED ALWAYS AS IDENTITY, \
+             email TEXT NOT NULL, bio TEXT NOT NULL DEFAULT '');\n\
+             CREATE UNIQUE INDEX IF NOT EXISTS \"users_email_ci_key\" ON users (lower(email));\n\
+             CREATE INDEX IF NOT EXISTS \"users_bio_trgm_idx\" ON users USING GIN (bio gin_trgm_ops);\n\
+             COMMENT ON TABLE users IS 'People who can sign in';\n\
+             COMMENT ON COLUMN users.email IS 'Login, compared ignoring case';\n"
+        );
+    }
+
+    #[test]
+    fn create_sql_seeds_lookup_tables() {
+        static STATUS: [ColumnSchema; 1] = [ColumnSchema {
+            primary: true,
+            ..column("name")
+        }];
+        let status = TableSchema {
+            lookup_values: &["open", "won't fix"],
+            ..table("status", &STATUS)
+        };
+        assert_eq!(
+            status.to_create_sql(Dialect::Postgres),
+            "CREATE TABLE IF NOT EXISTS status (name TEXT PRIMARY KEY NOT NULL);\n\
+             INSERT INTO status (name) VALUES ('open'), ('won''t fix') ON CONFLICT DO NOTHING;\n"
+        );
+    }
+
+    #[test]
+    fn names_derived_from_qualified_tables_stay_in_their_schema() {
+        let users = TableSchema {
+            versioned: true,
+            ..table("app.users", &USERS)
+        };
+        assert_eq!(users.unqualified_name(), "users");
+        assert_eq!(users.history_table(), "app.\"users_history\"");
+        assert_eq!(
+            users.index_statements()[0],
+            "CREATE UNIQUE INDEX IF NOT EXISTS \"users_email_ci_key\" ON app.users (lower(email))"
+        );
+        let history = users.history_statements().unwrap();
+        assert!(
+            history[0]
+                .starts_with("CREATE TABLE IF NOT EXISTS app.\"users_history\" (LIKE app.users, ")
+        );
+        assert!(
+            history[2].starts_with(
+                "CREATE OR REPLACE FUNCTION app.\"users_history_fn\"() RETURNS trigger"
+            )
+        );
+        assert_eq!(
+            history[3],
+            "DROP TRIGGER IF EXISTS \"users_history\" ON app.users"
+        );
+        assert!(history[4].starts_with("CREATE TRIGGER \"users_history\" AFTER INSERT"));
+        assert!(history[4].ends_with("EXECUTE FUNCTION app.\"users_history_fn\"()"));
+    }
+
+    #[test]
+    fn dependency_order_puts_parents_first() {
+        let tables = ["comments", "posts", "users"].map(String::from);
+        let parents = [vec![1, 2], vec![2], vec![]];
+        assert_eq!(dependency_order(&tables, &parents), [2, 1, 0]);
+    }
+
+    #[test]
+    fn dependency_order_keeps_unrelated_tables_in_place_and_ignores_self_references() {
+        let tables = ["a", "b", "c"].map(String::from);
+        let parents = [vec![0], vec![], vec![]];
+        assert_eq!(dependency_order(&tables, &parents), [0, 1, 2]);
+    }
+
+    #[test]
+    fn dependency_order_breaks_cycles() {
+        let tables = ["users", "teams", "logins"].map(String::from);
+        let parents = [vec![1], vec![0], vec![0]];
+        assert_eq!(dependency_order(&tables, &parents), [1, 0, 2]);
+    }
+
+    #[test]
+    fn foreign_keys_are_added_after_the_tables() {
+        static MEMBERS: [ColumnSchema; 2] = [
+            ColumnSchema {
+                foreign_key: Some("teams.id"),
+                ..column("team_id")
+            },
+            ColumnSchema {
+                foreign_key: Some("users"),
+                deferrable: true,
+                initially_deferred: true,
+                ..column("user_id")
+            },
+        ];
+        assert_eq!(
+            table("app.members", &MEMBERS).to_create_sql(Dialect::Postgres),
+            "CREATE TABLE IF NOT EXISTS app.members (team_id TEXT NOT NULL, user_id TEXT NOT NULL);\n\
+             ALTER TABLE app.members ADD CONSTRAINT \"members_team_id_fkey\" \
+             FOREIGN KEY (team_id) REFERENCES teams (id);\n\
+             ALTER TABLE app.members ADD CONSTRAINT \"members_user_id_fkey\" \
+             FOREIGN KEY (user_id) REFERENCES users DEFERRABLE INITIALLY DEFERRED;\n"
+        );
+    }
+}
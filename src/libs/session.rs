@@ -0,0 +1,202 @@
+use crate::row::row_to_json;
+use crate::{OrmStruct, SqlValue};
+use serde::de::DeserializeOwned;
+use sqlx::postgres::PgConnection;
+use sqlx::{Postgres, Transaction};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Boxed future returned by the closures passed to `OrmStruct::session`.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Transaction isolation level, see `TxOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    fn as_sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+/// Options for `OrmStruct::begin`, emitted as `SET TRANSACTION ...`.
+/// The default is the server's isolation level, read-write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxOptions {
+    pub isolation_level: Option<IsolationLevel>,
+    pub read_only: bool,
+}
+
+impl TxOptions {
+    /// `SET TRANSACTION ...` for these options, or `None` for the defaults.
+    pub(crate) fn to_sql(self) -> Option<String> {
+        let mut modes = Vec::new();
+        if let Some(level) = self.isolation_level {
+            modes.push(format!("ISOLATION LEVEL {}", level.as_sql()));
+        }
+        if self.read_only {
+            modes.push("READ ONLY".to_string());
+        }
+        if modes.is_empty() {
+            None
+        } else {
+            Some(format!("SET TRANSACTION {}", modes.join(", ")))
+        }
+    }
+}
+
+/// How `OrmStruct::transaction_with_retry` re-runs transactions that hit
+/// a serialization failure or deadlock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further one.
+    pub base_delay: Duration,
+    /// Upper bound for the delay between attempts.
+    pub max_delay: Duration,
+    pub isolation_level: IsolationLevel,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(20),
+            max_delay: Duration::from_secs(1),
+            isolation_level: IsolationLevel::Serializable,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (0-based), with up to 50% jitter so
+    /// conflicting clients don't retry in lockstep.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        delay.mul_f64(1.0 + rand::random::<f64>() * 0.5)
+    }
+}
+
+/// Whether `err` is a serialization failure (`40001`) or deadlock
+/// (`40P01`), i.e. the transaction can safely be run again.
+pub(crate) fn is_retryable(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "40001" || code == "40P01")
+}
+
+/// One pooled connection running a transaction, from `OrmStruct::session`
+/// or `OrmStruct::begin`.
+///
+/// Settings made with [`set_local`](Self::set_local) apply to every query
+/// of the session and are discarded when it ends. A session dropped
+/// without [`commit`](Self::commit) rolls back.
+pub struct Session {
+    orm: OrmStruct,
+    tx: Transaction<'static, Postgres>,
+}
+
+impl Session {
+    pub(crate) fn new(orm: OrmStruct, tx: Transaction<'static, Postgres>) -> Self {
+        Self { orm, tx }
+    }
+
+    pub(crate) fn orm(&self) -> &OrmStruct {
+        &self.orm
+    }
+
+    /// `SET LOCAL name = value`, e.g. `app.tenant_id` for row-level
+    /// security policies. The value is bound, not interpolated.
+    pub async fn set_local(&mut self, name: &str, value: &str) -> sqlx::Result<()> {
+        self.orm
+            .statement("SELECT set_config($1, $2, true)")
+            .bind(name)
+            .bind(value)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// `SET CONSTRAINTS ALL DEFERRED`: every deferrable constraint (see
+    /// `#[slint(deferrable)]`) is checked at commit instead of after each
+    /// statement, so rows referring to each other can be inserted in any
+    /// order.
+    pub async fn set_constraints_deferred(&mut self) -> sqlx::Result<()> {
+        self.orm
+            .statement("SET CONSTRAINTS ALL DEFERRED")
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Runs a statement with positional `binds`; returns the rows affected.
+    pub async fn execute(&mut self, sql: &str, binds: &[SqlValue]) -> sqlx::Result<u64> {
+        let mut query = self.orm.statement(sql);
+        for v in binds {
+            query = v.clone().bind(query);
+        }
+        Ok(query.execute(&mut *self.tx).await?.rows_affected())
+    }
+
+    /// Runs a select with positional `binds` and returns each row as a JSON
+    /// object, like `OrmStruct::raw_fetch_json`.
+    pub async fn fetch_json(
+        &mut self,
+        sql: &str,
+        binds: &[SqlValue],
+    ) -> sqlx::Result<Vec<serde_json::Value>> {
+        let mut query = self.orm.statement(sql);
+        for v in binds {
+            query = v.clone().bind(query);
+        }
+        let rows = query.fetch_all(&mut *self.tx).await?;
+        Ok(rows.iter().map(row_to_json).collect())
+    }
+
+    /// Selects rows of `table_name` and decodes them into `T`, decrypting
+    /// and masking columns like table queries do.
+    pub async fn fetch_all<T>(
+        &mut self,
+        table_name: &str,
+        sql: &str,
+        binds: &[SqlValue],
+    ) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut query = self.orm.statement(sql);
+        for v in binds {
+            query = v.clone().bind(query);
+        }
+        let rows = query.fetch_all(&mut *self.tx).await?;
+        rows.iter()
+            .map(|r| self.orm.decode::<T>(table_name, r))
+            .collect()
+    }
+
+    /// The underlying connection, for running `sqlx` queries directly.
+    pub fn connection(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+
+    pub async fn commit(self) -> sqlx::Result<()> {
+        self.tx.commit().await
+    }
+
+    pub async fn rollback(self) -> sqlx::Result<()> {
+        self.tx.rollback().await
+    }
+}
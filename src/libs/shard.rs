@@ -0,0 +1,364 @@
+#[cfg(feature = "migrate")]
+use crate::MigrationReport;
+use crate::{OrmStruct, TableSchema};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Points each shard places on the hash ring. More points spread keys more
+/// evenly between shards.
+const VIRTUAL_NODES: usize = 128;
+
+/// Routes rows across several databases holding the same tables.
+///
+/// Each table is split by a shard key column (see
+/// [`with_shard_key`](Self::with_shard_key)); a row lives on the shard its
+/// key hashes to on a consistent-hash ring, so adding a shard moves only
+/// about `1/N` of the keys. Writes take the key from the record, reads take
+/// it as an argument, and [`get_all_shards`](Self::get_all_shards) queries
+/// every shard at once.
+///
+/// # Example
+/// ```ignore
+/// let orm = ShardedOrm::new(vec![url_a, url_b, url_c], schemas)
+///     .with_shard_key("orders", "user_id");
+/// orm.connect().await?;
+/// orm.migrate().await?;
+///
+/// orm.insert("orders", &order).await?; // lands on the shard of order.user_id
+/// let mine: Vec<Order> = orm.find("orders", &user_id, "user_id", &user_id).await?;
+/// let all: Vec<Order> = orm.get_all_shards("orders").await?;
+/// ```
+#[derive(Clone)]
+pub struct ShardedOrm {
+    shards: Vec<OrmStruct>,
+    /// `(point, shard index)`, sorted by point.
+    ring: Vec<(u64, usize)>,
+    shard_keys: HashMap<String, String>,
+}
+
+impl ShardedOrm {
+    /// One shard per URL, each with the same `schemas`. The order of the URLs
+    /// decides which keys each shard owns, so keep it stable.
+    pub fn new(database_urls: Vec<String>, schemas: Vec<TableSchema>) -> Self {
+        Self::from_shards(
+            database_urls
+                .into_iter()
+                .map(|url| OrmStruct::new(url, schemas.clone()))
+                .collect(),
+        )
+    }
+
+    /// Shards from already configured ORMs, e.g. with encryption keys or
+    /// options set.
+    ///
+    /// # Panics
+    /// If `shards` is empty.
+    pub fn from_shards(shards: Vec<OrmStruct>) -> Self {
+        assert!(!shards.is_empty(), "ShardedOrm needs at least one shard");
+        let mut ring: Vec<(u64, usize)> = (0..shards.len())
+            .flat_map(|shard| {
+                (0..VIRTUAL_NODES)
+                    .map(move |node| (hash(&format!("shard-{}-{}", shard, node)), shard))
+            })
+            .collect();
+        ring.sort_unstable();
+        Self {
+            shards,
+            ring,
+            shard_keys: HashMap::new(),
+        }
+    }
+
+    /// Splits `table` by `column`. Every table written through this handle
+    /// needs a shard key.
+    pub fn with_shard_key(mut self, table: &str, column: &str) -> Self {
+        self.shard_keys
+            .insert(table.to_string(), column.to_string());
+        self
+    }
+
+    pub fn shards(&self) -> &[OrmStruct] {
+        &self.shards
+    }
+
+    /// The shard owning `key`. Use it for anything the router doesn't wrap,
+    /// such as sessions or the query builder.
+    pub fn shard(&self, key: &str) -> &OrmStruct {
+        let point = hash(key);
+        let i = self.ring.partition_point(|&(p, _)| p < point);
+        let (_, shard) = self.ring[i % self.ring.len()];
+        &self.shards[shard]
+    }
+
+    /// The shard owning `item`, found from its value in `table`'s shard key
+    /// column.
+    pub fn shard_for<T: Serialize>(&self, table: &str, item: &T) -> sqlx::Result<&OrmStruct> {
+        let column = self.shard_keys.get(table).ok_or_else(|| {
+            sqlx::Error::Configuration(format!("table `{}` has no shard key", table).into())
+        })?;
+        let row = serde_json::to_value(item).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        let key = match row.get(column) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => {
+                return Err(sqlx::Error::InvalidArgument(format!(
+                    "record for `{}` has no `{}` to shard by",
+                    table, column
+                )));
+            }
+            // Numbers hash as their text, the same key `shard` gets.
+            Some(other) => other.to_string(),
+        };
+        Ok(self.shard(&key))
+    }
+
+    // -------- Lifecycle --------
+    pub async fn connect(&self) -> sqlx::Result<()> {
+        for shard in &self.shards {
+            shard.connect().await?;
+        }
+        Ok(())
+    }
+
+    /// Migrates every shard, returning their reports in shard order.
+    #[cfg(feature = "migrate")]
+    pub async fn migrate(&self) -> sqlx::Result<Vec<MigrationReport>> {
+        let mut reports = Vec::with_capacity(self.shards.len());
+        for shard in &self.shards {
+            reports.push(shard.migrate().await?);
+        }
+        Ok(reports)
+    }
+
+    pub async fn close(&self) {
+        for shard in &self.shards {
+            shard.close().await;
+        }
+    }
+
+    // -------- Routed operations --------
+    pub async fn insert<T>(&self, table_name: &str, item: &T) -> sqlx::Result<()>
+    where
+        T: Serialize,
+    {
+        self.shard_for(table_name, item)?
+            .insert(table_name, item)
+            .await
+    }
+
+    /// Updates the row matching `column = value` on `item`'s shard. `item`
+    /// must carry its shard key, and changing the key doesn't move the row.
+    pub async fn update<T>(
+        &self,
+        table_name: &str,
+        column: &str,
+        value: &str,
+        item: &T,
+    ) -> sqlx::Result<()>
+    where
+        T: Serialize,
+    {
+        self.shard_for(table_name, item)?
+            .update(table_name, column, value, item)
+            .await
+    }
+
+    pub async fn first<T>(
+        &self,
+        table_name: &str,
+        shard_key: &str,
+        column: &str,
+        value: &str,
+    ) -> sqlx::Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.shard(shard_key).first(table_name, column, value).await
+    }
+
+    pub async fn find<T>(
+        &self,
+        table_name: &str,
+        shard_key: &str,
+        column: &str,
+        value: &str,
+    ) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.shard(shard_key).find(table_name, column, value).await
+    }
+
+    pub async fn delete(
+        &self,
+        table_name: &str,
+        shard_key: &str,
+        column: &str,
+        value: &str,
+    ) -> sqlx::Result<()> {
+        self.shard(shard_key)
+            .delete(table_name, column, value)
+            .await
+    }
+
+    // -------- Fan-out --------
+    /// Every row of `table_name` from all shards, queried concurrently and
+    /// concatenated in shard order.
+    pub async fn get_all_shards<T>(&self, table_name: &str) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let tasks: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.clone();
+                let table = table_name.to_string();
+                tokio::spawn(async move { shard.get_all::<T>(&table).await })
+            })
+            .collect();
+        collect(tasks).await
+    }
+
+    /// Rows matching `column = value` from all shards, for lookups by
+    /// anything other than the shard key.
+    pub async fn find_all_shards<T>(
+        &self,
+        table_name: &str,
+        column: &str,
+        value: &str,
+    ) -> sqlx::Result<Vec<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let tasks: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.clone();
+                let (table, column, value) = (
+                    table_name.to_string(),
+                    column.to_string(),
+                    value.to_string(),
+                );
+                tokio::spawn(async move { shard.find::<T>(&table, &column, &value).await })
+            })
+            .collect();
+        collect(tasks).await
+    }
+}
+
+/// Awaits per-shard queries in order and concatenates their rows.
+async fn collect<T>(
+    tasks: Vec<tokio::task::JoinHandle<sqlx::Result<Vec<T>>>>,
+) -> sqlx::Result<Vec<T>> {
+    let mut rows = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok(shard_rows) => rows.extend(shard_rows?),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    Ok(rows)
+}
+
+/// 64-bit FNV-1a, finished with MurmurHash3's mixer so that keys differing
+/// only in their last characters (`u1`, `u2`) still land far apart on the
+/// ring. Unlike `DefaultHasher` the output is fixed, so keys stay on the same
+/// shard across builds and Rust versions.
+fn hash(key: &str) -> u64 {
+    let mut h = key.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sharded(n: usize) -> ShardedOrm {
+        let urls = (0..n)
+            .map(|i| format!("postgres://localhost/shard{}", i))
+            .collect();
+        ShardedOrm::new(urls, Vec::new()).with_shard_key("orders", "user_id")
+    }
+
+    /// Index of the shard owning `key`.
+    fn owner(orm: &ShardedOrm, key: &str) -> usize {
+        let shard = orm.shard(key);
+        orm.shards()
+            .iter()
+            .position(|s| std::ptr::eq(s, shard))
+            .unwrap()
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        // Changing the hash moves keys between shards of existing deployments.
+        assert_eq!(hash(""), 17280346270528514342);
+        assert_eq!(hash("user-1"), 4729620347622785477);
+    }
+
+    #[test]
+    fn keys_spread_over_every_shard() {
+        let orm = sharded(4);
+        let mut counts = [0; 4];
+        for i in 0..4000 {
+            counts[owner(&orm, &format!("user-{}", i))] += 1;
+        }
+        assert!(
+            counts.iter().all(|&n| (600..1400).contains(&n)),
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn adding_a_shard_moves_only_its_share_of_keys() {
+        let (before, after) = (sharded(4), sharded(5));
+        let keys: Vec<String> = (0..4000).map(|i| format!("user-{}", i)).collect();
+        let moved: Vec<&String> = keys
+            .iter()
+            .filter(|k| owner(&before, k) != owner(&after, k))
+            .collect();
+        // Keys only move to the new shard, about a fifth of them.
+        assert!(moved.iter().all(|k| owner(&after, k) == 4));
+        assert!(
+            (500..1200).contains(&moved.len()),
+            "{} keys moved",
+            moved.len()
+        );
+    }
+
+    #[test]
+    fn records_route_by_their_shard_key() {
+        let orm = sharded(3);
+        let shard = orm
+            .shard_for("orders", &json!({ "id": 1, "user_id": "u7" }))
+            .unwrap();
+        assert!(std::ptr::eq(shard, orm.shard("u7")));
+        // Numbers route like their text.
+        let shard = orm.shard_for("orders", &json!({ "user_id": 42 })).unwrap();
+        assert!(std::ptr::eq(shard, orm.shard("42")));
+    }
+
+    #[test]
+    fn records_without_a_shard_key_are_rejected() {
+        let orm = sharded(2);
+        assert!(matches!(
+            orm.shard_for("orders", &json!({ "id": 1 })),
+            Err(sqlx::Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            orm.shard_for("users", &json!({ "id": 1 })),
+            Err(sqlx::Error::Configuration(_))
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use sqlx::postgres::{PgQueryResult, PgRow};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Latencies kept per `(table, operation)` for the p95; older ones are
+/// dropped first.
+pub const LATENCY_SAMPLES: usize = 1024;
+
+/// Kind of statement counted by `OrmStruct::query_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Operation {
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Aggregated executions of one operation on one table, from
+/// `OrmStruct::query_stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStat {
+    pub table: String,
+    pub operation: Operation,
+    /// Executions, including failed ones.
+    pub count: u64,
+    /// Time spent in all executions.
+    pub total: Duration,
+    pub mean: Duration,
+    /// 95th percentile over the last [`LATENCY_SAMPLES`] executions.
+    pub p95: Duration,
+    /// Rows returned, or affected by writes without `RETURNING`.
+    pub rows: u64,
+}
+
+#[derive(Default)]
+struct Entry {
+    count: u64,
+    total: Duration,
+    rows: u64,
+    recent: VecDeque<Duration>,
+}
+
+/// Per-ORM collector behind `OrmStruct::query_stats`, shared by every
+/// handle cloned from the ORM.
+#[derive(Default)]
+pub(crate) struct QueryStats {
+    entries: Mutex<HashMap<(String, Operation), Entry>>,
+}
+
+impl QueryStats {
+    pub(crate) fn record(&self, table: &str, operation: Operation, elapsed: Duration, rows: u64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.entry((table.to_string(), operation)).or_default();
+        entry.count += 1;
+        entry.total += elapsed;
+        entry.rows += rows;
+        if entry.recent.len() == LATENCY_SAMPLES {
+            entry.recent.pop_front();
+        }
+        entry.recent.push_back(elapsed);
+    }
+
+    /// Every entry, busiest (by total time) first.
+    pub(crate) fn snapshot(&self) -> Vec<QueryStat> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats: Vec<QueryStat> = entries
+            .iter()
+            .map(|((table, operation), entry)| {
+                let mut recent: Vec<Duration> = entry.recent.iter().copied().collect();
+                recent.sort_unstable();
+                // Nearest-rank percentile: the smallest sample covering 95%.
+                let rank = (recent.len() * 95).div_ceil(100).max(1);
+                QueryStat {
+                    table: table.clone(),
+                    operation: *operation,
+                    count: entry.count,
+                    total: entry.total,
+                    mean: Duration::from_nanos(
+                        (entry.total.as_nanos() / entry.count as u128) as u64,
+                    ),
+                    p95: recent[rank - 1],
+                    rows: entry.rows,
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| (&a.table, a.operation).cmp(&(&b.table, b.operation)))
+        });
+        stats
+    }
+
+    pub(crate) fn reset(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// Number of rows a query result counts for in `QueryStat::rows`.
+pub(crate) trait RowCount {
+    fn row_count(&self) -> u64;
+}
+
+impl<T> RowCount for Vec<T> {
+    fn row_count(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+impl<T> RowCount for Option<T> {
+    fn row_count(&self) -> u64 {
+        self.is_some() as u64
+    }
+}
+
+impl RowCount for PgRow {
+    fn row_count(&self) -> u64 {
+        1
+    }
+}
+
+impl RowCount for PgQueryResult {
+    fn row_count(&self) -> u64 {
+        self.rows_affected()
+    }
+}
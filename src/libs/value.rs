@@ -0,0 +1,309 @@
+use crate::orm::PgQuery;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::types::Oid;
+use sqlx::postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo};
+use sqlx::{Encode, Postgres, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use uuid::Uuid;
+
+/// A query parameter bound with its native Postgres type.
+///
+/// Anything that converts into `SqlValue` can be passed where a query
+/// builder expects a value, so `where_clause("age", ">", 18)` binds an
+/// integer instead of the string `"18"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Uuid(Uuid),
+    Timestamp(DateTime<Utc>),
+    NaiveTimestamp(NaiveDateTime),
+    Date(NaiveDate),
+    /// Bound as `INET`, so it compares with `INET` and `CIDR` columns.
+    Inet(IpAddr),
+    /// A list bound as one array parameter, as `where_in` compares with
+    /// `= ANY($n)`.
+    List(Vec<SqlValue>),
+}
+
+impl SqlValue {
+    pub(crate) fn bind(self, query: PgQuery<'_>) -> PgQuery<'_> {
+        match self {
+            SqlValue::Null => query.bind(None::<String>),
+            SqlValue::Bool(v) => query.bind(v),
+            SqlValue::Int(v) => query.bind(v),
+            SqlValue::Float(v) => query.bind(v),
+            SqlValue::Text(v) => query.bind(v),
+            SqlValue::Uuid(v) => query.bind(v),
+            SqlValue::Timestamp(v) => query.bind(v),
+            SqlValue::NaiveTimestamp(v) => query.bind(v),
+            SqlValue::Date(v) => query.bind(v),
+            SqlValue::Inet(v) => query.bind(Inet(v)),
+            SqlValue::List(items) => bind_list(query, items),
+        }
+    }
+
+    /// The value as Postgres parses it from text; `None` for NULL and
+    /// nested lists.
+    fn text(&self) -> Option<String> {
+        match self {
+            SqlValue::Null | SqlValue::List(_) => None,
+            SqlValue::Bool(v) => Some(v.to_string()),
+            SqlValue::Int(v) => Some(v.to_string()),
+            SqlValue::Float(v) => Some(v.to_string()),
+            SqlValue::Text(v) => Some(v.clone()),
+            SqlValue::Uuid(v) => Some(v.to_string()),
+            SqlValue::Timestamp(v) => Some(v.to_rfc3339()),
+            SqlValue::NaiveTimestamp(v) => Some(v.to_string()),
+            SqlValue::Date(v) => Some(v.to_string()),
+            SqlValue::Inet(v) => Some(v.to_string()),
+        }
+    }
+
+    /// The value bound as text, for a placeholder cast to the column's type.
+    pub(crate) fn into_text(self) -> Self {
+        self.text().map_or(SqlValue::Null, SqlValue::Text)
+    }
+
+    /// Converts a JSON filter value the way `bind_json` binds it: strings
+    /// and numbers as text, booleans natively, anything else as NULL.
+    pub(crate) fn from_json(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(s) => SqlValue::Text(s),
+            serde_json::Value::Number(n) => SqlValue::Text(n.to_string()),
+            serde_json::Value::Bool(b) => SqlValue::Bool(b),
+            _ => SqlValue::Null,
+        }
+    }
+
+    /// Converts a record's key as it serializes to JSON. Unlike
+    /// [`from_json`](Self::from_json), integers stay integers so they
+    /// compare with integer key columns of unregistered tables too.
+    pub(crate) fn from_key(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => SqlValue::Int(i),
+                None => n.as_f64().map_or(SqlValue::Null, SqlValue::Float),
+            },
+            other => SqlValue::from_json(other),
+        }
+    }
+}
+
+/// Binds `items` as an array of their common type, e.g. `BIGINT[]` for
+/// integers. Lists mixing types or holding NULLs are bound as `TEXT[]`.
+fn bind_list(query: PgQuery<'_>, items: Vec<SqlValue>) -> PgQuery<'_> {
+    fn all<T>(items: &[SqlValue], get: impl Fn(&SqlValue) -> Option<T>) -> Option<Vec<T>> {
+        items.iter().map(get).collect()
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Int(v) => Some(*v),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Float(v) => Some(*v),
+        SqlValue::Int(v) => Some(*v as f64),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Bool(v) => Some(*v),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Uuid(v) => Some(*v),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Timestamp(v) => Some(*v),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::NaiveTimestamp(v) => Some(*v),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Date(v) => Some(*v),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    if let Some(v) = all(&items, |i| match i {
+        SqlValue::Inet(v) => Some(Inet(*v)),
+        _ => None,
+    }) {
+        return query.bind(v);
+    }
+    query.bind(items.iter().map(SqlValue::text).collect::<Vec<_>>())
+}
+
+/// An address bound in `INET`'s binary format, which `sqlx` only encodes
+/// with an extra network crate enabled.
+struct Inet(IpAddr);
+
+impl Type<Postgres> for Inet {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_oid(Oid(869))
+    }
+}
+
+impl PgHasArrayType for Inet {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_oid(Oid(1041))
+    }
+}
+
+impl Encode<'_, Postgres> for Inet {
+    /// Family, prefix length, CIDR flag and address length, then the
+    /// address bytes.
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match self.0 {
+            IpAddr::V4(ip) => {
+                buf.extend_from_slice(&[2, 32, 0, 4]);
+                buf.extend_from_slice(&ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                buf.extend_from_slice(&[3, 128, 0, 16]);
+                buf.extend_from_slice(&ip.octets());
+            }
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl From<bool> for SqlValue {
+    fn from(v: bool) -> Self {
+        SqlValue::Bool(v)
+    }
+}
+
+impl From<i16> for SqlValue {
+    fn from(v: i16) -> Self {
+        SqlValue::Int(v.into())
+    }
+}
+
+impl From<i32> for SqlValue {
+    fn from(v: i32) -> Self {
+        SqlValue::Int(v.into())
+    }
+}
+
+impl From<i64> for SqlValue {
+    fn from(v: i64) -> Self {
+        SqlValue::Int(v)
+    }
+}
+
+impl From<u32> for SqlValue {
+    fn from(v: u32) -> Self {
+        SqlValue::Int(v.into())
+    }
+}
+
+impl From<f32> for SqlValue {
+    fn from(v: f32) -> Self {
+        SqlValue::Float(v.into())
+    }
+}
+
+impl From<f64> for SqlValue {
+    fn from(v: f64) -> Self {
+        SqlValue::Float(v)
+    }
+}
+
+impl From<&str> for SqlValue {
+    fn from(v: &str) -> Self {
+        SqlValue::Text(v.to_string())
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(v: String) -> Self {
+        SqlValue::Text(v)
+    }
+}
+
+impl From<&String> for SqlValue {
+    fn from(v: &String) -> Self {
+        SqlValue::Text(v.clone())
+    }
+}
+
+impl From<Uuid> for SqlValue {
+    fn from(v: Uuid) -> Self {
+        SqlValue::Uuid(v)
+    }
+}
+
+impl From<DateTime<Utc>> for SqlValue {
+    fn from(v: DateTime<Utc>) -> Self {
+        SqlValue::Timestamp(v)
+    }
+}
+
+impl From<NaiveDateTime> for SqlValue {
+    fn from(v: NaiveDateTime) -> Self {
+        SqlValue::NaiveTimestamp(v)
+    }
+}
+
+impl From<NaiveDate> for SqlValue {
+    fn from(v: NaiveDate) -> Self {
+        SqlValue::Date(v)
+    }
+}
+
+impl From<IpAddr> for SqlValue {
+    fn from(v: IpAddr) -> Self {
+        SqlValue::Inet(v)
+    }
+}
+
+impl From<Ipv4Addr> for SqlValue {
+    fn from(v: Ipv4Addr) -> Self {
+        SqlValue::Inet(v.into())
+    }
+}
+
+impl From<Ipv6Addr> for SqlValue {
+    fn from(v: Ipv6Addr) -> Self {
+        SqlValue::Inet(v.into())
+    }
+}
+
+impl<T> From<Option<T>> for SqlValue
+where
+    T: Into<SqlValue>,
+{
+    fn from(v: Option<T>) -> Self {
+        v.map_or(SqlValue::Null, Into::into)
+    }
+}
+
+/// A list, e.g. the values of `Op::In`.
+impl<T> From<Vec<T>> for SqlValue
+where
+    T: Into<SqlValue>,
+{
+    fn from(v: Vec<T>) -> Self {
+        SqlValue::List(v.into_iter().map(Into::into).collect())
+    }
+}
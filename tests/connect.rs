@@ -0,0 +1,45 @@
+//! Connection tests. They need a database and are skipped unless
+//! `DATABASE_URL` is set, e.g. `postgres://postgres@localhost:5432/postgres`.
+
+use slintrust::*;
+
+fn database_url() -> Option<String> {
+    std::env::var("DATABASE_URL").ok()
+}
+
+#[tokio::test]
+async fn handles_derived_before_connect_share_the_pool() {
+    let Some(url) = database_url() else {
+        return;
+    };
+    let mut orm = OrmStruct::new(url, Vec::new());
+    let actor = orm.with_actor("alice");
+    let optioned = orm.clone().with_options(OrmOptions::new());
+    orm.grant("public", &["nothing"], &[Privilege::Select])
+        .unwrap();
+
+    orm.connect().await.unwrap();
+
+    assert!(orm.is_connected());
+    assert!(actor.is_connected());
+    assert!(optioned.is_connected());
+    actor.ping().await.unwrap();
+    optioned.ping().await.unwrap();
+}
+
+#[tokio::test]
+async fn health_checks_fail_without_a_pool_instead_of_panicking() {
+    let orm = OrmStruct::new("postgres://localhost/app".into(), Vec::new());
+    assert!(matches!(orm.ping().await, Err(sqlx::Error::PoolClosed)));
+    assert_eq!(orm.pool_status(), None);
+
+    let Some(url) = database_url() else {
+        return;
+    };
+    let orm = OrmStruct::new(url, Vec::new());
+    orm.connect().await.unwrap();
+    orm.ping().await.unwrap();
+    assert!(orm.pool_status().is_some_and(|s| s.size >= 1));
+    orm.close().await;
+    assert!(matches!(orm.ping().await, Err(sqlx::Error::PoolClosed)));
+}